@@ -0,0 +1,788 @@
+//! Build ABC (ActionScript Byte Code) files for `DoAbc` tags.
+//!
+//! The structures follow the layout of the ABC format: references to constant pool entries, methods, metadata and
+//! classes are indices. The constant pool entries start at index 1 (index 0 is the implicit "any" or empty entry),
+//! while the other lists start at index 0. Method bodies hold the raw bytecode; use `emit_u30` and `emit_s24` to
+//! encode instruction operands.
+
+use crate::basic_data_types::emit_leb128_u32;
+use crate::primitives::{emit_le_u16, emit_u8};
+use std::io;
+use swf_types as ast;
+
+/// Largest value of the `u30` encoding.
+pub const MAX_U30: u32 = (1 << 30) - 1;
+
+/// `DoAbc` flag deferring the execution of the scripts until they are needed.
+pub const DO_ABC_LAZY_INITIALIZE: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AbcFile {
+  pub minor_version: u16,
+  pub major_version: u16,
+  pub constant_pool: ConstantPool,
+  pub methods: Vec<Method>,
+  pub metadata: Vec<Metadata>,
+  /// Instance part of each class; it must have the same length as `classes`.
+  pub instances: Vec<Instance>,
+  pub classes: Vec<Class>,
+  pub scripts: Vec<Script>,
+  pub method_bodies: Vec<MethodBody>,
+}
+
+impl Default for AbcFile {
+  /// Empty file with the version used by Flash Player 9 and above (46.16).
+  fn default() -> Self {
+    Self {
+      minor_version: 16,
+      major_version: 46,
+      constant_pool: ConstantPool::default(),
+      methods: Vec::new(),
+      metadata: Vec::new(),
+      instances: Vec::new(),
+      classes: Vec::new(),
+      scripts: Vec::new(),
+      method_bodies: Vec::new(),
+    }
+  }
+}
+
+/// Constant pool, without the implicit entry at index 0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConstantPool {
+  pub ints: Vec<i32>,
+  pub uints: Vec<u32>,
+  pub doubles: Vec<f64>,
+  pub strings: Vec<String>,
+  pub namespaces: Vec<Namespace>,
+  /// Namespace sets, as lists of namespace indices.
+  pub namespace_sets: Vec<Vec<u32>>,
+  pub multinames: Vec<Multiname>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NamespaceKind {
+  Namespace,
+  PackageNamespace,
+  PackageInternalNamespace,
+  ProtectedNamespace,
+  ExplicitNamespace,
+  StaticProtectedNamespace,
+  PrivateNamespace,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Namespace {
+  pub kind: NamespaceKind,
+  /// String index.
+  pub name: u32,
+}
+
+/// Multiname; `name` fields are string indices and `namespace` fields namespace indices. The variants ending with
+/// `A` name attributes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Multiname {
+  QName {
+    namespace: u32,
+    name: u32,
+  },
+  QNameA {
+    namespace: u32,
+    name: u32,
+  },
+  RtqName {
+    name: u32,
+  },
+  RtqNameA {
+    name: u32,
+  },
+  RtqNameL,
+  RtqNameLA,
+  Multiname {
+    name: u32,
+    namespace_set: u32,
+  },
+  MultinameA {
+    name: u32,
+    namespace_set: u32,
+  },
+  MultinameL {
+    namespace_set: u32,
+  },
+  MultinameLA {
+    namespace_set: u32,
+  },
+  /// Parameterized type (such as `Vector.<int>`): `name` and `parameters` are multiname indices.
+  TypeName {
+    name: u32,
+    parameters: Vec<u32>,
+  },
+}
+
+/// Kind of a constant value, selecting the constant pool used by its index.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ConstantKind {
+  Int,
+  Uint,
+  Double,
+  Utf8,
+  True,
+  False,
+  Null,
+  Undefined,
+  Namespace(NamespaceKind),
+}
+
+/// Default value of an optional parameter or slot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConstantValue {
+  pub kind: ConstantKind,
+  /// Index in the constant pool of the kind, which must not be 0.
+  ///
+  /// It is ignored for `True`, `False`, `Null` and `Undefined`: like compilers, the emitter writes their kind code
+  /// instead, so slots reading the kind only for non-zero indices stay readable.
+  pub index: u32,
+}
+
+/// Method signature.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Method {
+  /// Multiname indices of the parameter types (0 for any type).
+  pub parameter_types: Vec<u32>,
+  /// Multiname index of the return type (0 for any type).
+  pub return_type: u32,
+  /// String index.
+  pub name: u32,
+  pub need_arguments: bool,
+  pub need_activation: bool,
+  pub need_rest: bool,
+  pub set_dxns: bool,
+  /// Default values of the last parameters.
+  pub optional_parameters: Option<Vec<ConstantValue>>,
+  /// String indices of the parameter names.
+  pub parameter_names: Option<Vec<u32>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Metadata {
+  /// String index.
+  pub name: u32,
+  pub items: Vec<MetadataItem>,
+}
+
+/// Metadata entry; the key is the string index 0 for entries without key.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MetadataItem {
+  pub key: u32,
+  pub value: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Instance {
+  /// Multiname index.
+  pub name: u32,
+  /// Multiname index (0 for no super class).
+  pub super_name: u32,
+  pub is_sealed: bool,
+  pub is_final: bool,
+  pub is_interface: bool,
+  /// Namespace index of the protected members.
+  pub protected_namespace: Option<u32>,
+  /// Multiname indices.
+  pub interfaces: Vec<u32>,
+  /// Method index of the constructor.
+  pub initializer: u32,
+  pub traits: Vec<Trait>,
+}
+
+/// Static part of a class.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Class {
+  /// Method index of the static initializer.
+  pub initializer: u32,
+  pub traits: Vec<Trait>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Script {
+  /// Method index of the script initializer.
+  pub initializer: u32,
+  pub traits: Vec<Trait>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trait {
+  /// Multiname index.
+  pub name: u32,
+  pub kind: TraitKind,
+  pub is_final: bool,
+  pub is_override: bool,
+  /// Metadata indices.
+  pub metadata: Vec<u32>,
+}
+
+/// Trait data; a slot id or dispatch id of 0 lets the runtime assign it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraitKind {
+  Slot {
+    slot_id: u32,
+    type_name: u32,
+    value: Option<ConstantValue>,
+  },
+  Const {
+    slot_id: u32,
+    type_name: u32,
+    value: Option<ConstantValue>,
+  },
+  Method {
+    disp_id: u32,
+    method: u32,
+  },
+  Getter {
+    disp_id: u32,
+    method: u32,
+  },
+  Setter {
+    disp_id: u32,
+    method: u32,
+  },
+  Class {
+    slot_id: u32,
+    class: u32,
+  },
+  Function {
+    slot_id: u32,
+    function: u32,
+  },
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MethodBody {
+  /// Method index.
+  pub method: u32,
+  pub max_stack: u32,
+  pub local_count: u32,
+  pub init_scope_depth: u32,
+  pub max_scope_depth: u32,
+  pub code: Vec<u8>,
+  pub exceptions: Vec<Exception>,
+  /// Traits of the activation object.
+  pub traits: Vec<Trait>,
+}
+
+/// Exception handler: `from`, `to` and `target` are byte offsets in the code.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Exception {
+  pub from: u32,
+  pub to: u32,
+  pub target: u32,
+  /// Multiname index of the caught type (0 for any type).
+  pub exception_type: u32,
+  /// Multiname index of the catch variable (0 for none).
+  pub variable_name: u32,
+}
+
+/// Builds a `DoAbc` tag with a header (`DoAbc2` tag) from the ABC file.
+pub fn build_do_abc(file: &AbcFile, name: &str, flags: u32) -> io::Result<ast::tags::DoAbc> {
+  let mut data: Vec<u8> = Vec::new();
+  emit_abc_file(&mut data, file)?;
+  Ok(ast::tags::DoAbc {
+    header: Some(ast::AbcHeader {
+      flags,
+      name: String::from(name),
+    }),
+    data,
+  })
+}
+
+pub fn emit_abc_file<W: io::Write>(writer: &mut W, value: &AbcFile) -> io::Result<()> {
+  if value.instances.len() != value.classes.len() {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "ABC files must have as many instances as classes",
+    ));
+  }
+  emit_le_u16(writer, value.minor_version)?;
+  emit_le_u16(writer, value.major_version)?;
+  emit_constant_pool(writer, &value.constant_pool)?;
+  emit_list(writer, &value.methods, emit_method)?;
+  emit_list(writer, &value.metadata, emit_metadata)?;
+  emit_list(writer, &value.instances, emit_instance)?;
+  for class in &value.classes {
+    emit_u30(writer, class.initializer)?;
+    emit_list(writer, &class.traits, emit_trait)?;
+  }
+  emit_list(writer, &value.scripts, emit_script)?;
+  emit_list(writer, &value.method_bodies, emit_method_body)
+}
+
+/// Emits an unsigned integer using 1 to 5 bytes, with 7 bits per byte (least significant first).
+pub fn emit_u30<W: io::Write>(writer: &mut W, value: u32) -> io::Result<()> {
+  if value > MAX_U30 {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("value does not fit in u30: {}", value),
+    ));
+  }
+  emit_leb128_u32(writer, value)
+}
+
+/// Emits a signed 32-bit integer with the variable length encoding of `u30` (negative values use 5 bytes).
+pub fn emit_s32<W: io::Write>(writer: &mut W, value: i32) -> io::Result<()> {
+  emit_leb128_u32(writer, value as u32)
+}
+
+/// Emits a signed 24-bit integer (little-endian), used by branch instruction offsets.
+pub fn emit_s24<W: io::Write>(writer: &mut W, value: i32) -> io::Result<()> {
+  if !(-(1 << 23)..(1 << 23)).contains(&value) {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("value does not fit in s24: {}", value),
+    ));
+  }
+  writer.write_all(&value.to_le_bytes()[..3])
+}
+
+fn emit_len<W: io::Write>(writer: &mut W, len: usize) -> io::Result<()> {
+  emit_u30(writer, u32::try_from(len).unwrap_or(u32::MAX))
+}
+
+fn emit_list<W: io::Write, T>(
+  writer: &mut W,
+  values: &[T],
+  emit_item: fn(&mut W, &T) -> io::Result<()>,
+) -> io::Result<()> {
+  emit_len(writer, values.len())?;
+  for value in values {
+    emit_item(writer, value)?;
+  }
+  Ok(())
+}
+
+/// Emits a constant pool list, whose count includes the implicit entry at index 0 (except for empty lists).
+fn emit_pool_list<W: io::Write, T>(
+  writer: &mut W,
+  values: &[T],
+  emit_item: fn(&mut W, &T) -> io::Result<()>,
+) -> io::Result<()> {
+  emit_len(writer, if values.is_empty() { 0 } else { values.len() + 1 })?;
+  for value in values {
+    emit_item(writer, value)?;
+  }
+  Ok(())
+}
+
+fn emit_u30_list<W: io::Write>(writer: &mut W, values: &[u32]) -> io::Result<()> {
+  emit_list(writer, values, |writer, value| emit_u30(writer, *value))
+}
+
+fn emit_constant_pool<W: io::Write>(writer: &mut W, value: &ConstantPool) -> io::Result<()> {
+  emit_pool_list(writer, &value.ints, |writer, value| emit_s32(writer, *value))?;
+  emit_pool_list(writer, &value.uints, |writer, value| emit_leb128_u32(writer, *value))?;
+  emit_pool_list(writer, &value.doubles, |writer, value| {
+    writer.write_all(&value.to_le_bytes())
+  })?;
+  emit_pool_list(writer, &value.strings, |writer, value| {
+    emit_len(writer, value.len())?;
+    writer.write_all(value.as_bytes())
+  })?;
+  emit_pool_list(writer, &value.namespaces, |writer, value| {
+    emit_u8(writer, get_namespace_kind_code(value.kind))?;
+    emit_u30(writer, value.name)
+  })?;
+  emit_pool_list(writer, &value.namespace_sets, |writer, value| {
+    emit_u30_list(writer, value)
+  })?;
+  emit_pool_list(writer, &value.multinames, emit_multiname)
+}
+
+fn get_namespace_kind_code(value: NamespaceKind) -> u8 {
+  match value {
+    NamespaceKind::Namespace => 0x08,
+    NamespaceKind::PackageNamespace => 0x16,
+    NamespaceKind::PackageInternalNamespace => 0x17,
+    NamespaceKind::ProtectedNamespace => 0x18,
+    NamespaceKind::ExplicitNamespace => 0x19,
+    NamespaceKind::StaticProtectedNamespace => 0x1a,
+    NamespaceKind::PrivateNamespace => 0x05,
+  }
+}
+
+fn get_constant_kind_code(value: ConstantKind) -> u8 {
+  match value {
+    ConstantKind::Int => 0x03,
+    ConstantKind::Uint => 0x04,
+    ConstantKind::Double => 0x06,
+    ConstantKind::Utf8 => 0x01,
+    ConstantKind::True => 0x0b,
+    ConstantKind::False => 0x0a,
+    ConstantKind::Null => 0x0c,
+    ConstantKind::Undefined => 0x00,
+    ConstantKind::Namespace(kind) => get_namespace_kind_code(kind),
+  }
+}
+
+fn emit_multiname<W: io::Write>(writer: &mut W, value: &Multiname) -> io::Result<()> {
+  match *value {
+    Multiname::QName { namespace, name } | Multiname::QNameA { namespace, name } => {
+      emit_u8(
+        writer,
+        if let Multiname::QName { .. } = value {
+          0x07
+        } else {
+          0x0d
+        },
+      )?;
+      emit_u30(writer, namespace)?;
+      emit_u30(writer, name)
+    }
+    Multiname::RtqName { name } => {
+      emit_u8(writer, 0x0f)?;
+      emit_u30(writer, name)
+    }
+    Multiname::RtqNameA { name } => {
+      emit_u8(writer, 0x10)?;
+      emit_u30(writer, name)
+    }
+    Multiname::RtqNameL => emit_u8(writer, 0x11),
+    Multiname::RtqNameLA => emit_u8(writer, 0x12),
+    Multiname::Multiname { name, namespace_set } | Multiname::MultinameA { name, namespace_set } => {
+      emit_u8(
+        writer,
+        if let Multiname::Multiname { .. } = value {
+          0x09
+        } else {
+          0x0e
+        },
+      )?;
+      emit_u30(writer, name)?;
+      emit_u30(writer, namespace_set)
+    }
+    Multiname::MultinameL { namespace_set } => {
+      emit_u8(writer, 0x1b)?;
+      emit_u30(writer, namespace_set)
+    }
+    Multiname::MultinameLA { namespace_set } => {
+      emit_u8(writer, 0x1c)?;
+      emit_u30(writer, namespace_set)
+    }
+    Multiname::TypeName { name, ref parameters } => {
+      emit_u8(writer, 0x1d)?;
+      emit_u30(writer, name)?;
+      emit_u30_list(writer, parameters)
+    }
+  }
+}
+
+fn emit_constant_value<W: io::Write>(writer: &mut W, value: &ConstantValue) -> io::Result<()> {
+  emit_u30(writer, get_constant_value_index(value)?)?;
+  emit_u8(writer, get_constant_kind_code(value.kind))
+}
+
+/// Returns the index written for the constant value: the kind code for the constants without pool.
+fn get_constant_value_index(value: &ConstantValue) -> io::Result<u32> {
+  match value.kind {
+    ConstantKind::True | ConstantKind::False | ConstantKind::Null | ConstantKind::Undefined => {
+      Ok(u32::from(get_constant_kind_code(value.kind)))
+    }
+    _ if value.index == 0 => Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "constant value index must not be 0",
+    )),
+    _ => Ok(value.index),
+  }
+}
+
+fn emit_method<W: io::Write>(writer: &mut W, value: &Method) -> io::Result<()> {
+  if let Some(ref names) = value.parameter_names {
+    if names.len() != value.parameter_types.len() {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "method parameter names must match the parameter types",
+      ));
+    }
+  }
+  emit_len(writer, value.parameter_types.len())?;
+  emit_u30(writer, value.return_type)?;
+  for parameter_type in &value.parameter_types {
+    emit_u30(writer, *parameter_type)?;
+  }
+  emit_u30(writer, value.name)?;
+  #[allow(clippy::identity_op)]
+  let flags: u8 = 0
+    | (if value.need_arguments { 1 << 0 } else { 0 })
+    | (if value.need_activation { 1 << 1 } else { 0 })
+    | (if value.need_rest { 1 << 2 } else { 0 })
+    | (if value.optional_parameters.is_some() { 1 << 3 } else { 0 })
+    | (if value.set_dxns { 1 << 6 } else { 0 })
+    | (if value.parameter_names.is_some() { 1 << 7 } else { 0 });
+  emit_u8(writer, flags)?;
+  if let Some(ref options) = value.optional_parameters {
+    emit_list(writer, options, emit_constant_value)?;
+  }
+  if let Some(ref names) = value.parameter_names {
+    for name in names {
+      emit_u30(writer, *name)?;
+    }
+  }
+  Ok(())
+}
+
+fn emit_metadata<W: io::Write>(writer: &mut W, value: &Metadata) -> io::Result<()> {
+  // Flash Player reads all the keys, then all the values (unlike the interleaved layout of the specification).
+  emit_u30(writer, value.name)?;
+  emit_len(writer, value.items.len())?;
+  for item in &value.items {
+    emit_u30(writer, item.key)?;
+  }
+  for item in &value.items {
+    emit_u30(writer, item.value)?;
+  }
+  Ok(())
+}
+
+fn emit_instance<W: io::Write>(writer: &mut W, value: &Instance) -> io::Result<()> {
+  emit_u30(writer, value.name)?;
+  emit_u30(writer, value.super_name)?;
+  #[allow(clippy::identity_op)]
+  let flags: u8 = 0
+    | (if value.is_sealed { 1 << 0 } else { 0 })
+    | (if value.is_final { 1 << 1 } else { 0 })
+    | (if value.is_interface { 1 << 2 } else { 0 })
+    | (if value.protected_namespace.is_some() { 1 << 3 } else { 0 });
+  emit_u8(writer, flags)?;
+  if let Some(protected_namespace) = value.protected_namespace {
+    emit_u30(writer, protected_namespace)?;
+  }
+  emit_u30_list(writer, &value.interfaces)?;
+  emit_u30(writer, value.initializer)?;
+  emit_list(writer, &value.traits, emit_trait)
+}
+
+fn emit_script<W: io::Write>(writer: &mut W, value: &Script) -> io::Result<()> {
+  emit_u30(writer, value.initializer)?;
+  emit_list(writer, &value.traits, emit_trait)
+}
+
+fn emit_trait<W: io::Write>(writer: &mut W, value: &Trait) -> io::Result<()> {
+  emit_u30(writer, value.name)?;
+  let kind_code: u8 = match value.kind {
+    TraitKind::Slot { .. } => 0,
+    TraitKind::Method { .. } => 1,
+    TraitKind::Getter { .. } => 2,
+    TraitKind::Setter { .. } => 3,
+    TraitKind::Class { .. } => 4,
+    TraitKind::Function { .. } => 5,
+    TraitKind::Const { .. } => 6,
+  };
+  #[allow(clippy::identity_op)]
+  let attributes: u8 = 0
+    | (if value.is_final { 1 << 0 } else { 0 })
+    | (if value.is_override { 1 << 1 } else { 0 })
+    | (if !value.metadata.is_empty() { 1 << 2 } else { 0 });
+  emit_u8(writer, kind_code | (attributes << 4))?;
+  match value.kind {
+    TraitKind::Slot {
+      slot_id,
+      type_name,
+      ref value,
+    }
+    | TraitKind::Const {
+      slot_id,
+      type_name,
+      ref value,
+    } => {
+      emit_u30(writer, slot_id)?;
+      emit_u30(writer, type_name)?;
+      // The kind is only present for non-zero indices: `Undefined` is the same as no value
+      match value {
+        Some(value) if get_constant_value_index(value)? != 0 => emit_constant_value(writer, value)?,
+        _ => emit_u30(writer, 0)?,
+      }
+    }
+    TraitKind::Method { disp_id, method }
+    | TraitKind::Getter { disp_id, method }
+    | TraitKind::Setter { disp_id, method } => {
+      emit_u30(writer, disp_id)?;
+      emit_u30(writer, method)?;
+    }
+    TraitKind::Class { slot_id, class } => {
+      emit_u30(writer, slot_id)?;
+      emit_u30(writer, class)?;
+    }
+    TraitKind::Function { slot_id, function } => {
+      emit_u30(writer, slot_id)?;
+      emit_u30(writer, function)?;
+    }
+  }
+  if !value.metadata.is_empty() {
+    emit_u30_list(writer, &value.metadata)?;
+  }
+  Ok(())
+}
+
+fn emit_method_body<W: io::Write>(writer: &mut W, value: &MethodBody) -> io::Result<()> {
+  emit_u30(writer, value.method)?;
+  emit_u30(writer, value.max_stack)?;
+  emit_u30(writer, value.local_count)?;
+  emit_u30(writer, value.init_scope_depth)?;
+  emit_u30(writer, value.max_scope_depth)?;
+  emit_len(writer, value.code.len())?;
+  writer.write_all(&value.code)?;
+  emit_list(writer, &value.exceptions, |writer, value| {
+    emit_u30(writer, value.from)?;
+    emit_u30(writer, value.to)?;
+    emit_u30(writer, value.target)?;
+    emit_u30(writer, value.exception_type)?;
+    emit_u30(writer, value.variable_name)
+  })?;
+  emit_list(writer, &value.traits, emit_trait)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn to_bytes<F: Fn(&mut Vec<u8>) -> io::Result<()>>(emit: F) -> io::Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    emit(&mut bytes)?;
+    Ok(bytes)
+  }
+
+  #[test]
+  fn test_emit_variable_length_integers() {
+    assert_eq!(to_bytes(|w| emit_u30(w, 0x7f)).unwrap(), vec![0x7f]);
+    assert_eq!(to_bytes(|w| emit_u30(w, 0x80)).unwrap(), vec![0x80, 0x01]);
+    assert_eq!(
+      to_bytes(|w| emit_u30(w, MAX_U30)).unwrap(),
+      vec![0xff, 0xff, 0xff, 0xff, 0x03]
+    );
+    assert!(emit_u30(&mut Vec::new(), MAX_U30 + 1).is_err());
+    assert_eq!(
+      to_bytes(|w| emit_s32(w, -1)).unwrap(),
+      vec![0xff, 0xff, 0xff, 0xff, 0x0f]
+    );
+    assert_eq!(to_bytes(|w| emit_s24(w, -2)).unwrap(), vec![0xfe, 0xff, 0xff]);
+    assert!(emit_s24(&mut Vec::new(), 1 << 23).is_err());
+  }
+
+  #[test]
+  fn test_build_do_abc() {
+    let file = AbcFile {
+      constant_pool: ConstantPool {
+        strings: vec![String::from("Test")],
+        namespaces: vec![Namespace {
+          kind: NamespaceKind::PackageNamespace,
+          name: 1,
+        }],
+        multinames: vec![Multiname::QName { namespace: 1, name: 1 }],
+        ..ConstantPool::default()
+      },
+      methods: vec![Method::default()],
+      scripts: vec![Script {
+        initializer: 0,
+        traits: vec![Trait {
+          name: 1,
+          kind: TraitKind::Slot {
+            slot_id: 0,
+            type_name: 0,
+            value: Some(ConstantValue {
+              kind: ConstantKind::True,
+              index: 0,
+            }),
+          },
+          is_final: false,
+          is_override: false,
+          metadata: Vec::new(),
+        }],
+      }],
+      method_bodies: vec![MethodBody {
+        method: 0,
+        max_stack: 1,
+        local_count: 1,
+        init_scope_depth: 0,
+        max_scope_depth: 1,
+        // getlocal0, pushscope, returnvoid
+        code: vec![0xd0, 0x30, 0x47],
+        exceptions: Vec::new(),
+        traits: Vec::new(),
+      }],
+      ..AbcFile::default()
+    };
+    let tag = build_do_abc(&file, "test", DO_ABC_LAZY_INITIALIZE).unwrap();
+    assert_eq!(
+      tag.data,
+      vec![
+        16, 0, 46, 0, // Version
+        0, 0, 0, 2, 4, b'T', b'e', b's', b't', 2, 0x16, 1, 0, 2, 0x07, 1, 1, // Constant pool
+        1, 0, 0, 0, 0, // Methods
+        0, // Metadata
+        0, // Classes
+        1, 0, 1, 1, 0, 0, 0, 0x0b, 0x0b, // Scripts
+        1, 0, 1, 1, 0, 1, 3, 0xd0, 0x30, 0x47, 0, 0, // Method bodies
+      ]
+    );
+
+    let tag = ast::Tag::DoAbc(tag);
+    let bytes = crate::emit_tag(&tag, 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(tag));
+  }
+
+  #[test]
+  fn test_emit_constant_values() {
+    let slot = |kind: ConstantKind, index: u32| Trait {
+      name: 1,
+      kind: TraitKind::Slot {
+        slot_id: 0,
+        type_name: 0,
+        value: Some(ConstantValue { kind, index }),
+      },
+      is_final: false,
+      is_override: false,
+      metadata: Vec::new(),
+    };
+    assert_eq!(
+      to_bytes(|w| emit_trait(w, &slot(ConstantKind::Null, 0))).unwrap(),
+      vec![1, 0, 0, 0, 0x0c, 0x0c]
+    );
+    assert_eq!(
+      to_bytes(|w| emit_trait(w, &slot(ConstantKind::Undefined, 5))).unwrap(),
+      vec![1, 0, 0, 0, 0]
+    );
+    assert_eq!(
+      to_bytes(|w| emit_trait(w, &slot(ConstantKind::Utf8, 2))).unwrap(),
+      vec![1, 0, 0, 0, 2, 0x01]
+    );
+    assert!(emit_trait(&mut Vec::new(), &slot(ConstantKind::Int, 0)).is_err());
+
+    let method = Method {
+      parameter_types: vec![0, 0],
+      optional_parameters: Some(vec![
+        ConstantValue {
+          kind: ConstantKind::False,
+          index: 0,
+        },
+        ConstantValue {
+          kind: ConstantKind::Undefined,
+          index: 0,
+        },
+      ]),
+      ..Method::default()
+    };
+    assert_eq!(
+      to_bytes(|w| emit_method(w, &method)).unwrap(),
+      vec![2, 0, 0, 0, 0, 0x08, 2, 0x0a, 0x0a, 0, 0]
+    );
+  }
+
+  #[test]
+  fn test_emit_abc_file_rejects_missing_class() {
+    let file = AbcFile {
+      instances: vec![Instance::default()],
+      ..AbcFile::default()
+    };
+    assert!(emit_abc_file(&mut Vec::new(), &file).is_err());
+  }
+}
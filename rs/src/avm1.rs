@@ -0,0 +1,799 @@
+//! Assemble AVM1 (ActionScript 1 and 2) actions to bytes.
+//!
+//! The assembled bytes end with the `End` action and can be used as the `actions` of `DoAction`, `DoInitAction`,
+//! clip actions and button conditional actions.
+
+use crate::constant_pool::pool_constants;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Maximum number of entries of a constant pool.
+pub const MAX_CONSTANT_POOL_SIZE: usize = 65535;
+
+/// Branch target. Labels are placed with `Action::Label` and referenced by `Action::Jump` and `Action::If`.
+///
+/// Function bodies have their own label scope; `With` and `Try` bodies share the scope of the enclosing code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label(pub u32);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PushValue {
+  String(String),
+  Float(f32),
+  Null,
+  Undefined,
+  Register(u8),
+  Boolean(bool),
+  Float64(f64),
+  Sint32(i32),
+  /// Index in the current constant pool.
+  Constant(u16),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SendVarsMethod {
+  None,
+  Get,
+  Post,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Parameter {
+  /// Register receiving the argument, or `0` to store it in a variable.
+  pub register: u8,
+  pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DefineFunction2 {
+  pub name: String,
+  pub register_count: u8,
+  pub preload_this: bool,
+  pub suppress_this: bool,
+  pub preload_arguments: bool,
+  pub suppress_arguments: bool,
+  pub preload_super: bool,
+  pub suppress_super: bool,
+  pub preload_root: bool,
+  pub preload_parent: bool,
+  pub preload_global: bool,
+  pub parameters: Vec<Parameter>,
+  pub body: Vec<Action>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CatchTarget {
+  Variable(String),
+  Register(u8),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Catch {
+  pub target: CatchTarget,
+  pub body: Vec<Action>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Try {
+  pub body: Vec<Action>,
+  pub catch: Option<Catch>,
+  pub finally: Option<Vec<Action>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+  /// Pseudo-action marking a branch target; it does not produce any bytes.
+  Label(Label),
+  // SWF 3
+  NextFrame,
+  PreviousFrame,
+  Play,
+  Stop,
+  ToggleQuality,
+  StopSounds,
+  GotoFrame(u16),
+  GetUrl {
+    url: String,
+    target: String,
+  },
+  WaitForFrame {
+    frame: u16,
+    skip_count: u8,
+  },
+  SetTarget(String),
+  GotoLabel(String),
+  // SWF 4
+  Add,
+  Subtract,
+  Multiply,
+  Divide,
+  Equals,
+  Less,
+  And,
+  Or,
+  Not,
+  StringEquals,
+  StringLength,
+  StringExtract,
+  Pop,
+  ToInteger,
+  GetVariable,
+  SetVariable,
+  SetTarget2,
+  StringAdd,
+  GetProperty,
+  SetProperty,
+  CloneSprite,
+  RemoveSprite,
+  Trace,
+  StartDrag,
+  EndDrag,
+  StringLess,
+  RandomNumber,
+  MbStringLength,
+  CharToAscii,
+  AsciiToChar,
+  GetTime,
+  MbStringExtract,
+  MbCharToAscii,
+  MbAsciiToChar,
+  WaitForFrame2 {
+    skip_count: u8,
+  },
+  Push(Vec<PushValue>),
+  Jump(Label),
+  GetUrl2 {
+    method: SendVarsMethod,
+    load_target: bool,
+    load_variables: bool,
+  },
+  If(Label),
+  Call,
+  GotoFrame2 {
+    play: bool,
+    scene_bias: Option<u16>,
+  },
+  // SWF 5
+  Delete,
+  Delete2,
+  DefineLocal,
+  CallFunction,
+  Return,
+  Modulo,
+  NewObject,
+  DefineLocal2,
+  InitArray,
+  InitObject,
+  TypeOf,
+  TargetPath,
+  Enumerate,
+  Add2,
+  Less2,
+  Equals2,
+  ToNumber,
+  ToString,
+  PushDuplicate,
+  StackSwap,
+  GetMember,
+  SetMember,
+  Increment,
+  Decrement,
+  CallMethod,
+  NewMethod,
+  BitAnd,
+  BitOr,
+  BitXor,
+  BitLShift,
+  BitRShift,
+  BitURShift,
+  StoreRegister(u8),
+  ConstantPool(Vec<String>),
+  With(Vec<Action>),
+  DefineFunction {
+    name: String,
+    parameters: Vec<String>,
+    body: Vec<Action>,
+  },
+  // SWF 6
+  InstanceOf,
+  Enumerate2,
+  StrictEquals,
+  Greater,
+  StringGreater,
+  // SWF 7
+  Throw,
+  CastOp,
+  ImplementsOp,
+  Extends,
+  DefineFunction2(DefineFunction2),
+  Try(Try),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Avm1Error {
+  /// The action (identified by its code) requires a more recent SWF version.
+  UnsupportedAction {
+    code: u8,
+    min_version: u8,
+    swf_version: u8,
+  },
+  /// The push value (identified by its type code) requires a more recent SWF version.
+  UnsupportedPushValue {
+    type_code: u8,
+    min_version: u8,
+    swf_version: u8,
+  },
+  UnknownLabel(Label),
+  DuplicateLabel(Label),
+  /// The branch offset to the label does not fit in 16 bits.
+  BranchOutOfRange(Label),
+  /// The data or body of the action (identified by its code) exceeds 65535 bytes or items.
+  ActionTooLong(u8),
+  /// A string contains a null character.
+  InvalidString(String),
+}
+
+impl fmt::Display for Avm1Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Avm1Error::UnsupportedAction {
+        code,
+        min_version,
+        swf_version,
+      } => write!(
+        f,
+        "action 0x{:02x} requires SWF {} (target: SWF {})",
+        code, min_version, swf_version
+      ),
+      Avm1Error::UnsupportedPushValue {
+        type_code,
+        min_version,
+        swf_version,
+      } => write!(
+        f,
+        "push value type {} requires SWF {} (target: SWF {})",
+        type_code, min_version, swf_version
+      ),
+      Avm1Error::UnknownLabel(label) => write!(f, "unknown label: {}", label.0),
+      Avm1Error::DuplicateLabel(label) => write!(f, "duplicate label: {}", label.0),
+      Avm1Error::BranchOutOfRange(label) => write!(f, "branch to label {} is out of range", label.0),
+      Avm1Error::ActionTooLong(code) => write!(f, "action 0x{:02x} is too long", code),
+      Avm1Error::InvalidString(ref value) => write!(f, "string contains a null character: {:?}", value),
+    }
+  }
+}
+
+impl Error for Avm1Error {}
+
+/// Assembles actions for the provided SWF version, rejecting the actions and push values it does not support.
+///
+/// Branch offsets are resolved from the labels. The repeated strings are then moved to a constant pool with
+/// `constant_pool::pool_constants` when this reduces the size of the code: actions defining their own `ConstantPool`
+/// or pushing `PushValue::Constant` values are left unchanged.
+pub fn assemble(actions: &[Action], swf_version: u8) -> Result<Vec<u8>, Avm1Error> {
+  let assembler = Assembler { swf_version };
+  let mut out = assembler.assemble_scope(actions)?;
+  out.push(0);
+  Ok(pool_constants(&out, swf_version).unwrap_or(out))
+}
+
+struct Assembler {
+  swf_version: u8,
+}
+
+/// Code sharing the same labels.
+#[derive(Default)]
+struct Scope {
+  out: Vec<u8>,
+  labels: HashMap<Label, usize>,
+  /// Position of the 16-bit branch offsets to resolve.
+  branches: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+  fn assemble_scope(&self, actions: &[Action]) -> Result<Vec<u8>, Avm1Error> {
+    let mut scope = Scope::default();
+    self.emit_actions(&mut scope, actions)?;
+    for (position, label) in scope.branches.iter() {
+      let target = *scope.labels.get(label).ok_or(Avm1Error::UnknownLabel(*label))?;
+      let offset = target as i64 - (*position as i64 + 2);
+      let offset = i16::try_from(offset).map_err(|_| Avm1Error::BranchOutOfRange(*label))?;
+      scope.out[*position..*position + 2].copy_from_slice(&offset.to_le_bytes());
+    }
+    Ok(scope.out)
+  }
+
+  fn emit_actions(&self, scope: &mut Scope, actions: &[Action]) -> Result<(), Avm1Error> {
+    for action in actions {
+      self.emit_action(scope, action)?;
+    }
+    Ok(())
+  }
+
+  fn emit_action(&self, scope: &mut Scope, action: &Action) -> Result<(), Avm1Error> {
+    if let Action::Label(label) = action {
+      if scope.labels.insert(*label, scope.out.len()).is_some() {
+        return Err(Avm1Error::DuplicateLabel(*label));
+      }
+      return Ok(());
+    }
+
+    let (code, min_version) = get_action_code(action);
+    if self.swf_version < min_version {
+      return Err(Avm1Error::UnsupportedAction {
+        code,
+        min_version,
+        swf_version: self.swf_version,
+      });
+    }
+    scope.out.push(code);
+    if code < 0x80 {
+      return Ok(());
+    }
+
+    let mut data: Vec<u8> = Vec::new();
+    // Nested code emitted after the action record: inline bodies share the scope, functions have their own.
+    let mut inline_bodies: Vec<(&[Action], usize)> = Vec::new();
+    let mut function_body: Option<Vec<u8>> = None;
+    match action {
+      Action::GotoFrame(frame) => write_u16(&mut data, *frame),
+      Action::GetUrl { ref url, ref target } => {
+        write_string(&mut data, url)?;
+        write_string(&mut data, target)?;
+      }
+      Action::WaitForFrame { frame, skip_count } => {
+        write_u16(&mut data, *frame);
+        data.push(*skip_count);
+      }
+      Action::SetTarget(ref target) => write_string(&mut data, target)?,
+      Action::GotoLabel(ref label) => write_string(&mut data, label)?,
+      Action::WaitForFrame2 { skip_count } => data.push(*skip_count),
+      Action::Push(ref values) => {
+        for value in values {
+          self.write_push_value(&mut data, value)?;
+        }
+      }
+      Action::Jump(label) | Action::If(label) => {
+        scope.branches.push((scope.out.len() + 2, *label));
+        write_u16(&mut data, 0);
+      }
+      Action::GetUrl2 {
+        method,
+        load_target,
+        load_variables,
+      } => {
+        let method_code: u8 = match method {
+          SendVarsMethod::None => 0,
+          SendVarsMethod::Get => 1,
+          SendVarsMethod::Post => 2,
+        };
+        #[allow(clippy::identity_op)]
+        let flags: u8 = 0
+          | (if *load_variables { 1 << 0 } else { 0 })
+          | (if *load_target { 1 << 1 } else { 0 })
+          // Skip bits [2, 5]
+          | (method_code << 6);
+        data.push(flags);
+      }
+      Action::Call => {}
+      Action::GotoFrame2 { play, scene_bias } => {
+        #[allow(clippy::identity_op)]
+        let flags: u8 = 0 | (if *play { 1 << 0 } else { 0 }) | (if scene_bias.is_some() { 1 << 1 } else { 0 });
+        data.push(flags);
+        if let Some(scene_bias) = scene_bias {
+          write_u16(&mut data, *scene_bias);
+        }
+      }
+      Action::StoreRegister(register) => data.push(*register),
+      Action::ConstantPool(ref pool) => {
+        write_u16(&mut data, get_length(pool.len(), code)?);
+        for value in pool {
+          write_string(&mut data, value)?;
+        }
+      }
+      Action::With(ref body) => {
+        inline_bodies.push((body.as_slice(), data.len()));
+        write_u16(&mut data, 0);
+      }
+      Action::DefineFunction {
+        ref name,
+        ref parameters,
+        ref body,
+      } => {
+        write_string(&mut data, name)?;
+        write_u16(&mut data, get_length(parameters.len(), code)?);
+        for parameter in parameters {
+          write_string(&mut data, parameter)?;
+        }
+        let body = self.assemble_scope(body)?;
+        write_u16(&mut data, get_length(body.len(), code)?);
+        function_body = Some(body);
+      }
+      Action::DefineFunction2(ref function) => {
+        write_string(&mut data, &function.name)?;
+        write_u16(&mut data, get_length(function.parameters.len(), code)?);
+        data.push(function.register_count);
+        #[allow(clippy::identity_op)]
+        let flags: u16 = 0
+          | (if function.preload_this { 1 << 0 } else { 0 })
+          | (if function.suppress_this { 1 << 1 } else { 0 })
+          | (if function.preload_arguments { 1 << 2 } else { 0 })
+          | (if function.suppress_arguments { 1 << 3 } else { 0 })
+          | (if function.preload_super { 1 << 4 } else { 0 })
+          | (if function.suppress_super { 1 << 5 } else { 0 })
+          | (if function.preload_root { 1 << 6 } else { 0 })
+          | (if function.preload_parent { 1 << 7 } else { 0 })
+          | (if function.preload_global { 1 << 8 } else { 0 });
+        write_u16(&mut data, flags);
+        for parameter in function.parameters.iter() {
+          data.push(parameter.register);
+          write_string(&mut data, &parameter.name)?;
+        }
+        let body = self.assemble_scope(&function.body)?;
+        write_u16(&mut data, get_length(body.len(), code)?);
+        function_body = Some(body);
+      }
+      Action::Try(ref value) => {
+        let catch_in_register = matches!(
+          value.catch,
+          Some(Catch {
+            target: CatchTarget::Register(_),
+            ..
+          })
+        );
+        #[allow(clippy::identity_op)]
+        let flags: u8 = 0
+          | (if value.catch.is_some() { 1 << 0 } else { 0 })
+          | (if value.finally.is_some() { 1 << 1 } else { 0 })
+          | (if catch_in_register { 1 << 2 } else { 0 });
+        data.push(flags);
+        inline_bodies.push((value.body.as_slice(), data.len()));
+        write_u16(&mut data, 0);
+        let empty: &[Action] = &[];
+        inline_bodies.push((
+          value.catch.as_ref().map(|catch| catch.body.as_slice()).unwrap_or(empty),
+          data.len(),
+        ));
+        write_u16(&mut data, 0);
+        inline_bodies.push((value.finally.as_deref().unwrap_or(empty), data.len()));
+        write_u16(&mut data, 0);
+        match value.catch {
+          Some(Catch {
+            target: CatchTarget::Register(register),
+            ..
+          }) => data.push(register),
+          Some(Catch {
+            target: CatchTarget::Variable(ref name),
+            ..
+          }) => write_string(&mut data, name)?,
+          None => write_string(&mut data, "")?,
+        }
+      }
+      _ => unreachable!("actions without data use codes below 0x80"),
+    }
+
+    write_u16(&mut scope.out, get_length(data.len(), code)?);
+    let data_start = scope.out.len();
+    scope.out.extend_from_slice(&data);
+    if let Some(body) = function_body {
+      scope.out.extend(body);
+    }
+    for (body, size_offset) in inline_bodies {
+      let body_start = scope.out.len();
+      self.emit_actions(scope, body)?;
+      let size = get_length(scope.out.len() - body_start, code)?;
+      let size_position = data_start + size_offset;
+      scope.out[size_position..size_position + 2].copy_from_slice(&size.to_le_bytes());
+    }
+    Ok(())
+  }
+
+  fn write_push_value(&self, data: &mut Vec<u8>, value: &PushValue) -> Result<(), Avm1Error> {
+    let type_code: u8 = match value {
+      PushValue::String(_) => 0,
+      PushValue::Float(_) => 1,
+      PushValue::Null => 2,
+      PushValue::Undefined => 3,
+      PushValue::Register(_) => 4,
+      PushValue::Boolean(_) => 5,
+      PushValue::Float64(_) => 6,
+      PushValue::Sint32(_) => 7,
+      PushValue::Constant(index) if *index <= u16::from(u8::MAX) => 8,
+      PushValue::Constant(_) => 9,
+    };
+    let min_version = if type_code <= 1 { 4 } else { 5 };
+    if self.swf_version < min_version {
+      return Err(Avm1Error::UnsupportedPushValue {
+        type_code,
+        min_version,
+        swf_version: self.swf_version,
+      });
+    }
+    data.push(type_code);
+    match value {
+      PushValue::String(ref value) => write_string(data, value)?,
+      PushValue::Float(value) => data.extend_from_slice(&value.to_le_bytes()),
+      PushValue::Null | PushValue::Undefined => {}
+      PushValue::Register(register) => data.push(*register),
+      PushValue::Boolean(value) => data.push(u8::from(*value)),
+      PushValue::Float64(value) => {
+        // AVM1 doubles store the most significant 32 bits first, each half in little-endian order.
+        let bits = value.to_bits();
+        data.extend_from_slice(&((bits >> 32) as u32).to_le_bytes());
+        data.extend_from_slice(&(bits as u32).to_le_bytes());
+      }
+      PushValue::Sint32(value) => data.extend_from_slice(&value.to_le_bytes()),
+      PushValue::Constant(index) => {
+        if type_code == 8 {
+          data.push(*index as u8);
+        } else {
+          write_u16(data, *index);
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Returns the action code and the minimum SWF version supporting the action.
+fn get_action_code(action: &Action) -> (u8, u8) {
+  match action {
+    Action::Label(_) => unreachable!("labels are not emitted"),
+    Action::NextFrame => (0x04, 3),
+    Action::PreviousFrame => (0x05, 3),
+    Action::Play => (0x06, 3),
+    Action::Stop => (0x07, 3),
+    Action::ToggleQuality => (0x08, 3),
+    Action::StopSounds => (0x09, 3),
+    Action::GotoFrame(_) => (0x81, 3),
+    Action::GetUrl { .. } => (0x83, 3),
+    Action::WaitForFrame { .. } => (0x8a, 3),
+    Action::SetTarget(_) => (0x8b, 3),
+    Action::GotoLabel(_) => (0x8c, 3),
+    Action::Add => (0x0a, 4),
+    Action::Subtract => (0x0b, 4),
+    Action::Multiply => (0x0c, 4),
+    Action::Divide => (0x0d, 4),
+    Action::Equals => (0x0e, 4),
+    Action::Less => (0x0f, 4),
+    Action::And => (0x10, 4),
+    Action::Or => (0x11, 4),
+    Action::Not => (0x12, 4),
+    Action::StringEquals => (0x13, 4),
+    Action::StringLength => (0x14, 4),
+    Action::StringExtract => (0x15, 4),
+    Action::Pop => (0x17, 4),
+    Action::ToInteger => (0x18, 4),
+    Action::GetVariable => (0x1c, 4),
+    Action::SetVariable => (0x1d, 4),
+    Action::SetTarget2 => (0x20, 4),
+    Action::StringAdd => (0x21, 4),
+    Action::GetProperty => (0x22, 4),
+    Action::SetProperty => (0x23, 4),
+    Action::CloneSprite => (0x24, 4),
+    Action::RemoveSprite => (0x25, 4),
+    Action::Trace => (0x26, 4),
+    Action::StartDrag => (0x27, 4),
+    Action::EndDrag => (0x28, 4),
+    Action::StringLess => (0x29, 4),
+    Action::RandomNumber => (0x30, 4),
+    Action::MbStringLength => (0x31, 4),
+    Action::CharToAscii => (0x32, 4),
+    Action::AsciiToChar => (0x33, 4),
+    Action::GetTime => (0x34, 4),
+    Action::MbStringExtract => (0x35, 4),
+    Action::MbCharToAscii => (0x36, 4),
+    Action::MbAsciiToChar => (0x37, 4),
+    Action::WaitForFrame2 { .. } => (0x8d, 4),
+    Action::Push(_) => (0x96, 4),
+    Action::Jump(_) => (0x99, 4),
+    Action::GetUrl2 { .. } => (0x9a, 4),
+    Action::If(_) => (0x9d, 4),
+    Action::Call => (0x9e, 4),
+    Action::GotoFrame2 { .. } => (0x9f, 4),
+    Action::Delete => (0x3a, 5),
+    Action::Delete2 => (0x3b, 5),
+    Action::DefineLocal => (0x3c, 5),
+    Action::CallFunction => (0x3d, 5),
+    Action::Return => (0x3e, 5),
+    Action::Modulo => (0x3f, 5),
+    Action::NewObject => (0x40, 5),
+    Action::DefineLocal2 => (0x41, 5),
+    Action::InitArray => (0x42, 5),
+    Action::InitObject => (0x43, 5),
+    Action::TypeOf => (0x44, 5),
+    Action::TargetPath => (0x45, 5),
+    Action::Enumerate => (0x46, 5),
+    Action::Add2 => (0x47, 5),
+    Action::Less2 => (0x48, 5),
+    Action::Equals2 => (0x49, 5),
+    Action::ToNumber => (0x4a, 5),
+    Action::ToString => (0x4b, 5),
+    Action::PushDuplicate => (0x4c, 5),
+    Action::StackSwap => (0x4d, 5),
+    Action::GetMember => (0x4e, 5),
+    Action::SetMember => (0x4f, 5),
+    Action::Increment => (0x50, 5),
+    Action::Decrement => (0x51, 5),
+    Action::CallMethod => (0x52, 5),
+    Action::NewMethod => (0x53, 5),
+    Action::BitAnd => (0x60, 5),
+    Action::BitOr => (0x61, 5),
+    Action::BitXor => (0x62, 5),
+    Action::BitLShift => (0x63, 5),
+    Action::BitRShift => (0x64, 5),
+    Action::BitURShift => (0x65, 5),
+    Action::StoreRegister(_) => (0x87, 5),
+    Action::ConstantPool(_) => (0x88, 5),
+    Action::With(_) => (0x94, 5),
+    Action::DefineFunction { .. } => (0x9b, 5),
+    Action::InstanceOf => (0x54, 6),
+    Action::Enumerate2 => (0x55, 6),
+    Action::StrictEquals => (0x66, 6),
+    Action::Greater => (0x67, 6),
+    Action::StringGreater => (0x68, 6),
+    Action::Throw => (0x2a, 7),
+    Action::CastOp => (0x2b, 7),
+    Action::ImplementsOp => (0x2c, 7),
+    Action::Extends => (0x69, 7),
+    Action::DefineFunction2(_) => (0x8e, 7),
+    Action::Try(_) => (0x8f, 7),
+  }
+}
+
+fn get_length(len: usize, code: u8) -> Result<u16, Avm1Error> {
+  u16::try_from(len).map_err(|_| Avm1Error::ActionTooLong(code))
+}
+
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+  out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) -> Result<(), Avm1Error> {
+  if value.contains('\0') {
+    return Err(Avm1Error::InvalidString(String::from(value)));
+  }
+  out.extend_from_slice(value.as_bytes());
+  out.push(0);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_assemble_branches() {
+    let actions = vec![
+      Action::Push(vec![PushValue::String(String::from("a"))]),
+      Action::GetVariable,
+      Action::If(Label(1)),
+      Action::Play,
+      Action::Label(Label(1)),
+      Action::Stop,
+    ];
+    assert_eq!(
+      assemble(&actions, 4).unwrap(),
+      vec![0x96, 3, 0, 0, b'a', 0, 0x1c, 0x9d, 2, 0, 1, 0, 0x06, 0x07, 0x00]
+    );
+    let actions = vec![Action::Label(Label(0)), Action::Jump(Label(0))];
+    assert_eq!(assemble(&actions, 4).unwrap(), vec![0x99, 2, 0, 0xfb, 0xff, 0x00]);
+  }
+
+  #[test]
+  fn test_assemble_rejects_invalid_code() {
+    assert_eq!(
+      assemble(&[Action::StrictEquals], 5),
+      Err(Avm1Error::UnsupportedAction {
+        code: 0x66,
+        min_version: 6,
+        swf_version: 5,
+      })
+    );
+    assert_eq!(
+      assemble(&[Action::Push(vec![PushValue::Null])], 4),
+      Err(Avm1Error::UnsupportedPushValue {
+        type_code: 2,
+        min_version: 5,
+        swf_version: 4,
+      })
+    );
+    assert_eq!(
+      assemble(&[Action::Jump(Label(3))], 4),
+      Err(Avm1Error::UnknownLabel(Label(3)))
+    );
+    let function = Action::DefineFunction {
+      name: String::new(),
+      parameters: Vec::new(),
+      body: vec![Action::Jump(Label(0))],
+    };
+    assert_eq!(
+      assemble(&[Action::Label(Label(0)), function], 5),
+      Err(Avm1Error::UnknownLabel(Label(0)))
+    );
+  }
+
+  #[test]
+  fn test_assemble_literal_strings() {
+    let actions = vec![
+      Action::Push(vec![
+        PushValue::String(String::from("x")),
+        PushValue::String(String::from("y")),
+      ]),
+      Action::Push(vec![PushValue::String(String::from("x"))]),
+    ];
+    assert_eq!(
+      assemble(&actions, 6).unwrap(),
+      vec![0x96, 6, 0, 0, b'x', 0, 0, b'y', 0, 0x96, 3, 0, 0, b'x', 0, 0x00]
+    );
+  }
+
+  #[test]
+  fn test_assemble_constant_pool() {
+    let push = Action::Push(vec![PushValue::String(String::from("visible"))]);
+    let actions = vec![push.clone(), Action::GetVariable, push, Action::GetVariable];
+    assert_eq!(
+      assemble(&actions, 6).unwrap(),
+      vec![
+        0x88, 10, 0, 1, 0, b'v', b'i', b's', b'i', b'b', b'l', b'e', 0, 0x96, 2, 0, 8, 0, 0x1c, 0x96, 2, 0, 8, 0, 0x1c,
+        0x00
+      ]
+    );
+    // Not available before SWF 5
+    assert_eq!(assemble(&actions, 4).unwrap()[0], 0x96);
+    // Explicit constant pool
+    let actions = vec![
+      Action::ConstantPool(vec![String::from("x")]),
+      Action::Push(vec![PushValue::Constant(0)]),
+      Action::Push(vec![PushValue::String(String::from("visible"))]),
+      Action::Push(vec![PushValue::String(String::from("visible"))]),
+    ];
+    assert_eq!(
+      assemble(&actions, 6).unwrap(),
+      vec![
+        0x88, 4, 0, 1, 0, b'x', 0, 0x96, 2, 0, 8, 0, 0x96, 9, 0, 0, b'v', b'i', b's', b'i', b'b', b'l', b'e', 0, 0x96,
+        9, 0, 0, b'v', b'i', b's', b'i', b'b', b'l', b'e', 0, 0x00
+      ]
+    );
+  }
+
+  #[test]
+  fn test_assemble_define_function2_and_try() {
+    let function = Action::DefineFunction2(DefineFunction2 {
+      name: String::from("f"),
+      register_count: 2,
+      preload_this: true,
+      suppress_this: false,
+      preload_arguments: false,
+      suppress_arguments: true,
+      preload_super: false,
+      suppress_super: false,
+      preload_root: false,
+      preload_parent: false,
+      preload_global: true,
+      parameters: vec![Parameter {
+        register: 1,
+        name: String::from("a"),
+      }],
+      body: vec![Action::Push(vec![PushValue::Register(1)]), Action::Return],
+    });
+    assert_eq!(
+      assemble(&[function], 7).unwrap(),
+      vec![0x8e, 12, 0, b'f', 0, 1, 0, 2, 0x09, 0x01, 1, b'a', 0, 6, 0, 0x96, 2, 0, 4, 1, 0x3e, 0x00]
+    );
+
+    let value = Action::Try(Try {
+      body: vec![Action::Push(vec![PushValue::Sint32(1)]), Action::Throw],
+      catch: Some(Catch {
+        target: CatchTarget::Register(1),
+        body: vec![Action::Pop],
+      }),
+      finally: None,
+    });
+    assert_eq!(
+      assemble(&[value], 7).unwrap(),
+      vec![0x8f, 8, 0, 0x05, 9, 0, 1, 0, 0, 0, 1, 0x96, 5, 0, 7, 1, 0, 0, 0, 0x2a, 0x17, 0x00]
+    );
+  }
+}
@@ -0,0 +1,435 @@
+//! Move the `Push` string literals of AVM1 action bytes to a constant pool.
+//!
+//! The pass works on the bytes stored in `DoAction` and `DoInitAction` tags: it decodes the actions, replaces the
+//! repeated string literals with references to a `ConstantPool` action inserted at the start of the code and fixes
+//! the branch offsets and code sizes affected by the smaller `Push` actions.
+
+use crate::avm1::MAX_CONSTANT_POOL_SIZE;
+use std::collections::HashMap;
+use swf_types as ast;
+
+const ACTION_CONSTANT_POOL: u8 = 0x88;
+const ACTION_DEFINE_FUNCTION2: u8 = 0x8e;
+const ACTION_TRY: u8 = 0x8f;
+const ACTION_WITH: u8 = 0x94;
+const ACTION_PUSH: u8 = 0x96;
+const ACTION_JUMP: u8 = 0x99;
+const ACTION_DEFINE_FUNCTION: u8 = 0x9b;
+const ACTION_IF: u8 = 0x9d;
+
+/// Sizes of the action buffers processed by `pool_tag_constants`, in bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConstantPoolStats {
+  pub original_size: usize,
+  pub optimized_size: usize,
+}
+
+impl ConstantPoolStats {
+  /// Number of bytes saved by the optimization.
+  pub fn saved(&self) -> usize {
+    self.original_size - self.optimized_size
+  }
+}
+
+/// Value of a `Push` action.
+#[derive(Copy, Clone, Debug)]
+enum PushItem<'a> {
+  /// String literal, without its null terminator.
+  String(&'a [u8]),
+  /// Any other value, with its type byte.
+  Other(&'a [u8]),
+}
+
+#[derive(Clone, Debug)]
+struct RawAction<'a> {
+  offset: usize,
+  code: u8,
+  data: &'a [u8],
+  /// Values of `Push` actions.
+  push: Option<Vec<PushItem<'a>>>,
+}
+
+impl RawAction<'_> {
+  fn end(&self) -> usize {
+    self.offset + header_size(self.code) + self.data.len()
+  }
+}
+
+/// Rewrites the action bytes to use a constant pool, returning `None` if it would not reduce their size.
+///
+/// The code is left unchanged before SWF 5, when it already uses a constant pool (a `ConstantPool` action or constant
+/// push values) or when it cannot be decoded. Functions capture the constant pool active when they are defined, so
+/// the pool defined at the start of the code also applies to the nested function bodies.
+///
+/// The pool holds at most `MAX_CONSTANT_POOL_SIZE` entries and its data must fit in a single action.
+pub fn pool_constants(actions: &[u8], swf_version: u8) -> Option<Vec<u8>> {
+  if swf_version < 5 {
+    return None;
+  }
+  let decoded = decode_actions(actions)?;
+  if decoded.iter().any(|action| action.code == ACTION_CONSTANT_POOL) {
+    return None;
+  }
+
+  let pool = get_constant_pool(&decoded);
+  if pool.is_empty() {
+    return None;
+  }
+  let constants: HashMap<&[u8], u16> = pool.iter().enumerate().map(|(i, s)| (*s, i as u16)).collect();
+
+  // New data of each action, before the branch fixes (which do not change the sizes)
+  let mut datas: Vec<Vec<u8>> = Vec::with_capacity(decoded.len());
+  let mut offsets: HashMap<usize, usize> = HashMap::with_capacity(decoded.len() + 1);
+  let mut offset: usize = 0;
+  for action in &decoded {
+    offsets.insert(action.offset, offset);
+    let data = match action.push {
+      Some(ref items) => emit_push_items(items, &constants),
+      None => action.data.to_vec(),
+    };
+    offset += header_size(action.code) + data.len();
+    datas.push(data);
+  }
+  offsets.insert(actions.len(), offset);
+
+  for (action, data) in decoded.iter().zip(datas.iter_mut()) {
+    let old_end = action.end();
+    let new_end = offsets[&action.offset] + header_size(action.code) + data.len();
+    match action.code {
+      ACTION_JUMP | ACTION_IF => {
+        let branch_offset = i16::from_le_bytes([data[0], data[1]]);
+        let target = (old_end as isize).checked_add(isize::from(branch_offset))?;
+        let new_target = *offsets.get(&usize::try_from(target).ok()?)?;
+        let new_offset = i16::try_from(new_target as isize - new_end as isize).ok()?;
+        data[..2].copy_from_slice(&new_offset.to_le_bytes());
+      }
+      ACTION_DEFINE_FUNCTION | ACTION_DEFINE_FUNCTION2 => {
+        let size_pos = data.len() - 2;
+        fix_code_size(&mut data[size_pos..], old_end, new_end, &offsets)?;
+      }
+      ACTION_WITH => {
+        fix_code_size(&mut data[..2], old_end, new_end, &offsets)?;
+      }
+      ACTION_TRY => {
+        // The `try`, `catch` and `finally` bodies follow each other
+        let (mut old_start, mut new_start) = (old_end, new_end);
+        for pos in [1, 3, 5] {
+          let (old_next, new_next) = fix_code_size(&mut data[pos..pos + 2], old_start, new_start, &offsets)?;
+          old_start = old_next;
+          new_start = new_next;
+        }
+      }
+      _ => {}
+    }
+  }
+
+  let mut out: Vec<u8> = Vec::with_capacity(offset + 5);
+  let mut pool_data: Vec<u8> = Vec::new();
+  pool_data.extend_from_slice(&(pool.len() as u16).to_le_bytes());
+  for value in &pool {
+    pool_data.extend_from_slice(value);
+    pool_data.push(0);
+  }
+  emit_raw_action(&mut out, ACTION_CONSTANT_POOL, &pool_data);
+  for (action, data) in decoded.iter().zip(datas.iter()) {
+    emit_raw_action(&mut out, action.code, data);
+  }
+
+  if out.len() < actions.len() {
+    Some(out)
+  } else {
+    None
+  }
+}
+
+/// Applies `pool_constants` to the `DoAction` and `DoInitAction` tags, including the tags of sprites.
+pub fn pool_tag_constants(tags: &mut [ast::Tag], swf_version: u8) -> ConstantPoolStats {
+  let mut stats = ConstantPoolStats::default();
+  pool_tag_constants_into(tags, swf_version, &mut stats);
+  stats
+}
+
+fn pool_tag_constants_into(tags: &mut [ast::Tag], swf_version: u8, stats: &mut ConstantPoolStats) {
+  for tag in tags.iter_mut() {
+    let actions: &mut Vec<u8> = match tag {
+      ast::Tag::DoAction(ref mut tag) => &mut tag.actions,
+      ast::Tag::DoInitAction(ref mut tag) => &mut tag.actions,
+      ast::Tag::DefineSprite(ref mut tag) => {
+        pool_tag_constants_into(&mut tag.tags, swf_version, stats);
+        continue;
+      }
+      _ => continue,
+    };
+    stats.original_size += actions.len();
+    if let Some(optimized) = pool_constants(actions, swf_version) {
+      *actions = optimized;
+    }
+    stats.optimized_size += actions.len();
+  }
+}
+
+fn header_size(code: u8) -> usize {
+  if code >= 0x80 {
+    3
+  } else {
+    1
+  }
+}
+
+/// Decodes the actions, returning `None` for truncated actions or `Push` values using a constant pool.
+fn decode_actions(bytes: &[u8]) -> Option<Vec<RawAction<'_>>> {
+  let mut actions: Vec<RawAction> = Vec::new();
+  let mut offset: usize = 0;
+  while offset < bytes.len() {
+    let code = bytes[offset];
+    let data: &[u8] = if code >= 0x80 {
+      let len_bytes = bytes.get(offset + 1..offset + 3)?;
+      let len = usize::from(u16::from_le_bytes([len_bytes[0], len_bytes[1]]));
+      bytes.get(offset + 3..offset + 3 + len)?
+    } else {
+      &[]
+    };
+    let min_len = match code {
+      ACTION_JUMP | ACTION_IF | ACTION_WITH | ACTION_DEFINE_FUNCTION | ACTION_DEFINE_FUNCTION2 => 2,
+      ACTION_TRY => 7,
+      _ => 0,
+    };
+    if data.len() < min_len {
+      return None;
+    }
+    let push = if code == ACTION_PUSH {
+      Some(decode_push_items(data)?)
+    } else {
+      None
+    };
+    actions.push(RawAction {
+      offset,
+      code,
+      data,
+      push,
+    });
+    offset += header_size(code) + data.len();
+  }
+  Some(actions)
+}
+
+fn decode_push_items(mut data: &[u8]) -> Option<Vec<PushItem<'_>>> {
+  let mut items: Vec<PushItem> = Vec::new();
+  while let Some(&type_code) = data.first() {
+    let item_len = match type_code {
+      0 => {
+        let len = data[1..].iter().position(|b| *b == 0)?;
+        items.push(PushItem::String(&data[1..1 + len]));
+        data = &data[len + 2..];
+        continue;
+      }
+      2 | 3 => 0,
+      4 | 5 => 1,
+      1 | 7 => 4,
+      6 => 8,
+      // Constants (8 and 9) depend on an existing pool; other codes are invalid
+      _ => return None,
+    };
+    let item = data.get(..1 + item_len)?;
+    items.push(PushItem::Other(item));
+    data = &data[1 + item_len..];
+  }
+  Some(items)
+}
+
+/// Selects the strings to pool: each entry must save more bytes in the `Push` actions than it takes in the pool.
+fn get_constant_pool<'a>(actions: &[RawAction<'a>]) -> Vec<&'a [u8]> {
+  let mut uses: HashMap<&[u8], (usize, usize)> = HashMap::new();
+  for action in actions {
+    for item in action.push.iter().flatten() {
+      if let PushItem::String(value) = item {
+        let next_index = uses.len();
+        uses.entry(*value).or_insert((0, next_index)).0 += 1;
+      }
+    }
+  }
+
+  // A literal takes `len + 2` bytes, a reference 2 bytes (3 bytes after the first 256 entries), a pool entry
+  // `len + 1` bytes.
+  let gain = |len: usize, count: usize, reference_size: usize| {
+    (count * (len + 2)) as isize - (count * reference_size) as isize - (len + 1) as isize
+  };
+  let mut candidates: Vec<(&[u8], usize, usize)> = uses
+    .into_iter()
+    .filter(|(value, (count, _))| gain(value.len(), *count, 2) > 0)
+    .map(|(value, (count, first))| (value, count, first))
+    .collect();
+  candidates.sort_by(|a, b| {
+    gain(b.0.len(), b.1, 2)
+      .cmp(&gain(a.0.len(), a.1, 2))
+      .then(a.2.cmp(&b.2))
+  });
+
+  let mut pool: Vec<&[u8]> = Vec::new();
+  let mut size: usize = 2;
+  for (value, count, _) in candidates {
+    if pool.len() >= MAX_CONSTANT_POOL_SIZE {
+      break;
+    }
+    let reference_size = if pool.len() <= usize::from(u8::MAX) { 2 } else { 3 };
+    if gain(value.len(), count, reference_size) <= 0 || size + value.len() + 1 > usize::from(u16::MAX) {
+      continue;
+    }
+    size += value.len() + 1;
+    pool.push(value);
+  }
+  pool
+}
+
+fn emit_push_items(items: &[PushItem], constants: &HashMap<&[u8], u16>) -> Vec<u8> {
+  let mut data: Vec<u8> = Vec::new();
+  for item in items {
+    match *item {
+      PushItem::String(value) => match constants.get(value) {
+        Some(&index) if index <= u16::from(u8::MAX) => data.extend_from_slice(&[8, index as u8]),
+        Some(&index) => {
+          data.push(9);
+          data.extend_from_slice(&index.to_le_bytes());
+        }
+        None => {
+          data.push(0);
+          data.extend_from_slice(value);
+          data.push(0);
+        }
+      },
+      PushItem::Other(value) => data.extend_from_slice(value),
+    }
+  }
+  data
+}
+
+/// Updates the size of the code starting at `old_start` (`new_start` after the rewrite), returning the old and new
+/// ends of the code.
+fn fix_code_size(
+  size: &mut [u8],
+  old_start: usize,
+  new_start: usize,
+  offsets: &HashMap<usize, usize>,
+) -> Option<(usize, usize)> {
+  let old_end = old_start + usize::from(u16::from_le_bytes([size[0], size[1]]));
+  let new_end = *offsets.get(&old_end)?;
+  let new_size = u16::try_from(new_end.checked_sub(new_start)?).ok()?;
+  size.copy_from_slice(&new_size.to_le_bytes());
+  Some((old_end, new_end))
+}
+
+fn emit_raw_action(out: &mut Vec<u8>, code: u8, data: &[u8]) {
+  out.push(code);
+  if code >= 0x80 {
+    out.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    out.extend_from_slice(data);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::avm1::{assemble, Action, Label, PushValue};
+
+  fn push_string(value: &str) -> Action {
+    Action::Push(vec![PushValue::String(String::from(value))])
+  }
+
+  /// Assembles the actions without the automatic constant pool (by removing an explicit empty pool).
+  fn assemble_without_pool(actions: &[Action]) -> Vec<u8> {
+    let mut with_pool = vec![Action::ConstantPool(Vec::new())];
+    with_pool.extend_from_slice(actions);
+    assemble(&with_pool, 7).unwrap()[5..].to_vec()
+  }
+
+  #[test]
+  fn test_pool_constants_with_branches() {
+    let actions = vec![
+      push_string("visible"),
+      Action::GetVariable,
+      Action::If(Label(1)),
+      push_string("visible"),
+      push_string("once"),
+      Action::SetVariable,
+      Action::Label(Label(1)),
+      Action::DefineFunction {
+        name: String::from("f"),
+        parameters: Vec::new(),
+        body: vec![push_string("visible"), Action::Return],
+      },
+    ];
+    let expected = assemble(
+      &[
+        vec![Action::ConstantPool(vec![String::from("visible")])],
+        actions
+          .iter()
+          .map(|action| match action {
+            Action::DefineFunction { .. } => Action::DefineFunction {
+              name: String::from("f"),
+              parameters: Vec::new(),
+              body: vec![Action::Push(vec![PushValue::Constant(0)]), Action::Return],
+            },
+            Action::Push(values) if values[0] == PushValue::String(String::from("visible")) => {
+              Action::Push(vec![PushValue::Constant(0)])
+            }
+            action => action.clone(),
+          })
+          .collect(),
+      ]
+      .concat(),
+      7,
+    )
+    .unwrap();
+
+    let input = assemble_without_pool(&actions);
+    assert_eq!(assemble(&actions, 7).unwrap(), expected);
+    let output = pool_constants(&input, 7).unwrap();
+    assert_eq!(output, expected);
+    assert!(output.len() < input.len());
+  }
+
+  #[test]
+  fn test_pool_constants_skips_unprofitable() {
+    // Old SWF version
+    let input = assemble(&[push_string("visible"), push_string("visible")], 4).unwrap();
+    assert_eq!(pool_constants(&input, 4), None);
+    // Single use
+    let input = assemble_without_pool(&[push_string("visible"), push_string("other")]);
+    assert_eq!(pool_constants(&input, 7), None);
+    // Existing constant references
+    let input = assemble_without_pool(&[
+      Action::Push(vec![PushValue::Constant(0)]),
+      push_string("visible"),
+      push_string("visible"),
+    ]);
+    assert_eq!(pool_constants(&input, 7), None);
+    // Truncated action
+    assert_eq!(pool_constants(&[0x96, 4, 0, 0], 7), None);
+  }
+
+  #[test]
+  fn test_pool_tag_constants() {
+    let actions = assemble_without_pool(&[push_string("visible"), push_string("visible"), push_string("visible")]);
+    let mut tags = vec![
+      ast::Tag::DoAction(ast::tags::DoAction {
+        actions: actions.clone(),
+      }),
+      ast::Tag::DefineSprite(ast::tags::DefineSprite {
+        id: 1,
+        frame_count: 1,
+        tags: vec![ast::Tag::DoInitAction(ast::tags::DoInitAction {
+          sprite_id: 1,
+          actions: actions.clone(),
+        })],
+      }),
+    ];
+    let stats = pool_tag_constants(&mut tags, 8);
+    assert_eq!(stats.original_size, 2 * actions.len());
+    assert_eq!(stats.saved(), 16);
+
+    let bytes = crate::emit_tag(&tags[1], 8).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 8);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(tags[1].clone()));
+  }
+}
@@ -0,0 +1,207 @@
+//! Collapse identical character definitions.
+
+use crate::dictionary::{get_attached_character, get_defined_character, remap_character_ids};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use swf_types as ast;
+
+/// Removes the characters identical to a previous character, and replaces their references with the previous id.
+///
+/// Two characters are identical if their emitted definitions only differ by their id, after replacing the references
+/// to removed duplicates. The tags attached to the characters (`DefineFontInfo`, `DefineScalingGrid`,
+/// `DoInitAction`, ...) must also be identical; the attached tags of the removed characters are removed with them.
+/// Characters bound to a class with `SymbolClass` are left out: removing them would lose their class, and merging
+/// other characters into them would turn their instances into instances of the class.
+///
+/// Returns the surviving id of each removed character.
+pub fn deduplicate_characters(movie: &mut ast::Movie) -> io::Result<BTreeMap<u16, u16>> {
+  let swf_version = movie.header.swf_version;
+  let mut bound: HashSet<u16> = HashSet::new();
+  let mut attached: HashMap<u16, Vec<&ast::Tag>> = HashMap::new();
+  for tag in movie.tags.iter() {
+    if let ast::Tag::SymbolClass(ref tag) = tag {
+      bound.extend(tag.symbols.iter().map(|symbol| symbol.id));
+    }
+    if let Some(id) = get_attached_character(tag) {
+      attached.entry(id).or_default().push(tag);
+    }
+  }
+
+  let mut duplicates: BTreeMap<u16, u16> = BTreeMap::new();
+  let mut survivors: HashMap<Vec<u8>, u16> = HashMap::new();
+  for tag in movie.tags.iter() {
+    let id = match get_defined_character(tag) {
+      Some((id, _)) if !bound.contains(&id) => id,
+      _ => continue,
+    };
+    let tags = std::iter::once(tag).chain(attached.get(&id).into_iter().flatten().copied());
+    let mut key: Vec<u8> = Vec::new();
+    for tag in tags {
+      key.extend(get_anonymous_tag_bytes(tag, id, &duplicates, swf_version)?);
+    }
+    match survivors.entry(key) {
+      Entry::Occupied(survivor) => {
+        duplicates.insert(id, *survivor.get());
+      }
+      Entry::Vacant(entry) => {
+        entry.insert(id);
+      }
+    }
+  }
+
+  if !duplicates.is_empty() {
+    movie.tags.retain(|tag| {
+      let id = get_defined_character(tag)
+        .map(|(id, _)| id)
+        .or_else(|| get_attached_character(tag));
+      match id {
+        Some(id) => !duplicates.contains_key(&id),
+        None => true,
+      }
+    });
+    remap_character_ids(&mut movie.tags, &mut |id| *duplicates.get(&id).unwrap_or(&id));
+  }
+  Ok(duplicates)
+}
+
+/// Emits the tag with the character `id` replaced by 0 and the references to duplicates replaced by their survivor.
+fn get_anonymous_tag_bytes(
+  tag: &ast::Tag,
+  id: u16,
+  duplicates: &BTreeMap<u16, u16>,
+  swf_version: u8,
+) -> io::Result<Vec<u8>> {
+  let mut tag = tag.clone();
+  remap_character_ids(std::slice::from_mut(&mut tag), &mut |character_id| {
+    if character_id == id {
+      0
+    } else {
+      *duplicates.get(&character_id).unwrap_or(&character_id)
+    }
+  });
+  crate::emit_tag(&tag, swf_version)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::timeline::{DisplayList, DisplayObject, Timeline};
+
+  fn define_binary_data(id: u16, data: &[u8]) -> ast::Tag {
+    ast::Tag::DefineBinaryData(ast::tags::DefineBinaryData {
+      id,
+      data: data.to_vec(),
+    })
+  }
+
+  fn define_sprite(id: u16, character_id: u16) -> ast::Tag {
+    let mut display_list = DisplayList::new();
+    display_list.insert(1, DisplayObject::new(character_id));
+    let mut timeline = Timeline::new();
+    timeline.add_frame(display_list);
+    ast::Tag::DefineSprite(ast::tags::DefineSprite {
+      id,
+      frame_count: 1,
+      tags: timeline.emit_tags(),
+    })
+  }
+
+  fn export(id: u16, name: &str) -> ast::Tag {
+    ast::Tag::ExportAssets(ast::tags::ExportAssets {
+      assets: vec![ast::NamedId {
+        id,
+        name: String::from(name),
+      }],
+    })
+  }
+
+  fn movie(tags: Vec<ast::Tag>) -> ast::Movie {
+    ast::Movie {
+      header: ast::Header {
+        swf_version: 10,
+        frame_size: ast::Rect {
+          x_min: 0,
+          x_max: 0,
+          y_min: 0,
+          y_max: 0,
+        },
+        frame_rate: swf_fixed::Ufixed8P8::from_epsilons(256),
+        frame_count: 1,
+      },
+      tags,
+    }
+  }
+
+  #[test]
+  fn test_deduplicate_characters() {
+    let mut movie = movie(vec![
+      define_binary_data(1, &[1, 2, 3]),
+      define_binary_data(2, &[1, 2, 3]),
+      define_binary_data(3, &[4]),
+      define_sprite(4, 1),
+      define_sprite(5, 2),
+      define_sprite(6, 3),
+      export(5, "a"),
+      export(6, "b"),
+    ]);
+    let duplicates = deduplicate_characters(&mut movie).unwrap();
+
+    // The sprite 5 only becomes a duplicate once the data 2 is replaced
+    assert_eq!(duplicates.into_iter().collect::<Vec<_>>(), vec![(2, 1), (5, 4)]);
+    assert_eq!(
+      movie.tags,
+      vec![
+        define_binary_data(1, &[1, 2, 3]),
+        define_binary_data(3, &[4]),
+        define_sprite(4, 1),
+        define_sprite(6, 3),
+        export(4, "a"),
+        export(6, "b"),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_deduplicate_characters_with_attached_tags() {
+    let scaling_grid = |character_id: u16, x_max: i32| {
+      ast::Tag::DefineScalingGrid(ast::tags::DefineScalingGrid {
+        character_id,
+        splitter: ast::Rect {
+          x_min: 0,
+          x_max,
+          y_min: 0,
+          y_max: 20,
+        },
+      })
+    };
+    let symbol_class = ast::Tag::SymbolClass(ast::tags::SymbolClass {
+      symbols: vec![
+        ast::NamedId {
+          id: 3,
+          name: String::from("Clip"),
+        },
+        ast::NamedId {
+          id: 4,
+          name: String::from("Clip2"),
+        },
+      ],
+    });
+    let mut movie = movie(vec![
+      define_binary_data(1, &[0]),
+      define_sprite(2, 1),
+      scaling_grid(2, 20),
+      define_sprite(3, 1),
+      scaling_grid(3, 40),
+      define_sprite(4, 1),
+      scaling_grid(4, 20),
+      define_sprite(5, 1),
+      scaling_grid(5, 40),
+      symbol_class,
+    ]);
+    // 2 and 3 differ by their scaling grid, 4 is bound after 2 and 5 is unbound after 3
+    let expected = movie.tags.clone();
+    assert!(deduplicate_characters(&mut movie).unwrap().is_empty());
+    assert_eq!(movie.tags, expected);
+  }
+}
@@ -0,0 +1,590 @@
+//! Track the characters defined by a movie and the references between them.
+//!
+//! Character ids are assigned by `Define*` tags and used by the control tags (`PlaceObject`, `StartSound`, ...) and
+//! by other definitions (button records, text fonts, bitmap fills, sprite timelines). Some tags are attached to a
+//! character instead of defining one: `DefineFontInfo` or `DefineButtonSound` for example.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use swf_types as ast;
+
+/// Bitmap id used by the bitmap fills without bitmap.
+pub const NO_BITMAP_ID: u16 = 0xffff;
+
+/// Character id used by `SymbolClass` to bind the main timeline.
+pub const MAIN_TIMELINE_ID: u16 = 0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CharacterKind {
+  BinaryData,
+  Bitmap,
+  Button,
+  DynamicText,
+  Font,
+  /// Character imported from another movie with `ImportAssets`.
+  Imported,
+  MorphShape,
+  Shape,
+  Sound,
+  Sprite,
+  Text,
+  VideoStream,
+}
+
+/// Character id allocator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dictionary {
+  characters: BTreeMap<u16, CharacterKind>,
+}
+
+impl Dictionary {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Creates a dictionary with the characters defined or imported by the tags.
+  ///
+  /// Returns an `InvalidInput` error if a character id is defined twice.
+  pub fn from_tags(tags: &[ast::Tag]) -> io::Result<Self> {
+    let mut dictionary = Self::new();
+    for tag in tags {
+      if let ast::Tag::ImportAssets(ref tag) = tag {
+        for asset in tag.assets.iter() {
+          dictionary.define(asset.id, CharacterKind::Imported)?;
+        }
+      }
+      if let Some((id, kind)) = get_defined_character(tag) {
+        dictionary.define(id, kind)?;
+      }
+    }
+    Ok(dictionary)
+  }
+
+  /// Allocates the lowest unused character id, starting at 1.
+  pub fn allocate(&mut self, kind: CharacterKind) -> io::Result<u16> {
+    let id = (1..=u16::MAX)
+      .find(|id| !self.characters.contains_key(id))
+      .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "all character ids are used"))?;
+    self.characters.insert(id, kind);
+    Ok(id)
+  }
+
+  /// Records a character with a known id.
+  ///
+  /// Returns an `InvalidInput` error if the id is already used.
+  pub fn define(&mut self, id: u16, kind: CharacterKind) -> io::Result<()> {
+    if self.characters.contains_key(&id) {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("duplicate character id: {}", id),
+      ));
+    }
+    self.characters.insert(id, kind);
+    Ok(())
+  }
+
+  pub fn get(&self, id: u16) -> Option<CharacterKind> {
+    self.characters.get(&id).copied()
+  }
+
+  pub fn contains(&self, id: u16) -> bool {
+    self.characters.contains_key(&id)
+  }
+
+  pub fn len(&self) -> usize {
+    self.characters.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.characters.is_empty()
+  }
+
+  /// Iterates over the characters, by increasing id.
+  pub fn iter(&self) -> impl Iterator<Item = (u16, CharacterKind)> + '_ {
+    self.characters.iter().map(|(id, kind)| (*id, *kind))
+  }
+}
+
+/// Reference to a character that is not defined by a previous tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DanglingReference {
+  /// Name of the referencing tag, see `get_tag_name`.
+  pub tag: &'static str,
+  pub character_id: u16,
+}
+
+/// Returns the references to characters that are not defined by a previous tag.
+///
+/// The tags of a sprite may reference the characters defined before the sprite.
+pub fn find_dangling_references(tags: &[ast::Tag]) -> Vec<DanglingReference> {
+  let mut defined: HashSet<u16> = HashSet::new();
+  let mut dangling: Vec<DanglingReference> = Vec::new();
+  for tag in tags {
+    let name = get_tag_name(tag);
+    let references = get_attached_character(tag)
+      .into_iter()
+      .chain(get_character_references(tag));
+    for character_id in references {
+      if !defined.contains(&character_id) {
+        dangling.push(DanglingReference {
+          tag: name,
+          character_id,
+        });
+      }
+    }
+    if let ast::Tag::ImportAssets(ref tag) = tag {
+      defined.extend(tag.assets.iter().map(|asset| asset.id));
+    }
+    if let Some((id, _)) = get_defined_character(tag) {
+      defined.insert(id);
+    }
+  }
+  dangling
+}
+
+/// Returns the character defined by the tag.
+///
+/// Characters imported with `ImportAssets` are not returned since a single tag imports several characters.
+pub fn get_defined_character(tag: &ast::Tag) -> Option<(u16, CharacterKind)> {
+  match tag {
+    ast::Tag::DefineBinaryData(ref tag) => Some((tag.id, CharacterKind::BinaryData)),
+    ast::Tag::DefineBitmap(ref tag) => Some((tag.id, CharacterKind::Bitmap)),
+    ast::Tag::DefineButton(ref tag) => Some((tag.id, CharacterKind::Button)),
+    ast::Tag::DefineCffFont(ref tag) => Some((tag.id, CharacterKind::Font)),
+    ast::Tag::DefineDynamicText(ref tag) => Some((tag.id, CharacterKind::DynamicText)),
+    ast::Tag::DefineFont(ref tag) => Some((tag.id, CharacterKind::Font)),
+    ast::Tag::DefineGlyphFont(ref tag) => Some((tag.id, CharacterKind::Font)),
+    ast::Tag::DefineMorphShape(ref tag) => Some((tag.id, CharacterKind::MorphShape)),
+    ast::Tag::DefineShape(ref tag) => Some((tag.id, CharacterKind::Shape)),
+    ast::Tag::DefineSound(ref tag) => Some((tag.id, CharacterKind::Sound)),
+    ast::Tag::DefineSprite(ref tag) => Some((tag.id, CharacterKind::Sprite)),
+    ast::Tag::DefineText(ref tag) => Some((tag.id, CharacterKind::Text)),
+    ast::Tag::DefineVideoStream(ref tag) => Some((tag.id, CharacterKind::VideoStream)),
+    _ => None,
+  }
+}
+
+/// Returns the character extended by the tag, for tags that only make sense with their character.
+pub fn get_attached_character(tag: &ast::Tag) -> Option<u16> {
+  match tag {
+    ast::Tag::CsmTextSettings(ref tag) => Some(tag.text_id),
+    ast::Tag::DefineButtonColorTransform(ref tag) => Some(tag.button_id),
+    ast::Tag::DefineButtonSound(ref tag) => Some(tag.button_id),
+    ast::Tag::DefineFontAlignZones(ref tag) => Some(tag.font_id),
+    ast::Tag::DefineFontInfo(ref tag) => Some(tag.font_id),
+    ast::Tag::DefineFontName(ref tag) => Some(tag.font_id),
+    ast::Tag::DefineScalingGrid(ref tag) => Some(tag.character_id),
+    ast::Tag::DoInitAction(ref tag) => Some(tag.sprite_id),
+    _ => None,
+  }
+}
+
+/// Returns the characters referenced by the tag, excluding its attached character.
+///
+/// The references of a sprite are the references of its tags. Ids may be repeated.
+pub fn get_character_references(tag: &ast::Tag) -> Vec<u16> {
+  let mut references: Vec<u16> = Vec::new();
+  match tag {
+    ast::Tag::DefineButton(ref tag) => {
+      references.extend(tag.records.iter().map(|record| record.character_id));
+    }
+    ast::Tag::DefineButtonSound(ref tag) => {
+      let sounds = [
+        &tag.over_up_to_idle,
+        &tag.idle_to_over_up,
+        &tag.over_up_to_over_down,
+        &tag.over_down_to_over_up,
+      ];
+      references.extend(
+        sounds
+          .iter()
+          .filter_map(|sound| sound.as_ref().map(|sound| sound.sound_id)),
+      );
+    }
+    ast::Tag::DefineDynamicText(ref tag) => references.extend(tag.font_id),
+    ast::Tag::DefineMorphShape(ref tag) => {
+      let styles = &tag.shape.initial_styles;
+      let records_styles = tag.shape.records.iter().filter_map(|record| match record {
+        ast::MorphShapeRecord::StyleChange(ref record) => record.new_styles.as_ref(),
+        _ => None,
+      });
+      for styles in std::iter::once(styles).chain(records_styles) {
+        let fills = styles.fill.iter().chain(styles.line.iter().map(|line| &line.fill));
+        for fill in fills {
+          if let ast::MorphFillStyle::Bitmap(ref fill) = fill {
+            if fill.bitmap_id != NO_BITMAP_ID {
+              references.push(fill.bitmap_id);
+            }
+          }
+        }
+      }
+    }
+    ast::Tag::DefineShape(ref tag) => {
+      let styles = &tag.shape.initial_styles;
+      let records_styles = tag.shape.records.iter().filter_map(|record| match record {
+        ast::ShapeRecord::StyleChange(ref record) => record.new_styles.as_ref(),
+        _ => None,
+      });
+      for styles in std::iter::once(styles).chain(records_styles) {
+        let fills = styles.fill.iter().chain(styles.line.iter().map(|line| &line.fill));
+        for fill in fills {
+          if let ast::FillStyle::Bitmap(ref fill) = fill {
+            if fill.bitmap_id != NO_BITMAP_ID {
+              references.push(fill.bitmap_id);
+            }
+          }
+        }
+      }
+    }
+    ast::Tag::DefineSprite(ref tag) => {
+      for tag in tag.tags.iter() {
+        references.extend(get_attached_character(tag));
+        references.extend(get_character_references(tag));
+      }
+    }
+    ast::Tag::DefineText(ref tag) => references.extend(tag.records.iter().filter_map(|record| record.font_id)),
+    ast::Tag::ExportAssets(ref tag) => references.extend(tag.assets.iter().map(|asset| asset.id)),
+    ast::Tag::PlaceObject(ref tag) => references.extend(tag.character_id),
+    ast::Tag::RemoveObject(ref tag) => references.extend(tag.character_id),
+    ast::Tag::StartSound(ref tag) => references.push(tag.sound_id),
+    ast::Tag::SymbolClass(ref tag) => references.extend(
+      tag
+        .symbols
+        .iter()
+        .map(|symbol| symbol.id)
+        .filter(|id| *id != MAIN_TIMELINE_ID),
+    ),
+    ast::Tag::VideoFrame(ref tag) => references.push(tag.video_id),
+    _ => {}
+  }
+  references
+}
+
+/// Replaces every character id of the tags (definitions, attachments and references), including the tags of sprites.
+///
+/// Bitmap fills without bitmap and the main timeline binding of `SymbolClass` are left unchanged.
+pub fn remap_character_ids<F: FnMut(u16) -> u16>(tags: &mut [ast::Tag], remap: &mut F) {
+  for tag in tags.iter_mut() {
+    remap_tag_character_ids(tag, remap);
+  }
+}
+
+fn remap_tag_character_ids<F: FnMut(u16) -> u16>(tag: &mut ast::Tag, remap: &mut F) {
+  match tag {
+    ast::Tag::CsmTextSettings(ref mut tag) => tag.text_id = remap(tag.text_id),
+    ast::Tag::DefineBinaryData(ref mut tag) => tag.id = remap(tag.id),
+    ast::Tag::DefineBitmap(ref mut tag) => tag.id = remap(tag.id),
+    ast::Tag::DefineButton(ref mut tag) => {
+      tag.id = remap(tag.id);
+      for record in tag.records.iter_mut() {
+        record.character_id = remap(record.character_id);
+      }
+    }
+    ast::Tag::DefineButtonColorTransform(ref mut tag) => tag.button_id = remap(tag.button_id),
+    ast::Tag::DefineButtonSound(ref mut tag) => {
+      tag.button_id = remap(tag.button_id);
+      let sounds = [
+        &mut tag.over_up_to_idle,
+        &mut tag.idle_to_over_up,
+        &mut tag.over_up_to_over_down,
+        &mut tag.over_down_to_over_up,
+      ];
+      for sound in sounds.into_iter().flatten() {
+        sound.sound_id = remap(sound.sound_id);
+      }
+    }
+    ast::Tag::DefineCffFont(ref mut tag) => tag.id = remap(tag.id),
+    ast::Tag::DefineDynamicText(ref mut tag) => {
+      tag.id = remap(tag.id);
+      tag.font_id = tag.font_id.map(&mut *remap);
+    }
+    ast::Tag::DefineFont(ref mut tag) => tag.id = remap(tag.id),
+    ast::Tag::DefineFontAlignZones(ref mut tag) => tag.font_id = remap(tag.font_id),
+    ast::Tag::DefineFontInfo(ref mut tag) => tag.font_id = remap(tag.font_id),
+    ast::Tag::DefineFontName(ref mut tag) => tag.font_id = remap(tag.font_id),
+    ast::Tag::DefineGlyphFont(ref mut tag) => tag.id = remap(tag.id),
+    ast::Tag::DefineMorphShape(ref mut tag) => {
+      tag.id = remap(tag.id);
+      remap_morph_styles(&mut tag.shape.initial_styles, remap);
+      for record in tag.shape.records.iter_mut() {
+        if let ast::MorphShapeRecord::StyleChange(ref mut record) = record {
+          if let Some(ref mut styles) = record.new_styles {
+            remap_morph_styles(styles, remap);
+          }
+        }
+      }
+    }
+    ast::Tag::DefineScalingGrid(ref mut tag) => tag.character_id = remap(tag.character_id),
+    ast::Tag::DefineShape(ref mut tag) => {
+      tag.id = remap(tag.id);
+      remap_styles(&mut tag.shape.initial_styles, remap);
+      for record in tag.shape.records.iter_mut() {
+        if let ast::ShapeRecord::StyleChange(ref mut record) = record {
+          if let Some(ref mut styles) = record.new_styles {
+            remap_styles(styles, remap);
+          }
+        }
+      }
+    }
+    ast::Tag::DefineSound(ref mut tag) => tag.id = remap(tag.id),
+    ast::Tag::DefineSprite(ref mut tag) => {
+      tag.id = remap(tag.id);
+      remap_character_ids(&mut tag.tags, remap);
+    }
+    ast::Tag::DefineText(ref mut tag) => {
+      tag.id = remap(tag.id);
+      for record in tag.records.iter_mut() {
+        record.font_id = record.font_id.map(&mut *remap);
+      }
+    }
+    ast::Tag::DefineVideoStream(ref mut tag) => tag.id = remap(tag.id),
+    ast::Tag::DoInitAction(ref mut tag) => tag.sprite_id = remap(tag.sprite_id),
+    ast::Tag::ExportAssets(ref mut tag) => {
+      for asset in tag.assets.iter_mut() {
+        asset.id = remap(asset.id);
+      }
+    }
+    ast::Tag::ImportAssets(ref mut tag) => {
+      for asset in tag.assets.iter_mut() {
+        asset.id = remap(asset.id);
+      }
+    }
+    ast::Tag::PlaceObject(ref mut tag) => tag.character_id = tag.character_id.map(&mut *remap),
+    ast::Tag::RemoveObject(ref mut tag) => tag.character_id = tag.character_id.map(&mut *remap),
+    ast::Tag::StartSound(ref mut tag) => tag.sound_id = remap(tag.sound_id),
+    ast::Tag::SymbolClass(ref mut tag) => {
+      for symbol in tag.symbols.iter_mut() {
+        if symbol.id != MAIN_TIMELINE_ID {
+          symbol.id = remap(symbol.id);
+        }
+      }
+    }
+    ast::Tag::VideoFrame(ref mut tag) => tag.video_id = remap(tag.video_id),
+    _ => {}
+  }
+}
+
+fn remap_styles<F: FnMut(u16) -> u16>(styles: &mut ast::ShapeStyles, remap: &mut F) {
+  let fills = styles
+    .fill
+    .iter_mut()
+    .chain(styles.line.iter_mut().map(|line| &mut line.fill));
+  for fill in fills {
+    if let ast::FillStyle::Bitmap(ref mut fill) = fill {
+      if fill.bitmap_id != NO_BITMAP_ID {
+        fill.bitmap_id = remap(fill.bitmap_id);
+      }
+    }
+  }
+}
+
+fn remap_morph_styles<F: FnMut(u16) -> u16>(styles: &mut ast::MorphShapeStyles, remap: &mut F) {
+  let fills = styles
+    .fill
+    .iter_mut()
+    .chain(styles.line.iter_mut().map(|line| &mut line.fill));
+  for fill in fills {
+    if let ast::MorphFillStyle::Bitmap(ref mut fill) = fill {
+      if fill.bitmap_id != NO_BITMAP_ID {
+        fill.bitmap_id = remap(fill.bitmap_id);
+      }
+    }
+  }
+}
+
+/// Renumbers the characters of the movie from 1, in definition order, and returns the new id of each old id.
+///
+/// Returns an `InvalidInput` error if the movie defines a character twice or references an undefined character.
+pub fn renumber_characters(movie: &mut ast::Movie) -> io::Result<HashMap<u16, u16>> {
+  let mut ids: HashMap<u16, u16> = HashMap::new();
+  for tag in movie.tags.iter() {
+    let defined: Vec<u16> = match tag {
+      ast::Tag::ImportAssets(ref tag) => tag.assets.iter().map(|asset| asset.id).collect(),
+      tag => get_defined_character(tag).map(|(id, _)| id).into_iter().collect(),
+    };
+    for id in defined {
+      let new_id = ids.len() + 1;
+      if ids.insert(id, new_id as u16).is_some() {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidInput,
+          format!("duplicate character id: {}", id),
+        ));
+      }
+    }
+  }
+
+  let mut undefined: Option<u16> = None;
+  let mut tags = movie.tags.clone();
+  remap_character_ids(&mut tags, &mut |id| match ids.get(&id) {
+    Some(new_id) => *new_id,
+    None => {
+      undefined = Some(id);
+      id
+    }
+  });
+  if let Some(id) = undefined {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("reference to undefined character: {}", id),
+    ));
+  }
+  movie.tags = tags;
+  Ok(ids)
+}
+
+/// Returns the name of the tag type, such as `"DefineShape"`.
+///
+/// Tags with several versions (`DefineShape` to `DefineShape4` for example) share the same name.
+pub fn get_tag_name(tag: &ast::Tag) -> &'static str {
+  match tag {
+    ast::Tag::CsmTextSettings(_) => "CsmTextSettings",
+    ast::Tag::DefineBinaryData(_) => "DefineBinaryData",
+    ast::Tag::DefineBitmap(_) => "DefineBitmap",
+    ast::Tag::DefineButton(_) => "DefineButton",
+    ast::Tag::DefineButtonColorTransform(_) => "DefineButtonColorTransform",
+    ast::Tag::DefineButtonSound(_) => "DefineButtonSound",
+    ast::Tag::DefineCffFont(_) => "DefineCffFont",
+    ast::Tag::DefineDynamicText(_) => "DefineDynamicText",
+    ast::Tag::DefineFont(_) => "DefineFont",
+    ast::Tag::DefineFontAlignZones(_) => "DefineFontAlignZones",
+    ast::Tag::DefineFontInfo(_) => "DefineFontInfo",
+    ast::Tag::DefineFontName(_) => "DefineFontName",
+    ast::Tag::DefineGlyphFont(_) => "DefineGlyphFont",
+    ast::Tag::DefineJpegTables(_) => "DefineJpegTables",
+    ast::Tag::DefineMorphShape(_) => "DefineMorphShape",
+    ast::Tag::DefineScalingGrid(_) => "DefineScalingGrid",
+    ast::Tag::DefineSceneAndFrameLabelData(_) => "DefineSceneAndFrameLabelData",
+    ast::Tag::DefineShape(_) => "DefineShape",
+    ast::Tag::DefineSound(_) => "DefineSound",
+    ast::Tag::DefineSprite(_) => "DefineSprite",
+    ast::Tag::DefineText(_) => "DefineText",
+    ast::Tag::DefineVideoStream(_) => "DefineVideoStream",
+    ast::Tag::EnablePostscript => "EnablePostscript",
+    ast::Tag::DoAbc(_) => "DoAbc",
+    ast::Tag::DoAction(_) => "DoAction",
+    ast::Tag::DoInitAction(_) => "DoInitAction",
+    ast::Tag::EnableDebugger(_) => "EnableDebugger",
+    ast::Tag::ExportAssets(_) => "ExportAssets",
+    ast::Tag::FileAttributes(_) => "FileAttributes",
+    ast::Tag::FrameLabel(_) => "FrameLabel",
+    ast::Tag::ImportAssets(_) => "ImportAssets",
+    ast::Tag::Metadata(_) => "Metadata",
+    ast::Tag::PlaceObject(_) => "PlaceObject",
+    ast::Tag::Protect(_) => "Protect",
+    ast::Tag::Raw(_) => "Raw",
+    ast::Tag::RawBody(_) => "RawBody",
+    ast::Tag::RemoveObject(_) => "RemoveObject",
+    ast::Tag::ScriptLimits(_) => "ScriptLimits",
+    ast::Tag::SetBackgroundColor(_) => "SetBackgroundColor",
+    ast::Tag::SetTabIndex(_) => "SetTabIndex",
+    ast::Tag::ShowFrame => "ShowFrame",
+    ast::Tag::SoundStreamBlock(_) => "SoundStreamBlock",
+    ast::Tag::SoundStreamHead(_) => "SoundStreamHead",
+    ast::Tag::StartSound(_) => "StartSound",
+    ast::Tag::StartSound2(_) => "StartSound2",
+    ast::Tag::SymbolClass(_) => "SymbolClass",
+    ast::Tag::Telemetry(_) => "Telemetry",
+    ast::Tag::VideoFrame(_) => "VideoFrame",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::timeline::{DisplayList, DisplayObject, Timeline};
+
+  fn define_sound(id: u16) -> ast::Tag {
+    ast::Tag::DefineSound(ast::tags::DefineSound {
+      id,
+      sound_type: ast::SoundType::Mono,
+      sound_size: ast::SoundSize::SoundSize8,
+      sound_rate: ast::SoundRate::SoundRate5500,
+      format: ast::AudioCodingFormat::UncompressedLittleEndian,
+      sample_count: 1,
+      data: vec![128],
+    })
+  }
+
+  fn define_sprite(id: u16, character_id: u16) -> ast::Tag {
+    let mut display_list = DisplayList::new();
+    display_list.insert(1, DisplayObject::new(character_id));
+    let mut timeline = Timeline::new();
+    timeline.add_frame(display_list);
+    ast::Tag::DefineSprite(ast::tags::DefineSprite {
+      id,
+      frame_count: 1,
+      tags: timeline.emit_tags(),
+    })
+  }
+
+  fn export(id: u16, name: &str) -> ast::Tag {
+    ast::Tag::ExportAssets(ast::tags::ExportAssets {
+      assets: vec![ast::NamedId {
+        id,
+        name: String::from(name),
+      }],
+    })
+  }
+
+  #[test]
+  fn test_dictionary_allocate() {
+    let mut dictionary = Dictionary::from_tags(&[define_sound(1), define_sprite(3, 1)]).unwrap();
+    assert_eq!(dictionary.get(3), Some(CharacterKind::Sprite));
+    assert_eq!(dictionary.allocate(CharacterKind::Shape).unwrap(), 2);
+    assert_eq!(dictionary.allocate(CharacterKind::Shape).unwrap(), 4);
+    assert_eq!(dictionary.len(), 4);
+    assert!(dictionary.define(4, CharacterKind::Bitmap).is_err());
+    assert!(Dictionary::from_tags(&[define_sound(1), define_sound(1)]).is_err());
+  }
+
+  #[test]
+  fn test_find_dangling_references() {
+    let tags = vec![
+      define_sprite(2, 1),
+      define_sound(1),
+      export(1, "sound"),
+      export(4, "missing"),
+    ];
+    assert_eq!(
+      find_dangling_references(&tags),
+      vec![
+        DanglingReference {
+          tag: "DefineSprite",
+          character_id: 1,
+        },
+        DanglingReference {
+          tag: "ExportAssets",
+          character_id: 4,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_renumber_characters() {
+    let mut movie = ast::Movie {
+      header: ast::Header {
+        swf_version: 10,
+        frame_size: ast::Rect {
+          x_min: 0,
+          x_max: 0,
+          y_min: 0,
+          y_max: 0,
+        },
+        frame_rate: swf_fixed::Ufixed8P8::from_epsilons(256),
+        frame_count: 0,
+      },
+      tags: vec![define_sound(10), define_sprite(30, 10), export(30, "clip")],
+    };
+    let ids = renumber_characters(&mut movie).unwrap();
+    assert_eq!(ids.get(&10), Some(&1));
+    assert_eq!(ids.get(&30), Some(&2));
+    assert_eq!(
+      movie.tags,
+      vec![define_sound(1), define_sprite(2, 1), export(2, "clip")]
+    );
+
+    movie.tags.push(export(7, "missing"));
+    assert!(renumber_characters(&mut movie).is_err());
+  }
+}
@@ -0,0 +1,508 @@
+//! Import TrueType and OpenType fonts as `DefineFont` tags.
+//!
+//! Glyph outlines are converted to quadratic shape records in the EM square of the requested `EmSquareSize`
+//! (1024 units for `DefineFont2`, 20480 units for `DefineFont3`). Cubic outlines (CFF) are approximated with
+//! quadratic curves. The font layout (ascent, descent, leading, advances, bounds and kerning) is always included.
+//!
+//! Kerning is read from the `kern` table; `GPOS` pair adjustments are not supported.
+
+use std::error::Error;
+use std::fmt;
+
+use swf_types as ast;
+
+use crate::path::{Point, ShapeRecordsBuilder};
+use crate::shape::get_shape_edge_bounds;
+
+#[derive(Debug)]
+pub enum FontError {
+  /// The input is not a valid TrueType or OpenType font.
+  Parse(String),
+  /// The character is outside of the Basic Multilingual Plane and can't be represented by a code unit.
+  UnsupportedCharacter(char),
+  /// The font does not have a glyph for the character.
+  MissingGlyph(char),
+}
+
+impl fmt::Display for FontError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FontError::Parse(ref message) => write!(f, "invalid font: {}", message),
+      FontError::UnsupportedCharacter(c) => write!(f, "character outside of the BMP: {:?}", c),
+      FontError::MissingGlyph(c) => write!(f, "font has no glyph for character: {:?}", c),
+    }
+  }
+}
+
+impl Error for FontError {}
+
+/// Converts the glyphs of a TrueType or OpenType font to a `DefineFont` tag with the provided character id.
+///
+/// Only the glyphs for `characters` are included, sorted by code unit (duplicates are ignored). For font
+/// collections, the first face is used.
+pub fn font_to_define_font(
+  id: u16,
+  data: &[u8],
+  characters: &str,
+  em_square_size: ast::text::EmSquareSize,
+) -> Result<ast::tags::DefineFont, FontError> {
+  let face = ttf_parser::Face::parse(data, 0).map_err(|e| FontError::Parse(e.to_string()))?;
+  let scale = f64::from(get_em_square_units(em_square_size)) / f64::from(face.units_per_em());
+
+  let mut code_units: Vec<u16> = Vec::new();
+  for c in characters.chars() {
+    let code_unit = u16::try_from(u32::from(c)).map_err(|_| FontError::UnsupportedCharacter(c))?;
+    code_units.push(code_unit);
+  }
+  code_units.sort_unstable();
+  code_units.dedup();
+
+  let mut glyph_ids: Vec<ttf_parser::GlyphId> = Vec::with_capacity(code_units.len());
+  for code_unit in code_units.iter() {
+    let c = char::from_u32(u32::from(*code_unit)).unwrap();
+    glyph_ids.push(face.glyph_index(c).ok_or(FontError::MissingGlyph(c))?);
+  }
+
+  // TrueType outer contours are clockwise and CFF outer contours are counter-clockwise (y-up). Once the y-axis is
+  // flipped, the inside of TrueType contours is on the left side of the edges.
+  let is_cubic = face.tables().glyf.is_none();
+  let mut glyphs: Vec<ast::Glyph> = Vec::with_capacity(glyph_ids.len());
+  let mut advances: Vec<u16> = Vec::with_capacity(glyph_ids.len());
+  let mut bounds: Vec<ast::Rect> = Vec::with_capacity(glyph_ids.len());
+  for glyph_id in glyph_ids.iter() {
+    let mut builder = GlyphBuilder::new(scale, is_cubic);
+    face.outline_glyph(*glyph_id, &mut builder);
+    let records = builder.into_records();
+    bounds.push(get_shape_edge_bounds(&ast::Shape {
+      initial_styles: ast::ShapeStyles {
+        fill: Vec::new(),
+        line: Vec::new(),
+      },
+      records: records.clone(),
+    }));
+    glyphs.push(ast::Glyph { records });
+    advances.push(scale_unsigned(
+      face.glyph_hor_advance(*glyph_id).unwrap_or(0).into(),
+      scale,
+    ));
+  }
+
+  let layout = ast::text::FontLayout {
+    ascent: scale_unsigned(face.ascender().into(), scale),
+    descent: scale_unsigned(-i32::from(face.descender()), scale),
+    leading: scale_unsigned(face.line_gap().into(), scale),
+    advances,
+    bounds,
+    kerning: get_kerning(&face, &code_units, &glyph_ids, scale),
+  };
+
+  Ok(ast::tags::DefineFont {
+    id,
+    font_name: get_font_name(&face),
+    is_bold: face.is_bold(),
+    is_italic: face.is_italic(),
+    is_ansi: false,
+    is_small: false,
+    is_shift_jis: false,
+    em_square_size,
+    language: ast::LanguageCode::Auto,
+    glyphs: Some(glyphs),
+    code_units: Some(code_units),
+    layout: Some(layout),
+  })
+}
+
+fn get_em_square_units(value: ast::text::EmSquareSize) -> u16 {
+  match value {
+    ast::text::EmSquareSize::EmSquareSize1024 => 1024,
+    ast::text::EmSquareSize::EmSquareSize20480 => 20480,
+  }
+}
+
+fn scale_unsigned(value: i32, scale: f64) -> u16 {
+  (f64::from(value) * scale).round().clamp(0.0, f64::from(u16::MAX)) as u16
+}
+
+fn get_font_name(face: &ttf_parser::Face<'_>) -> String {
+  face
+    .names()
+    .into_iter()
+    .filter(|name| name.name_id == ttf_parser::name_id::FAMILY)
+    .find_map(|name| name.to_string())
+    .map(|name| name.replace('\0', ""))
+    .unwrap_or_default()
+}
+
+/// Returns the kerning records for all pairs of included glyphs with a non-zero adjustment.
+fn get_kerning(
+  face: &ttf_parser::Face<'_>,
+  code_units: &[u16],
+  glyph_ids: &[ttf_parser::GlyphId],
+  scale: f64,
+) -> Vec<ast::text::KerningRecord> {
+  let kern = match face.tables().kern {
+    Some(kern) => kern,
+    None => return Vec::new(),
+  };
+  let subtables: Vec<ttf_parser::kern::Subtable<'_>> = kern
+    .subtables
+    .into_iter()
+    .filter(|subtable| subtable.horizontal && !subtable.variable && !subtable.has_cross_stream)
+    .collect();
+  let mut kerning: Vec<ast::text::KerningRecord> = Vec::new();
+  if subtables.is_empty() {
+    return kerning;
+  }
+  for (left, left_id) in code_units.iter().zip(glyph_ids.iter()) {
+    for (right, right_id) in code_units.iter().zip(glyph_ids.iter()) {
+      let adjustment: i32 = subtables
+        .iter()
+        .filter_map(|subtable| subtable.glyphs_kerning(*left_id, *right_id))
+        .map(i32::from)
+        .sum();
+      let adjustment = (f64::from(adjustment) * scale)
+        .round()
+        .clamp(f64::from(i16::MIN), f64::from(i16::MAX)) as i16;
+      if adjustment != 0 {
+        kerning.push(ast::text::KerningRecord {
+          left: *left,
+          right: *right,
+          adjustment,
+        });
+      }
+    }
+  }
+  kerning
+}
+
+/// Converts font outlines (y-up, font units) to glyph records (y-down, EM square units).
+struct GlyphBuilder {
+  records: ShapeRecordsBuilder,
+  scale: f64,
+  is_cubic: bool,
+  has_fill: bool,
+}
+
+impl GlyphBuilder {
+  fn new(scale: f64, is_cubic: bool) -> Self {
+    Self {
+      records: ShapeRecordsBuilder::new(),
+      scale,
+      is_cubic,
+      has_fill: false,
+    }
+  }
+
+  fn point(&self, x: f32, y: f32) -> Point {
+    Point::new(f64::from(x) * self.scale, -f64::from(y) * self.scale)
+  }
+
+  fn into_records(self) -> Vec<ast::ShapeRecord> {
+    self.records.into_records()
+  }
+}
+
+impl ttf_parser::OutlineBuilder for GlyphBuilder {
+  fn move_to(&mut self, x: f32, y: f32) {
+    let p = self.point(x, y);
+    self.records.move_to(p);
+    if !self.has_fill {
+      let record = self.records.style_change_mut();
+      if self.is_cubic {
+        record.right_fill = Some(1);
+      } else {
+        record.left_fill = Some(1);
+      }
+      self.has_fill = true;
+    }
+  }
+
+  fn line_to(&mut self, x: f32, y: f32) {
+    let p = self.point(x, y);
+    self.records.line_to(p);
+  }
+
+  fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+    let control = self.point(x1, y1);
+    let p = self.point(x, y);
+    self.records.quad_to(control, p);
+  }
+
+  fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+    let control1 = self.point(x1, y1);
+    let control2 = self.point(x2, y2);
+    let p = self.point(x, y);
+    self.records.cubic_to(control1, control2, p);
+  }
+
+  fn close(&mut self) {
+    self.records.close();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Builds a TrueType font with glyphs for `A` (a square with a quadratic side) and `B` (a square with a hole).
+  ///
+  /// The font uses 1000 units per EM, with a `kern` pair `(A, B) = -50`.
+  fn build_test_font() -> Vec<u8> {
+    fn u16_be(out: &mut Vec<u8>, value: u16) {
+      out.extend_from_slice(&value.to_be_bytes());
+    }
+    fn i16_be(out: &mut Vec<u8>, value: i16) {
+      out.extend_from_slice(&value.to_be_bytes());
+    }
+    fn u32_be(out: &mut Vec<u8>, value: u32) {
+      out.extend_from_slice(&value.to_be_bytes());
+    }
+    // Contours as lists of `(x, y, on_curve)`.
+    fn simple_glyph(contours: &[&[(i16, i16, bool)]]) -> Vec<u8> {
+      let points: Vec<(i16, i16, bool)> = contours.iter().flat_map(|c| c.iter().copied()).collect();
+      let mut out = Vec::new();
+      i16_be(&mut out, contours.len() as i16);
+      i16_be(&mut out, points.iter().map(|p| p.0).min().unwrap());
+      i16_be(&mut out, points.iter().map(|p| p.1).min().unwrap());
+      i16_be(&mut out, points.iter().map(|p| p.0).max().unwrap());
+      i16_be(&mut out, points.iter().map(|p| p.1).max().unwrap());
+      let mut end = 0;
+      for contour in contours {
+        end += contour.len();
+        u16_be(&mut out, (end - 1) as u16);
+      }
+      u16_be(&mut out, 0);
+      for p in points.iter() {
+        out.push(if p.2 { 1 } else { 0 });
+      }
+      let (mut x, mut y) = (0, 0);
+      for p in points.iter() {
+        i16_be(&mut out, p.0 - x);
+        x = p.0;
+      }
+      for p in points.iter() {
+        i16_be(&mut out, p.1 - y);
+        y = p.1;
+      }
+      if out.len() % 2 == 1 {
+        out.push(0);
+      }
+      out
+    }
+
+    let glyphs: Vec<Vec<u8>> = vec![
+      Vec::new(),
+      simple_glyph(&[&[
+        (0, 0, true),
+        (0, 700, true),
+        (300, 900, false),
+        (600, 700, true),
+        (600, 0, true),
+      ]]),
+      simple_glyph(&[
+        &[(0, 0, true), (0, 700, true), (600, 700, true), (600, 0, true)],
+        &[(100, 100, true), (500, 100, true), (500, 600, true), (100, 600, true)],
+      ]),
+    ];
+
+    let mut head = Vec::new();
+    u32_be(&mut head, 0x0001_0000);
+    u32_be(&mut head, 0x0001_0000);
+    u32_be(&mut head, 0);
+    u32_be(&mut head, 0x5F0F_3CF5);
+    u16_be(&mut head, 0);
+    u16_be(&mut head, 1000);
+    head.extend_from_slice(&[0; 16]);
+    for v in [0, 0, 600, 900] {
+      i16_be(&mut head, v);
+    }
+    u16_be(&mut head, 0);
+    u16_be(&mut head, 8);
+    i16_be(&mut head, 2);
+    i16_be(&mut head, 0);
+    i16_be(&mut head, 0);
+
+    let mut hhea = Vec::new();
+    u32_be(&mut hhea, 0x0001_0000);
+    for v in [800, -200, 100] {
+      i16_be(&mut hhea, v);
+    }
+    u16_be(&mut hhea, 700);
+    hhea.extend_from_slice(&[0; 22]);
+    u16_be(&mut hhea, glyphs.len() as u16);
+
+    let mut maxp = Vec::new();
+    u32_be(&mut maxp, 0x0000_5000);
+    u16_be(&mut maxp, glyphs.len() as u16);
+
+    let mut hmtx = Vec::new();
+    for advance in [500, 700, 650] {
+      u16_be(&mut hmtx, advance);
+      i16_be(&mut hmtx, 0);
+    }
+
+    let mut loca = Vec::new();
+    let mut glyf = Vec::new();
+    u16_be(&mut loca, 0);
+    for glyph in glyphs.iter() {
+      glyf.extend_from_slice(glyph);
+      u16_be(&mut loca, (glyf.len() / 2) as u16);
+    }
+
+    let mut cmap = Vec::new();
+    u16_be(&mut cmap, 0);
+    u16_be(&mut cmap, 1);
+    u16_be(&mut cmap, 3);
+    u16_be(&mut cmap, 1);
+    u32_be(&mut cmap, 12);
+    let segments: [(u16, u16, i16); 2] = [(0x41, 0x42, 1 - 0x41), (0xffff, 0xffff, 1)];
+    u16_be(&mut cmap, 4);
+    u16_be(&mut cmap, (16 + segments.len() * 8) as u16);
+    u16_be(&mut cmap, 0);
+    u16_be(&mut cmap, (segments.len() * 2) as u16);
+    u16_be(&mut cmap, 4);
+    u16_be(&mut cmap, 1);
+    u16_be(&mut cmap, 0);
+    for s in segments.iter() {
+      u16_be(&mut cmap, s.1);
+    }
+    u16_be(&mut cmap, 0);
+    for s in segments.iter() {
+      u16_be(&mut cmap, s.0);
+    }
+    for s in segments.iter() {
+      i16_be(&mut cmap, s.2);
+    }
+    for _ in segments.iter() {
+      u16_be(&mut cmap, 0);
+    }
+
+    let mut kern = Vec::new();
+    u16_be(&mut kern, 0);
+    u16_be(&mut kern, 1);
+    u16_be(&mut kern, 0);
+    u16_be(&mut kern, 6 + 8 + 6);
+    u16_be(&mut kern, 0x0001);
+    u16_be(&mut kern, 1);
+    u16_be(&mut kern, 6);
+    u16_be(&mut kern, 0);
+    u16_be(&mut kern, 0);
+    u16_be(&mut kern, 1);
+    u16_be(&mut kern, 2);
+    i16_be(&mut kern, -50);
+
+    let family: Vec<u8> = "Test".encode_utf16().flat_map(|c| c.to_be_bytes()).collect();
+    let mut name = Vec::new();
+    u16_be(&mut name, 0);
+    u16_be(&mut name, 1);
+    u16_be(&mut name, 6 + 12);
+    for v in [3, 1, 0x0409, 1, family.len() as u16, 0] {
+      u16_be(&mut name, v);
+    }
+    name.extend_from_slice(&family);
+
+    let tables: [(&[u8; 4], Vec<u8>); 9] = [
+      (b"cmap", cmap),
+      (b"glyf", glyf),
+      (b"head", head),
+      (b"hhea", hhea),
+      (b"hmtx", hmtx),
+      (b"kern", kern),
+      (b"loca", loca),
+      (b"maxp", maxp),
+      (b"name", name),
+    ];
+    let mut font = Vec::new();
+    u32_be(&mut font, 0x0001_0000);
+    u16_be(&mut font, tables.len() as u16);
+    font.extend_from_slice(&[0; 6]);
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, data) in tables.iter() {
+      font.extend_from_slice(*tag);
+      u32_be(&mut font, 0);
+      u32_be(&mut font, offset as u32);
+      u32_be(&mut font, data.len() as u32);
+      offset += (data.len() + 3) & !3;
+    }
+    for (_, data) in tables.iter() {
+      font.extend_from_slice(data);
+      font.resize((font.len() + 3) & !3, 0);
+    }
+    font
+  }
+
+  fn round_trip(tag: &ast::tags::DefineFont) {
+    let bytes = crate::emit_tag(&ast::Tag::DefineFont(tag.clone()), 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(ast::Tag::DefineFont(tag.clone())));
+  }
+
+  #[test]
+  fn test_font_to_define_font() {
+    let font = build_test_font();
+    let tag = font_to_define_font(1, &font, "BAB", ast::text::EmSquareSize::EmSquareSize1024).unwrap();
+    round_trip(&tag);
+
+    assert_eq!(tag.font_name, "Test");
+    assert_eq!(tag.code_units, Some(vec![0x41, 0x42]));
+    let glyphs = tag.glyphs.as_ref().unwrap();
+    assert_eq!(glyphs.len(), 2);
+    match glyphs[0].records.first() {
+      Some(ast::ShapeRecord::StyleChange(ref record)) => {
+        assert_eq!(record.move_to, Some(ast::Vector2D { x: 0, y: 0 }));
+        assert_eq!(record.left_fill, Some(1));
+      }
+      record => panic!("expected style change, got {:?}", record),
+    }
+    // Move, 3 edges and the closing edge
+    assert_eq!(glyphs[0].records.len(), 5);
+    // Two contours of 4 edges each
+    assert_eq!(glyphs[1].records.len(), 10);
+
+    let layout = tag.layout.as_ref().unwrap();
+    assert_eq!((layout.ascent, layout.descent, layout.leading), (819, 205, 102));
+    assert_eq!(layout.advances, vec![717, 666]);
+    assert_eq!(
+      layout.bounds[0],
+      ast::Rect {
+        x_min: 0,
+        x_max: 614,
+        y_min: -820,
+        y_max: 0,
+      }
+    );
+    assert_eq!(
+      layout.kerning,
+      vec![ast::text::KerningRecord {
+        left: 0x41,
+        right: 0x42,
+        adjustment: -51,
+      }]
+    );
+  }
+
+  #[test]
+  fn test_font_to_define_font_em_square_20480() {
+    let font = build_test_font();
+    let tag = font_to_define_font(1, &font, "A", ast::text::EmSquareSize::EmSquareSize20480).unwrap();
+    round_trip(&tag);
+    let layout = tag.layout.as_ref().unwrap();
+    assert_eq!(layout.ascent, 16384);
+    assert_eq!(layout.advances, vec![14336]);
+    assert_eq!(layout.bounds[0].x_max, 12288);
+  }
+
+  #[test]
+  fn test_font_to_define_font_missing_glyph() {
+    let font = build_test_font();
+    let result = font_to_define_font(1, &font, "AZ", ast::text::EmSquareSize::EmSquareSize1024);
+    assert!(matches!(result, Err(FontError::MissingGlyph('Z'))));
+    let result = font_to_define_font(1, &font, "\u{1F600}", ast::text::EmSquareSize::EmSquareSize1024);
+    assert!(matches!(result, Err(FontError::UnsupportedCharacter('\u{1F600}'))));
+    assert!(matches!(
+      font_to_define_font(1, &[0, 1, 2], "A", ast::text::EmSquareSize::EmSquareSize1024),
+      Err(FontError::Parse(_))
+    ));
+  }
+}
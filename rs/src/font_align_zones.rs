@@ -0,0 +1,348 @@
+//! Compute `DefineFontAlignZones` tags from glyph outlines.
+//!
+//! Each glyph gets one zone with two entries: the dominant vertical stem (x origin and width) and the dominant
+//! horizontal bar (y origin and height), in glyph coordinates. A stem is a pair of parallel edges with opposite
+//! directions facing each other; the dominant stem is the one where the edges overlap the most.
+
+use std::io;
+use swf_types as ast;
+
+/// Maximum ratio between the minor and major components of a stem edge.
+const MAX_SLOPE: f64 = 0.1;
+
+/// Minimum length of a stem edge, relative to the EM square size.
+const MIN_EDGE_LENGTH: f64 = 0.02;
+
+/// Maximum width of a stem, relative to the EM square size.
+const MAX_STEM_WIDTH: f64 = 0.5;
+
+/// Number of segments used to flatten curved edges.
+const CURVE_SEGMENTS: u32 = 4;
+
+/// Average stem widths (relative to the EM square size) below which the font is considered thin or medium.
+const THIN_STEM_WIDTH: f64 = 0.08;
+const MEDIUM_STEM_WIDTH: f64 = 0.14;
+
+/// An edge aligned with an axis: `position` is its coordinate on the cross axis and `start..end` its (sorted) extent
+/// along the axis.
+#[derive(Copy, Clone, Debug)]
+struct AxisEdge {
+  position: f64,
+  start: f64,
+  end: f64,
+  is_forward: bool,
+}
+
+/// Builds the alignment zones of a font.
+///
+/// The CSM table hint is derived from the average width of the vertical stems. Fonts without glyphs produce an empty
+/// list of zones.
+///
+/// Alignment zones are only used by `DefineFont3` fonts: returns an `InvalidInput` error if the font uses the 1024
+/// units EM square of the previous versions.
+///
+/// The origins and sizes are rounded to the nearest 16-bit float, the precision of the tag: returns an `InvalidInput`
+/// error if a value is out of its range.
+pub fn build_define_font_align_zones(font: &ast::tags::DefineFont) -> io::Result<ast::tags::DefineFontAlignZones> {
+  let em_square_size = match font.em_square_size {
+    ast::text::EmSquareSize::EmSquareSize1024 => {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "alignment zones require a DefineFont3 font (20480 units EM square)",
+      ))
+    }
+    ast::text::EmSquareSize::EmSquareSize20480 => 20480.0,
+  };
+  let glyphs: &[ast::Glyph] = font.glyphs.as_deref().unwrap_or(&[]);
+
+  let mut zones: Vec<ast::text::FontAlignmentZone> = Vec::with_capacity(glyphs.len());
+  let mut stem_width_sum: f64 = 0.0;
+  let mut stem_count: usize = 0;
+  for glyph in glyphs {
+    let (vertical, horizontal) = get_axis_edges(glyph, em_square_size);
+    let stem = find_stem(&vertical, em_square_size);
+    let bar = find_stem(&horizontal, em_square_size);
+    if let Some((_, width)) = stem {
+      stem_width_sum += width;
+      stem_count += 1;
+    }
+    let (x_origin, x_size) = stem.unwrap_or((0.0, 0.0));
+    let (y_origin, y_size) = bar.unwrap_or((0.0, 0.0));
+    zones.push(ast::text::FontAlignmentZone {
+      data: vec![
+        ast::text::FontAlignmentZoneData {
+          origin: to_f16(x_origin)?,
+          size: to_f16(x_size)?,
+        },
+        ast::text::FontAlignmentZoneData {
+          origin: to_f16(y_origin)?,
+          size: to_f16(y_size)?,
+        },
+      ],
+      has_x: stem.is_some(),
+      has_y: bar.is_some(),
+    });
+  }
+
+  let csm_table_hint = if stem_count == 0 {
+    ast::text::CsmTableHint::Thin
+  } else {
+    let average_width = stem_width_sum / stem_count as f64 / em_square_size;
+    if average_width < THIN_STEM_WIDTH {
+      ast::text::CsmTableHint::Thin
+    } else if average_width < MEDIUM_STEM_WIDTH {
+      ast::text::CsmTableHint::Medium
+    } else {
+      ast::text::CsmTableHint::Thick
+    }
+  };
+
+  Ok(ast::tags::DefineFontAlignZones {
+    font_id: font.id,
+    csm_table_hint,
+    zones,
+  })
+}
+
+/// Rounds the value to the nearest 16-bit float.
+fn to_f16(value: f64) -> io::Result<f32> {
+  let rounded = half::f16::from_f64(value);
+  if !rounded.is_finite() {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("alignment zone value {} does not fit in a 16-bit float", value),
+    ));
+  }
+  Ok(rounded.to_f32())
+}
+
+/// Returns the vertical and horizontal edges of the glyph (curves are flattened).
+fn get_axis_edges(glyph: &ast::Glyph, em_square_size: f64) -> (Vec<AxisEdge>, Vec<AxisEdge>) {
+  let min_length = MIN_EDGE_LENGTH * em_square_size;
+  let mut vertical: Vec<AxisEdge> = Vec::new();
+  let mut horizontal: Vec<AxisEdge> = Vec::new();
+  let mut add_segment = |x0: f64, y0: f64, x1: f64, y1: f64| {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    if dy.abs() >= min_length && dx.abs() <= dy.abs() * MAX_SLOPE {
+      vertical.push(AxisEdge {
+        position: (x0 + x1) / 2.0,
+        start: y0.min(y1),
+        end: y0.max(y1),
+        is_forward: dy > 0.0,
+      });
+    } else if dx.abs() >= min_length && dy.abs() <= dx.abs() * MAX_SLOPE {
+      horizontal.push(AxisEdge {
+        position: (y0 + y1) / 2.0,
+        start: x0.min(x1),
+        end: x0.max(x1),
+        is_forward: dx > 0.0,
+      });
+    }
+  };
+
+  let (mut x, mut y) = (0.0, 0.0);
+  for record in &glyph.records {
+    match record {
+      ast::ShapeRecord::StyleChange(ref record) => {
+        if let Some(move_to) = record.move_to {
+          x = f64::from(move_to.x);
+          y = f64::from(move_to.y);
+        }
+      }
+      ast::ShapeRecord::Edge(ref record) => {
+        let end_x = x + f64::from(record.delta.x);
+        let end_y = y + f64::from(record.delta.y);
+        match record.control_delta {
+          None => add_segment(x, y, end_x, end_y),
+          Some(control_delta) => {
+            let control_x = x + f64::from(control_delta.x);
+            let control_y = y + f64::from(control_delta.y);
+            let (mut previous_x, mut previous_y) = (x, y);
+            for i in 1..=CURVE_SEGMENTS {
+              let t = f64::from(i) / f64::from(CURVE_SEGMENTS);
+              let u = 1.0 - t;
+              let next_x = u * u * x + 2.0 * u * t * control_x + t * t * end_x;
+              let next_y = u * u * y + 2.0 * u * t * control_y + t * t * end_y;
+              add_segment(previous_x, previous_y, next_x, next_y);
+              previous_x = next_x;
+              previous_y = next_y;
+            }
+          }
+        }
+        x = end_x;
+        y = end_y;
+      }
+    }
+  }
+  (vertical, horizontal)
+}
+
+/// Finds the stem with the largest overlap, returning its origin and width (ties favor narrower stems).
+fn find_stem(edges: &[AxisEdge], em_square_size: f64) -> Option<(f64, f64)> {
+  let max_width = MAX_STEM_WIDTH * em_square_size;
+  let mut best: Option<(f64, f64, f64)> = None;
+  for (i, first) in edges.iter().enumerate() {
+    for second in &edges[i + 1..] {
+      if first.is_forward == second.is_forward {
+        continue;
+      }
+      let (low, high) = if first.position <= second.position {
+        (first, second)
+      } else {
+        (second, first)
+      };
+      let width = high.position - low.position;
+      let overlap = low.end.min(high.end) - low.start.max(high.start);
+      if width <= 0.0 || width > max_width || overlap <= 0.0 {
+        continue;
+      }
+      let is_better = match best {
+        None => true,
+        Some((_, best_width, best_overlap)) => {
+          overlap > best_overlap || (overlap == best_overlap && width < best_width)
+        }
+      };
+      if is_better {
+        best = Some((low.position, width, overlap));
+      }
+    }
+  }
+  best.map(|(origin, width, _)| (origin, width))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn polygon(points: &[(i32, i32)]) -> ast::Glyph {
+    let mut records = vec![ast::ShapeRecord::StyleChange(ast::shape_records::StyleChange {
+      move_to: Some(ast::Vector2D {
+        x: points[0].0,
+        y: points[0].1,
+      }),
+      left_fill: Some(1),
+      right_fill: None,
+      line_style: None,
+      new_styles: None,
+    })];
+    for (i, start) in points.iter().enumerate() {
+      let end = points[(i + 1) % points.len()];
+      records.push(ast::ShapeRecord::Edge(ast::shape_records::Edge {
+        delta: ast::Vector2D {
+          x: end.0 - start.0,
+          y: end.1 - start.1,
+        },
+        control_delta: None,
+      }));
+    }
+    ast::Glyph { records }
+  }
+
+  fn font(glyphs: Vec<ast::Glyph>) -> ast::tags::DefineFont {
+    ast::tags::DefineFont {
+      id: 3,
+      font_name: String::from("Test"),
+      is_bold: false,
+      is_italic: false,
+      is_ansi: false,
+      is_small: false,
+      is_shift_jis: false,
+      em_square_size: ast::text::EmSquareSize::EmSquareSize20480,
+      language: ast::LanguageCode::Auto,
+      code_units: Some((0..glyphs.len() as u16).map(|i| 0x41 + i).collect()),
+      glyphs: Some(glyphs),
+      layout: None,
+    }
+  }
+
+  /// Glyph of an `L` without its foot: a vertical stem of the provided width.
+  fn stem(width: i32) -> ast::Glyph {
+    polygon(&[(2000, -14000), (2000 + width, -14000), (2000 + width, 0), (2000, 0)])
+  }
+
+  #[test]
+  fn test_build_define_font_align_zones() {
+    let glyph_t = polygon(&[
+      (0, -14000),
+      (12000, -14000),
+      (12000, -12000),
+      (7000, -12000),
+      (7000, 0),
+      (5000, 0),
+      (5000, -12000),
+      (0, -12000),
+    ]);
+    let glyph_o = ast::Glyph { records: Vec::new() };
+    let tag = build_define_font_align_zones(&font(vec![stem(2000), glyph_t, glyph_o])).unwrap();
+
+    assert_eq!(tag.font_id, 3);
+    assert_eq!(tag.csm_table_hint, ast::text::CsmTableHint::Medium);
+    let zone = |x: (f32, f32), y: (f32, f32), has_x: bool, has_y: bool| ast::text::FontAlignmentZone {
+      data: vec![
+        ast::text::FontAlignmentZoneData { origin: x.0, size: x.1 },
+        ast::text::FontAlignmentZoneData { origin: y.0, size: y.1 },
+      ],
+      has_x,
+      has_y,
+    };
+    assert_eq!(
+      tag.zones,
+      vec![
+        zone((2000.0, 2000.0), (0.0, 0.0), true, false),
+        zone((5000.0, 2000.0), (-14000.0, 2000.0), true, true),
+        zone((0.0, 0.0), (0.0, 0.0), false, false),
+      ]
+    );
+
+    let bytes = crate::emit_tag(&ast::Tag::DefineFontAlignZones(tag.clone()), 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(ast::Tag::DefineFontAlignZones(tag)));
+  }
+
+  #[test]
+  fn test_build_define_font_align_zones_curved_stem() {
+    // The left edge bulges inside the stem: the narrowest flattened segments are used
+    let mut glyph = stem(2000);
+    glyph.records[4] = ast::ShapeRecord::Edge(ast::shape_records::Edge {
+      delta: ast::Vector2D { x: 0, y: -14000 },
+      control_delta: Some(ast::Vector2D { x: 100, y: -7000 }),
+    });
+    let tag = build_define_font_align_zones(&font(vec![glyph])).unwrap();
+    let data = &tag.zones[0].data[0];
+    assert!(tag.zones[0].has_x);
+    // 2043.75 and 1956.25, rounded to 16-bit floats
+    assert_eq!((data.origin, data.size), (2044.0, 1956.0));
+
+    let bytes = crate::emit_tag(&ast::Tag::DefineFontAlignZones(tag.clone()), 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(ast::Tag::DefineFontAlignZones(tag)));
+  }
+
+  #[test]
+  fn test_build_define_font_align_zones_rejects_out_of_range_values() {
+    let glyph = polygon(&[(70000, -14000), (72000, -14000), (72000, 0), (70000, 0)]);
+    let error = build_define_font_align_zones(&font(vec![glyph])).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+  }
+
+  #[test]
+  fn test_build_define_font_align_zones_csm_table_hint() {
+    let hint = |glyphs: Vec<ast::Glyph>| build_define_font_align_zones(&font(glyphs)).unwrap().csm_table_hint;
+    assert_eq!(hint(vec![stem(1000), stem(1200)]), ast::text::CsmTableHint::Thin);
+    assert_eq!(hint(vec![stem(4000)]), ast::text::CsmTableHint::Thick);
+    assert_eq!(hint(vec![stem(1000), stem(4000)]), ast::text::CsmTableHint::Medium);
+    assert_eq!(hint(Vec::new()), ast::text::CsmTableHint::Thin);
+  }
+
+  #[test]
+  fn test_build_define_font_align_zones_rejects_legacy_em_square() {
+    let legacy_font = ast::tags::DefineFont {
+      em_square_size: ast::text::EmSquareSize::EmSquareSize1024,
+      ..font(vec![stem(100)])
+    };
+    let error = build_define_font_align_zones(&legacy_font).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+  }
+}
@@ -0,0 +1,528 @@
+//! Remove unused glyphs from embedded fonts.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use swf_types as ast;
+
+/// Removes the glyphs that are not used by the text fields of the movie.
+///
+/// A glyph is used if it is referenced by a `DefineText` record, or if its code unit appears in the initial text of a
+/// `DefineDynamicText` using the font. Unused glyphs are removed from `DefineFont` and `DefineGlyphFont` tags (with
+/// their layout, kerning and alignment zones), the `DefineFontInfo` code tables are filtered accordingly and the glyph
+/// indices of the text records are remapped.
+///
+/// Fonts that are not referenced by any text field, exported with `ExportAssets` or bound to a class with
+/// `SymbolClass` are left unchanged since their characters are only known at runtime. This is also the case of the
+/// fonts used by a `DefineDynamicText` whose text can change: fields that are editable, have a variable, are placed
+/// with an instance name, or are exported or bound to a class. Fonts used by a `DefineDynamicText` without code table
+/// and fonts referenced with out of range glyph indices are also left unchanged.
+///
+/// HTML text fields select their fonts by name (`<font face="...">`) and their text contains markup and entities: if
+/// a `DefineDynamicText` uses HTML, no font is subset.
+pub fn subset_fonts(tags: &mut [ast::Tag]) {
+  let remaps = get_glyph_remaps(tags);
+  if remaps.is_empty() {
+    return;
+  }
+  for tag in tags.iter_mut() {
+    match tag {
+      ast::Tag::DefineFont(ref mut tag) => {
+        if let Some(remap) = remaps.get(&tag.id) {
+          subset_define_font(tag, remap);
+        }
+      }
+      ast::Tag::DefineGlyphFont(ref mut tag) => {
+        if let Some(remap) = remaps.get(&tag.id) {
+          tag.glyphs = retain_used(std::mem::take(&mut tag.glyphs), remap);
+        }
+      }
+      ast::Tag::DefineFontInfo(ref mut tag) => {
+        if let Some(remap) = remaps.get(&tag.font_id) {
+          tag.code_units = retain_used(std::mem::take(&mut tag.code_units), remap);
+        }
+      }
+      ast::Tag::DefineFontAlignZones(ref mut tag) => {
+        if let Some(remap) = remaps.get(&tag.font_id) {
+          tag.zones = retain_used(std::mem::take(&mut tag.zones), remap);
+        }
+      }
+      ast::Tag::DefineText(ref mut tag) => {
+        let mut font_id: Option<u16> = None;
+        for record in tag.records.iter_mut() {
+          if record.font_id.is_some() {
+            font_id = record.font_id;
+          }
+          if let Some(remap) = font_id.and_then(|id| remaps.get(&id)) {
+            for entry in record.entries.iter_mut() {
+              entry.index = remap[entry.index].unwrap();
+            }
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Glyph index remapping: `remap[old_index]` is the new index of the glyph, or `None` if it is removed.
+type GlyphRemap = Vec<Option<usize>>;
+
+/// Returns the glyph remapping for each font that can be subset.
+fn get_glyph_remaps(tags: &[ast::Tag]) -> HashMap<u16, GlyphRemap> {
+  let mut glyph_counts: HashMap<u16, usize> = HashMap::new();
+  let mut code_tables: HashMap<u16, &[u16]> = HashMap::new();
+  let mut used_glyphs: HashMap<u16, BTreeSet<usize>> = HashMap::new();
+  let mut used_code_units: HashMap<u16, HashSet<u16>> = HashMap::new();
+  let mut excluded: HashSet<u16> = HashSet::new();
+  let mut dynamic_texts: Vec<(u16, u16, bool)> = Vec::new();
+  let mut named_characters: HashSet<u16> = HashSet::new();
+  collect_named_characters(tags, &mut named_characters);
+  let mut has_html = false;
+
+  for tag in tags {
+    match tag {
+      ast::Tag::DefineFont(ref tag) => {
+        if let Some(ref glyphs) = tag.glyphs {
+          glyph_counts.insert(tag.id, glyphs.len());
+        }
+        if let Some(ref code_units) = tag.code_units {
+          code_tables.insert(tag.id, code_units);
+        }
+      }
+      ast::Tag::DefineGlyphFont(ref tag) => {
+        glyph_counts.insert(tag.id, tag.glyphs.len());
+      }
+      ast::Tag::DefineFontInfo(ref tag) => {
+        code_tables.entry(tag.font_id).or_insert(&tag.code_units);
+      }
+      ast::Tag::DefineText(ref tag) => {
+        let mut font_id: Option<u16> = None;
+        for record in tag.records.iter() {
+          if record.font_id.is_some() {
+            font_id = record.font_id;
+          }
+          if let Some(font_id) = font_id {
+            used_glyphs
+              .entry(font_id)
+              .or_default()
+              .extend(record.entries.iter().map(|entry| entry.index));
+          }
+        }
+      }
+      ast::Tag::DefineDynamicText(ref tag) => {
+        has_html |= tag.html;
+        if let Some(font_id) = tag.font_id {
+          // The text of a field with a variable can be changed by ActionScript, even if it is read-only
+          let is_fixed = tag.readonly && tag.variable_name.is_none();
+          dynamic_texts.push((tag.id, font_id, is_fixed));
+          let code_units = used_code_units.entry(font_id).or_default();
+          if let Some(ref text) = tag.text {
+            code_units.extend(text.encode_utf16());
+          }
+        }
+      }
+      ast::Tag::ExportAssets(ref tag) => excluded.extend(tag.assets.iter().map(|asset| asset.id)),
+      ast::Tag::SymbolClass(ref tag) => excluded.extend(tag.symbols.iter().map(|symbol| symbol.id)),
+      _ => {}
+    }
+  }
+
+  if has_html {
+    return HashMap::new();
+  }
+
+  // Instance names and exports also give ActionScript access to the text
+  for (text_id, font_id, is_fixed) in dynamic_texts {
+    if !is_fixed || named_characters.contains(&text_id) || excluded.contains(&text_id) {
+      excluded.insert(font_id);
+    }
+  }
+
+  for (font_id, code_units) in used_code_units {
+    let code_table = match code_tables.get(&font_id) {
+      Some(code_table) => code_table,
+      None => {
+        // The glyphs of the text are unknown without code table
+        excluded.insert(font_id);
+        continue;
+      }
+    };
+    used_glyphs.entry(font_id).or_default().extend(
+      code_table
+        .iter()
+        .enumerate()
+        .filter(|(_, code_unit)| code_units.contains(code_unit))
+        .map(|(index, _)| index),
+    );
+  }
+
+  let mut remaps: HashMap<u16, GlyphRemap> = HashMap::new();
+  for (font_id, used) in used_glyphs {
+    let glyph_count = match glyph_counts.get(&font_id) {
+      Some(glyph_count) => *glyph_count,
+      None => continue,
+    };
+    if excluded.contains(&font_id) || used.iter().any(|index| *index >= glyph_count) {
+      continue;
+    }
+    let mut remap: GlyphRemap = vec![None; glyph_count];
+    for (new_index, old_index) in used.into_iter().enumerate() {
+      remap[old_index] = Some(new_index);
+    }
+    remaps.insert(font_id, remap);
+  }
+  remaps
+}
+
+/// Adds the characters placed with an instance name, including in sprites.
+///
+/// The name can be set by a later update of the depth, without character id.
+fn collect_named_characters(tags: &[ast::Tag], named_characters: &mut HashSet<u16>) {
+  let mut depths: HashMap<u16, u16> = HashMap::new();
+  for tag in tags {
+    match tag {
+      ast::Tag::PlaceObject(ref tag) => {
+        if let Some(character_id) = tag.character_id {
+          depths.insert(tag.depth, character_id);
+        }
+        if let (Some(character_id), Some(_)) = (depths.get(&tag.depth), &tag.name) {
+          named_characters.insert(*character_id);
+        }
+      }
+      ast::Tag::RemoveObject(ref tag) => {
+        depths.remove(&tag.depth);
+      }
+      ast::Tag::DefineSprite(ref tag) => collect_named_characters(&tag.tags, named_characters),
+      _ => {}
+    }
+  }
+}
+
+fn subset_define_font(tag: &mut ast::tags::DefineFont, remap: &[Option<usize>]) {
+  if let Some(glyphs) = tag.glyphs.take() {
+    tag.glyphs = Some(retain_used(glyphs, remap));
+  }
+  let kept_code_units: Option<HashSet<u16>> = match tag.code_units.take() {
+    Some(code_units) => {
+      let code_units = retain_used(code_units, remap);
+      let kept = code_units.iter().copied().collect();
+      tag.code_units = Some(code_units);
+      Some(kept)
+    }
+    None => None,
+  };
+  if let Some(ref mut layout) = tag.layout {
+    layout.advances = retain_used(std::mem::take(&mut layout.advances), remap);
+    layout.bounds = retain_used(std::mem::take(&mut layout.bounds), remap);
+    if let Some(kept) = kept_code_units {
+      layout
+        .kerning
+        .retain(|record| kept.contains(&record.left) && kept.contains(&record.right));
+    }
+  }
+}
+
+/// Keeps the per-glyph values of the used glyphs, in glyph order.
+fn retain_used<T>(values: Vec<T>, remap: &[Option<usize>]) -> Vec<T> {
+  values
+    .into_iter()
+    .enumerate()
+    .filter(|(index, _)| matches!(remap.get(*index), Some(Some(_))))
+    .map(|(_, value)| value)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn define_font(id: u16) -> ast::tags::DefineFont {
+    let code_units: Vec<u16> = "abcd".encode_utf16().collect();
+    ast::tags::DefineFont {
+      id,
+      font_name: String::from("Test"),
+      is_bold: false,
+      is_italic: false,
+      is_ansi: false,
+      is_small: false,
+      is_shift_jis: false,
+      em_square_size: ast::text::EmSquareSize::EmSquareSize1024,
+      language: ast::LanguageCode::Auto,
+      glyphs: Some(vec![ast::Glyph { records: Vec::new() }; code_units.len()]),
+      layout: Some(ast::text::FontLayout {
+        ascent: 800,
+        descent: 200,
+        leading: 0,
+        advances: vec![100, 200, 300, 400],
+        bounds: vec![
+          ast::Rect {
+            x_min: 0,
+            x_max: 0,
+            y_min: 0,
+            y_max: 0,
+          };
+          code_units.len()
+        ],
+        kerning: vec![
+          ast::text::KerningRecord {
+            left: u16::from(b'a'),
+            right: u16::from(b'c'),
+            adjustment: -10,
+          },
+          ast::text::KerningRecord {
+            left: u16::from(b'a'),
+            right: u16::from(b'b'),
+            adjustment: -20,
+          },
+        ],
+      }),
+      code_units: Some(code_units),
+    }
+  }
+
+  fn define_text(font_id: u16, indices: &[usize]) -> ast::tags::DefineText {
+    ast::tags::DefineText {
+      id: 10,
+      bounds: ast::Rect {
+        x_min: 0,
+        x_max: 1000,
+        y_min: 0,
+        y_max: 1000,
+      },
+      matrix: ast::Matrix::default(),
+      records: vec![ast::text::TextRecord {
+        font_id: Some(font_id),
+        color: None,
+        offset_x: 0,
+        offset_y: 0,
+        font_size: Some(240),
+        entries: indices
+          .iter()
+          .map(|index| ast::text::GlyphEntry {
+            index: *index,
+            advance: 100,
+          })
+          .collect(),
+      }],
+    }
+  }
+
+  fn define_dynamic_text(font_id: u16, text: &str) -> ast::tags::DefineDynamicText {
+    ast::tags::DefineDynamicText {
+      id: 11,
+      bounds: ast::Rect {
+        x_min: 0,
+        x_max: 1000,
+        y_min: 0,
+        y_max: 1000,
+      },
+      word_wrap: false,
+      multiline: false,
+      password: false,
+      readonly: true,
+      auto_size: false,
+      no_select: false,
+      border: false,
+      was_static: false,
+      html: false,
+      use_glyph_font: true,
+      font_id: Some(font_id),
+      font_class: None,
+      font_size: Some(240),
+      color: None,
+      max_length: None,
+      align: ast::text::TextAlignment::Left,
+      margin_left: 0,
+      margin_right: 0,
+      indent: 0,
+      leading: 0,
+      variable_name: None,
+      text: Some(String::from(text)),
+    }
+  }
+
+  #[test]
+  fn test_subset_fonts() {
+    let mut tags = vec![
+      ast::Tag::DefineFont(define_font(1)),
+      ast::Tag::DefineFontAlignZones(ast::tags::DefineFontAlignZones {
+        font_id: 1,
+        csm_table_hint: ast::text::CsmTableHint::Thin,
+        zones: (0..4)
+          .map(|i| ast::text::FontAlignmentZone {
+            data: vec![ast::text::FontAlignmentZoneData {
+              origin: i as f32,
+              size: 1.0,
+            }],
+            has_x: false,
+            has_y: true,
+          })
+          .collect(),
+      }),
+      ast::Tag::DefineGlyphFont(ast::tags::DefineGlyphFont {
+        id: 2,
+        glyphs: vec![ast::Glyph { records: Vec::new() }; 3],
+      }),
+      ast::Tag::DefineFontInfo(ast::tags::DefineFontInfo {
+        font_id: 2,
+        font_name: String::from("Glyphs"),
+        is_bold: false,
+        is_italic: false,
+        is_ansi: false,
+        is_shift_jis: false,
+        is_small: false,
+        language: ast::LanguageCode::Auto,
+        code_units: "xyz".encode_utf16().collect(),
+      }),
+      ast::Tag::DefineText(define_text(1, &[2, 2])),
+      ast::Tag::DefineText(define_text(2, &[1])),
+      ast::Tag::DefineDynamicText(define_dynamic_text(1, "a")),
+    ];
+    subset_fonts(&mut tags);
+
+    match &tags[0] {
+      ast::Tag::DefineFont(ref font) => {
+        assert_eq!(font.code_units, Some("ac".encode_utf16().collect()));
+        assert_eq!(font.glyphs.as_ref().map(Vec::len), Some(2));
+        let layout = font.layout.as_ref().unwrap();
+        assert_eq!(layout.advances, vec![100, 300]);
+        assert_eq!(layout.bounds.len(), 2);
+        assert_eq!(layout.kerning.len(), 1);
+        assert_eq!(layout.kerning[0].adjustment, -10);
+      }
+      tag => panic!("unexpected tag: {:?}", tag),
+    }
+    match &tags[1] {
+      ast::Tag::DefineFontAlignZones(ref zones) => {
+        let origins: Vec<f32> = zones.zones.iter().map(|zone| zone.data[0].origin).collect();
+        assert_eq!(origins, vec![0.0, 2.0]);
+      }
+      tag => panic!("unexpected tag: {:?}", tag),
+    }
+    assert_eq!(
+      tags[2],
+      ast::Tag::DefineGlyphFont(ast::tags::DefineGlyphFont {
+        id: 2,
+        glyphs: vec![ast::Glyph { records: Vec::new() }],
+      })
+    );
+    match &tags[3] {
+      ast::Tag::DefineFontInfo(ref info) => assert_eq!(info.code_units, vec![u16::from(b'y')]),
+      tag => panic!("unexpected tag: {:?}", tag),
+    }
+    assert_eq!(tags[4], ast::Tag::DefineText(define_text(1, &[1, 1])));
+    assert_eq!(tags[5], ast::Tag::DefineText(define_text(2, &[0])));
+  }
+
+  #[test]
+  fn test_subset_fonts_keeps_unsafe_fonts() {
+    let mut tags = vec![
+      ast::Tag::DefineFont(define_font(1)),
+      ast::Tag::DefineFont(define_font(2)),
+      ast::Tag::DefineFont(define_font(3)),
+      ast::Tag::DefineText(define_text(1, &[0])),
+      ast::Tag::DefineText(define_text(3, &[0, 9])),
+      ast::Tag::DefineDynamicText(ast::tags::DefineDynamicText {
+        readonly: false,
+        ..define_dynamic_text(2, "a")
+      }),
+      ast::Tag::ExportAssets(ast::tags::ExportAssets {
+        assets: vec![ast::NamedId {
+          id: 1,
+          name: String::from("Font"),
+        }],
+      }),
+    ];
+    let expected = tags.clone();
+    subset_fonts(&mut tags);
+    assert_eq!(tags, expected);
+  }
+
+  #[test]
+  fn test_subset_fonts_keeps_fonts_of_scriptable_text() {
+    let mut named_text = define_dynamic_text(2, "a");
+    named_text.id = 12;
+    let mut tags = vec![
+      ast::Tag::DefineFont(define_font(1)),
+      ast::Tag::DefineFont(define_font(2)),
+      ast::Tag::DefineDynamicText(ast::tags::DefineDynamicText {
+        variable_name: Some(String::from("score")),
+        ..define_dynamic_text(1, "a")
+      }),
+      ast::Tag::DefineDynamicText(named_text),
+      ast::Tag::DefineSprite(ast::tags::DefineSprite {
+        id: 13,
+        frame_count: 1,
+        tags: vec![
+          ast::Tag::PlaceObject(ast::tags::PlaceObject {
+            is_update: false,
+            depth: 1,
+            character_id: Some(12),
+            class_name: None,
+            matrix: None,
+            color_transform: None,
+            ratio: None,
+            name: None,
+            clip_depth: None,
+            filters: None,
+            blend_mode: None,
+            bitmap_cache: None,
+            visible: None,
+            background_color: None,
+            clip_actions: None,
+          }),
+          ast::Tag::ShowFrame,
+          // The instance name is only set by an update of the depth
+          ast::Tag::PlaceObject(ast::tags::PlaceObject {
+            is_update: true,
+            depth: 1,
+            character_id: None,
+            class_name: None,
+            matrix: None,
+            color_transform: None,
+            ratio: None,
+            name: Some(String::from("label")),
+            clip_depth: None,
+            filters: None,
+            blend_mode: None,
+            bitmap_cache: None,
+            visible: None,
+            background_color: None,
+            clip_actions: None,
+          }),
+          ast::Tag::ShowFrame,
+        ],
+      }),
+    ];
+    let expected = tags.clone();
+    subset_fonts(&mut tags);
+    assert_eq!(tags, expected);
+  }
+
+  #[test]
+  fn test_subset_fonts_keeps_fonts_with_html_text() {
+    let mut tags = vec![
+      ast::Tag::DefineFont(define_font(1)),
+      ast::Tag::DefineFont(define_font(2)),
+      ast::Tag::DefineDynamicText(ast::tags::DefineDynamicText {
+        html: true,
+        ..define_dynamic_text(1, r#"<font face="Test">&#98;</font>"#)
+      }),
+      ast::Tag::DefineText(define_text(2, &[0])),
+    ];
+    let expected = tags.clone();
+    subset_fonts(&mut tags);
+    assert_eq!(tags, expected);
+  }
+
+  #[test]
+  fn test_subset_fonts_keeps_fonts_without_code_table() {
+    let font = ast::Tag::DefineGlyphFont(ast::tags::DefineGlyphFont {
+      id: 2,
+      glyphs: vec![ast::Glyph { records: Vec::new() }; 3],
+    });
+    let mut tags = vec![font.clone(), ast::Tag::DefineDynamicText(define_dynamic_text(2, "a"))];
+    subset_fonts(&mut tags);
+    assert_eq!(tags[0], font);
+  }
+}
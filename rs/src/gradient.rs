@@ -5,6 +5,22 @@ use swf_types as ast;
 
 use crate::basic_data_types::{emit_s_rgb8, emit_straight_s_rgba8};
 use crate::primitives::emit_u8;
+use crate::shape::ShapeVersion;
+
+/// Maximum number of color stops in a gradient of a `DefineShape4` tag (stored on 4 bits).
+pub const MAX_GRADIENT_STOPS: usize = 15;
+
+/// Maximum number of color stops in a gradient of a `DefineShape`, `DefineShape2`, `DefineShape3` or morph shape tag.
+pub const MAX_LEGACY_GRADIENT_STOPS: usize = 8;
+
+/// Returns the maximum number of color stops in a gradient for the provided shape version.
+pub fn get_max_gradient_stops(version: ShapeVersion) -> usize {
+  if version >= ShapeVersion::Shape4 {
+    MAX_GRADIENT_STOPS
+  } else {
+    MAX_LEGACY_GRADIENT_STOPS
+  }
+}
 
 fn gradient_spread_to_code(value: ast::GradientSpread) -> u8 {
   match value {
@@ -26,10 +42,10 @@ pub(crate) fn emit_gradient<W: io::Write + ?Sized>(
   value: &ast::Gradient,
   with_alpha: bool,
 ) -> io::Result<()> {
-  assert!(value.colors.len() <= 0x0f);
+  let color_count = get_color_count(value.colors.len(), MAX_GRADIENT_STOPS)?;
   #[allow(clippy::identity_op)]
   let flags: u8 = 0
-    | ((u8::try_from(value.colors.len()).unwrap() & 0x0f) << 0)
+    | ((color_count & 0x0f) << 0)
     | ((gradient_spread_to_code(value.spread) & 0b11) << 4)
     | ((color_space_to_code(value.color_space) & 0b11) << 6);
   emit_u8(writer, flags)?;
@@ -41,6 +57,19 @@ pub(crate) fn emit_gradient<W: io::Write + ?Sized>(
   Ok(())
 }
 
+fn get_color_count(len: usize, max: usize) -> io::Result<u8> {
+  if len > max {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!(
+        "gradient has {} color stops, at most {} are supported (see `resample_gradient`)",
+        len, max
+      ),
+    ));
+  }
+  Ok(u8::try_from(len).unwrap())
+}
+
 pub(crate) fn emit_color_stop<W: io::Write + ?Sized>(
   writer: &mut W,
   value: &ast::ColorStop,
@@ -50,7 +79,7 @@ pub(crate) fn emit_color_stop<W: io::Write + ?Sized>(
   if with_alpha {
     emit_straight_s_rgba8(writer, value.color)
   } else {
-    assert!(value.color.a == u8::max_value());
+    assert!(value.color.a == u8::MAX);
     emit_s_rgb8(
       writer,
       ast::SRgb8 {
@@ -63,10 +92,11 @@ pub(crate) fn emit_color_stop<W: io::Write + ?Sized>(
 }
 
 pub(crate) fn emit_morph_gradient<W: io::Write + ?Sized>(writer: &mut W, value: &ast::MorphGradient) -> io::Result<()> {
-  assert!(value.colors.len() <= 0x0f);
+  // Morph shapes only support the legacy limit, even in `DefineMorphShape2`.
+  let color_count = get_color_count(value.colors.len(), MAX_LEGACY_GRADIENT_STOPS)?;
   #[allow(clippy::identity_op)]
   let flags: u8 = 0
-    | ((u8::try_from(value.colors.len()).unwrap() & 0x0f) << 0)
+    | ((color_count & 0x0f) << 0)
     | ((gradient_spread_to_code(value.spread) & 0b11) << 4)
     | ((color_space_to_code(value.color_space) & 0b11) << 6);
   emit_u8(writer, flags)?;
@@ -99,3 +129,207 @@ pub(crate) fn emit_morph_color_stop<W: io::Write + ?Sized>(
     true,
   )
 }
+
+/// Returns a gradient with at most `max_stops` color stops approximating `value`.
+///
+/// The first and last stops are kept. Interior stops are removed one at a time, always picking the stop whose
+/// removal causes the lowest perceptual error against the original gradient. Use `get_max_gradient_stops` to
+/// get the limit for a shape version.
+pub fn resample_gradient(value: &ast::Gradient, max_stops: usize) -> ast::Gradient {
+  let max_stops = max_stops.max(2);
+  let mut kept: Vec<usize> = (0..value.colors.len()).collect();
+  while kept.len() > max_stops {
+    let (index, _) = (1..kept.len() - 1)
+      .map(|i| (i, get_removal_error(value, kept[i - 1], kept[i + 1])))
+      .fold(
+        (1, f64::INFINITY),
+        |best, candidate| {
+          if candidate.1 < best.1 {
+            candidate
+          } else {
+            best
+          }
+        },
+      );
+    kept.remove(index);
+  }
+  ast::Gradient {
+    spread: value.spread,
+    color_space: value.color_space,
+    colors: kept.into_iter().map(|i| value.colors[i]).collect(),
+  }
+}
+
+/// Returns the maximum error between the original gradient and a straight interpolation from the stop `start`
+/// to the stop `end`, over the ratios between these stops.
+fn get_removal_error(value: &ast::Gradient, start: usize, end: usize) -> f64 {
+  let start_stop = &value.colors[start];
+  let end_stop = &value.colors[end];
+  let mut error: f64 = 0.0;
+  for ratio in start_stop.ratio..=end_stop.ratio {
+    let expected = get_gradient_color(value, ratio);
+    let t = get_ratio_position(ratio, start_stop.ratio, end_stop.ratio);
+    let actual = interpolate_color(start_stop.color, end_stop.color, t, value.color_space);
+    error = error.max(get_color_distance(expected, actual));
+  }
+  // Stops sharing a ratio define a hard transition: removing them changes the color on one side of it.
+  for stop in &value.colors[start + 1..end] {
+    let expected = [stop.color.r, stop.color.g, stop.color.b, stop.color.a].map(f64::from);
+    let t = get_ratio_position(stop.ratio, start_stop.ratio, end_stop.ratio);
+    let actual = interpolate_color(start_stop.color, end_stop.color, t, value.color_space);
+    error = error.max(get_color_distance(expected, actual));
+  }
+  error
+}
+
+/// Returns the color of the gradient at the provided ratio, as `[r, g, b, a]` floating point channels.
+fn get_gradient_color(value: &ast::Gradient, ratio: u8) -> [f64; 4] {
+  let colors = &value.colors;
+  let next = colors.iter().position(|stop| stop.ratio >= ratio);
+  match next {
+    None => color_to_channels(colors[colors.len() - 1].color),
+    Some(0) => color_to_channels(colors[0].color),
+    Some(i) => {
+      let (start, end) = (&colors[i - 1], &colors[i]);
+      let t = get_ratio_position(ratio, start.ratio, end.ratio);
+      interpolate_color(start.color, end.color, t, value.color_space)
+    }
+  }
+}
+
+/// Returns the position of `ratio` between `start` and `end`, in `[0, 1]`.
+fn get_ratio_position(ratio: u8, start: u8, end: u8) -> f64 {
+  if end <= start {
+    0.0
+  } else {
+    ((f64::from(ratio) - f64::from(start)) / (f64::from(end) - f64::from(start))).clamp(0.0, 1.0)
+  }
+}
+
+fn color_to_channels(color: ast::StraightSRgba8) -> [f64; 4] {
+  [color.r, color.g, color.b, color.a].map(f64::from)
+}
+
+fn interpolate_color(
+  start: ast::StraightSRgba8,
+  end: ast::StraightSRgba8,
+  t: f64,
+  color_space: ast::ColorSpace,
+) -> [f64; 4] {
+  let start = color_to_channels(start);
+  let end = color_to_channels(end);
+  let mut result = [0.0; 4];
+  for channel in 0..4 {
+    let is_linear = color_space == ast::ColorSpace::LinearRgb && channel < 3;
+    result[channel] = if is_linear {
+      let start = srgb_to_linear(start[channel]);
+      let end = srgb_to_linear(end[channel]);
+      linear_to_srgb(start + (end - start) * t)
+    } else {
+      start[channel] + (end[channel] - start[channel]) * t
+    };
+  }
+  result
+}
+
+fn srgb_to_linear(value: f64) -> f64 {
+  let value = value / 255.0;
+  if value <= 0.04045 {
+    value / 12.92
+  } else {
+    ((value + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+fn linear_to_srgb(value: f64) -> f64 {
+  let value = if value <= 0.0031308 {
+    value * 12.92
+  } else {
+    1.055 * value.powf(1.0 / 2.4) - 0.055
+  };
+  value * 255.0
+}
+
+/// Perceptual distance between two colors, using the "redmean" weighted RGB distance and the alpha difference.
+fn get_color_distance(left: [f64; 4], right: [f64; 4]) -> f64 {
+  let red_mean = (left[0] + right[0]) / 2.0;
+  let (dr, dg, db, da) = (
+    left[0] - right[0],
+    left[1] - right[1],
+    left[2] - right[2],
+    left[3] - right[3],
+  );
+  let rgb = (2.0 + red_mean / 256.0) * dr * dr + 4.0 * dg * dg + (2.0 + (255.0 - red_mean) / 256.0) * db * db;
+  (rgb + 3.0 * da * da).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn stop(ratio: u8, gray: u8) -> ast::ColorStop {
+    ast::ColorStop {
+      ratio,
+      color: ast::StraightSRgba8 {
+        r: gray,
+        g: gray,
+        b: gray,
+        a: 255,
+      },
+    }
+  }
+
+  #[test]
+  fn test_resample_gradient() {
+    // 30 stops on a linear ramp, except for a peak at ratio 100.
+    let colors: Vec<ast::ColorStop> = (0..30)
+      .map(|i| {
+        let ratio = (i * 255 / 29) as u8;
+        stop(ratio, if i == 11 { 0 } else { ratio })
+      })
+      .collect();
+    let gradient = ast::Gradient {
+      spread: ast::GradientSpread::Pad,
+      color_space: ast::ColorSpace::SRgb,
+      colors,
+    };
+    let resampled = resample_gradient(&gradient, get_max_gradient_stops(ShapeVersion::Shape4));
+    assert_eq!(resampled.colors.len(), 15);
+    assert_eq!(resampled.colors[0], gradient.colors[0]);
+    assert_eq!(resampled.colors[14], gradient.colors[29]);
+    assert!(resampled.colors.contains(&gradient.colors[11]));
+
+    let resampled = resample_gradient(&gradient, get_max_gradient_stops(ShapeVersion::Shape3));
+    assert_eq!(resampled.colors.len(), 8);
+    assert!(resampled.colors.contains(&gradient.colors[11]));
+  }
+
+  #[test]
+  fn test_emit_gradient_too_many_stops() {
+    let gradient = ast::Gradient {
+      spread: ast::GradientSpread::Pad,
+      color_space: ast::ColorSpace::SRgb,
+      colors: (0..16).map(|i| stop(i * 16, 0)).collect(),
+    };
+    let error = emit_gradient(&mut Vec::new(), &gradient, true).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+  }
+
+  #[test]
+  fn test_emit_morph_gradient_too_many_stops() {
+    let morph_stop = |i: u8| ast::MorphColorStop {
+      ratio: i * 16,
+      morph_ratio: i * 16,
+      color: stop(0, 0).color,
+      morph_color: stop(0, 0).color,
+    };
+    let gradient = |count: u8| ast::MorphGradient {
+      spread: ast::GradientSpread::Pad,
+      color_space: ast::ColorSpace::SRgb,
+      colors: (0..count).map(morph_stop).collect(),
+    };
+    assert!(emit_morph_gradient(&mut Vec::new(), &gradient(8)).is_ok());
+    let error = emit_morph_gradient(&mut Vec::new(), &gradient(9)).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+  }
+}
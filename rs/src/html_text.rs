@@ -0,0 +1,631 @@
+//! Validate and normalize the HTML initial text of `DefineDynamicText` tags.
+//!
+//! Flash Player supports a small subset of HTML: `p`, `font`, `b`, `i`, `u`, `a`, `li`, `br`, `img` and
+//! `textformat`. The normalized text only uses these tags (with lowercase names and double-quoted attributes), the
+//! `&amp;`, `&lt;`, `&gt;` and `&quot;` entities, and balanced tags.
+
+use std::fmt;
+
+use swf_types as ast;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HtmlDiagnostic {
+  /// Byte offset of the issue in the original text.
+  pub offset: usize,
+  pub kind: HtmlDiagnosticKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HtmlDiagnosticKind {
+  /// The tag is not supported by Flash Player: it is removed, its content is kept.
+  UnsupportedTag(String),
+  /// The attribute is not supported for this tag (or is repeated): it is removed.
+  UnsupportedAttribute { tag: String, name: String },
+  /// The attribute value is invalid: the attribute is removed.
+  InvalidAttributeValue { tag: String, name: String, value: String },
+  /// The entity is not recognized: it is kept as text.
+  UnknownEntity(String),
+  /// A `<` does not start a valid tag: it is kept as text.
+  MalformedTag,
+  /// The tag is not closed: it is closed at the end of its parent.
+  UnclosedTag(String),
+  /// The closing tag does not match an open tag: it is removed.
+  UnexpectedClosingTag(String),
+}
+
+impl fmt::Display for HtmlDiagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.kind {
+      HtmlDiagnosticKind::UnsupportedTag(ref tag) => write!(f, "unsupported tag `{}`", tag)?,
+      HtmlDiagnosticKind::UnsupportedAttribute { ref tag, ref name } => {
+        write!(f, "unsupported attribute `{}` for tag `{}`", name, tag)?
+      }
+      HtmlDiagnosticKind::InvalidAttributeValue {
+        ref tag,
+        ref name,
+        ref value,
+      } => write!(
+        f,
+        "invalid value for attribute `{}` of tag `{}`: {:?}",
+        name, tag, value
+      )?,
+      HtmlDiagnosticKind::UnknownEntity(ref entity) => write!(f, "unknown entity `&{};`", entity)?,
+      HtmlDiagnosticKind::MalformedTag => f.write_str("malformed tag")?,
+      HtmlDiagnosticKind::UnclosedTag(ref tag) => write!(f, "unclosed tag `{}`", tag)?,
+      HtmlDiagnosticKind::UnexpectedClosingTag(ref tag) => write!(f, "unexpected closing tag `{}`", tag)?,
+    }
+    write!(f, " at offset {}", self.offset)
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum AttributeType {
+  Text,
+  Integer,
+  /// Integer with an optional sign, used for relative font sizes.
+  FontSize,
+  Number,
+  Color,
+  Boolean,
+  /// Boolean encoded as `0` or `1`.
+  Flag,
+  TabStops,
+  Align,
+  ImageAlign,
+}
+
+struct TagSpec {
+  name: &'static str,
+  is_void: bool,
+  /// Canonical attribute names (matched case-insensitively) and their types.
+  attributes: &'static [(&'static str, AttributeType)],
+}
+
+const SUPPORTED_TAGS: &[TagSpec] = &[
+  TagSpec {
+    name: "a",
+    is_void: false,
+    attributes: &[("href", AttributeType::Text), ("target", AttributeType::Text)],
+  },
+  TagSpec {
+    name: "b",
+    is_void: false,
+    attributes: &[],
+  },
+  TagSpec {
+    name: "br",
+    is_void: true,
+    attributes: &[],
+  },
+  TagSpec {
+    name: "font",
+    is_void: false,
+    attributes: &[
+      ("color", AttributeType::Color),
+      ("face", AttributeType::Text),
+      ("kerning", AttributeType::Flag),
+      ("letterSpacing", AttributeType::Number),
+      ("size", AttributeType::FontSize),
+    ],
+  },
+  TagSpec {
+    name: "i",
+    is_void: false,
+    attributes: &[],
+  },
+  TagSpec {
+    name: "img",
+    is_void: true,
+    attributes: &[
+      ("align", AttributeType::ImageAlign),
+      ("checkPolicyFile", AttributeType::Boolean),
+      ("height", AttributeType::Integer),
+      ("hspace", AttributeType::Integer),
+      ("id", AttributeType::Text),
+      ("src", AttributeType::Text),
+      ("vspace", AttributeType::Integer),
+      ("width", AttributeType::Integer),
+    ],
+  },
+  TagSpec {
+    name: "li",
+    is_void: false,
+    attributes: &[],
+  },
+  TagSpec {
+    name: "p",
+    is_void: false,
+    attributes: &[("align", AttributeType::Align), ("class", AttributeType::Text)],
+  },
+  TagSpec {
+    name: "textformat",
+    is_void: false,
+    attributes: &[
+      ("blockindent", AttributeType::Integer),
+      ("indent", AttributeType::Integer),
+      ("leading", AttributeType::Integer),
+      ("leftmargin", AttributeType::Integer),
+      ("rightmargin", AttributeType::Integer),
+      ("tabstops", AttributeType::TabStops),
+    ],
+  },
+  TagSpec {
+    name: "u",
+    is_void: false,
+    attributes: &[],
+  },
+];
+
+/// Normalizes the initial text of an HTML `DefineDynamicText` tag, returning the diagnostics.
+///
+/// Tags without the `html` flag or initial text are left unchanged.
+pub fn normalize_dynamic_text_html(tag: &mut ast::tags::DefineDynamicText) -> Vec<HtmlDiagnostic> {
+  if !tag.html {
+    return Vec::new();
+  }
+  match tag.text {
+    Some(ref mut text) => {
+      let (normalized, diagnostics) = normalize_html_text(text);
+      *text = normalized;
+      diagnostics
+    }
+    None => Vec::new(),
+  }
+}
+
+/// Normalizes HTML text to the subset supported by Flash Player, returning the diagnostics.
+///
+/// Unsupported tags and attributes are removed, invalid attribute values are dropped, entities are decoded and
+/// re-encoded, comments are removed and unbalanced tags are fixed.
+pub fn normalize_html_text(text: &str) -> (String, Vec<HtmlDiagnostic>) {
+  let mut normalizer = HtmlNormalizer {
+    input: text,
+    output: String::with_capacity(text.len()),
+    diagnostics: Vec::new(),
+    open_tags: Vec::new(),
+  };
+  normalizer.run();
+  (normalizer.output, normalizer.diagnostics)
+}
+
+struct HtmlNormalizer<'a> {
+  input: &'a str,
+  output: String,
+  diagnostics: Vec<HtmlDiagnostic>,
+  /// Open tags, with the offset of their start tag.
+  open_tags: Vec<(&'static str, usize)>,
+}
+
+/// A parsed start or end tag.
+struct RawTag<'a> {
+  name: &'a str,
+  is_closing: bool,
+  attributes: Vec<(&'a str, usize, String)>,
+}
+
+impl<'a> HtmlNormalizer<'a> {
+  fn run(&mut self) {
+    let input = self.input;
+    let mut offset = 0;
+    while offset < input.len() {
+      let rest = &input[offset..];
+      if rest.starts_with("<!--") {
+        offset += match rest.find("-->") {
+          Some(end) => end + 3,
+          None => rest.len(),
+        };
+        continue;
+      }
+      if rest.starts_with('<') {
+        match parse_tag(input, offset) {
+          Some((tag, end)) => {
+            self.handle_tag(tag, offset);
+            offset = end;
+          }
+          None => {
+            self.report(offset, HtmlDiagnosticKind::MalformedTag);
+            self.output.push_str("&lt;");
+            offset += 1;
+          }
+        }
+        continue;
+      }
+      let end = rest.find('<').map(|end| offset + end).unwrap_or(input.len());
+      let decoded = self.decode_text(offset, end);
+      escape_into(&mut self.output, &decoded, false);
+      offset = end;
+    }
+    while let Some((name, start)) = self.open_tags.pop() {
+      self.report(start, HtmlDiagnosticKind::UnclosedTag(String::from(name)));
+      self.close_tag(name);
+    }
+  }
+
+  fn report(&mut self, offset: usize, kind: HtmlDiagnosticKind) {
+    self.diagnostics.push(HtmlDiagnostic { offset, kind });
+  }
+
+  fn handle_tag(&mut self, tag: RawTag<'a>, offset: usize) {
+    let spec = match SUPPORTED_TAGS
+      .iter()
+      .find(|spec| spec.name.eq_ignore_ascii_case(tag.name))
+    {
+      Some(spec) => spec,
+      None => {
+        self.report(
+          offset,
+          HtmlDiagnosticKind::UnsupportedTag(tag.name.to_ascii_lowercase()),
+        );
+        return;
+      }
+    };
+
+    if tag.is_closing {
+      if spec.is_void {
+        return;
+      }
+      match self.open_tags.iter().rposition(|(name, _)| *name == spec.name) {
+        Some(index) => {
+          while self.open_tags.len() > index + 1 {
+            let (name, start) = self.open_tags.pop().unwrap();
+            self.report(start, HtmlDiagnosticKind::UnclosedTag(String::from(name)));
+            self.close_tag(name);
+          }
+          self.open_tags.pop();
+          self.close_tag(spec.name);
+        }
+        None => self.report(
+          offset,
+          HtmlDiagnosticKind::UnexpectedClosingTag(String::from(spec.name)),
+        ),
+      }
+      return;
+    }
+
+    self.output.push('<');
+    self.output.push_str(spec.name);
+    let mut seen: Vec<&'static str> = Vec::new();
+    for (name, name_offset, value) in tag.attributes {
+      let attribute = spec
+        .attributes
+        .iter()
+        .find(|(attribute_name, _)| attribute_name.eq_ignore_ascii_case(name));
+      let (canonical_name, attribute_type) = match attribute {
+        Some(attribute) if !seen.contains(&attribute.0) => *attribute,
+        _ => {
+          self.report(
+            name_offset,
+            HtmlDiagnosticKind::UnsupportedAttribute {
+              tag: String::from(spec.name),
+              name: String::from(name),
+            },
+          );
+          continue;
+        }
+      };
+      seen.push(canonical_name);
+      match normalize_attribute_value(attribute_type, &value) {
+        Some(value) => {
+          self.output.push(' ');
+          self.output.push_str(canonical_name);
+          self.output.push_str("=\"");
+          escape_into(&mut self.output, &value, true);
+          self.output.push('"');
+        }
+        None => self.report(
+          name_offset,
+          HtmlDiagnosticKind::InvalidAttributeValue {
+            tag: String::from(spec.name),
+            name: String::from(canonical_name),
+            value,
+          },
+        ),
+      }
+    }
+    if spec.is_void {
+      self.output.push_str("/>");
+    } else {
+      self.output.push('>');
+      self.open_tags.push((spec.name, offset));
+    }
+  }
+
+  fn close_tag(&mut self, name: &str) {
+    self.output.push_str("</");
+    self.output.push_str(name);
+    self.output.push('>');
+  }
+
+  /// Decodes the entities of `input[start..end]`, reporting unknown entities.
+  fn decode_text(&mut self, start: usize, end: usize) -> String {
+    let mut diagnostics: Vec<HtmlDiagnostic> = Vec::new();
+    let decoded = decode_entities(self.input, start, end, &mut diagnostics);
+    self.diagnostics.extend(diagnostics);
+    decoded
+  }
+}
+
+/// Parses the tag starting at `offset` (a `<`), returning the tag and the offset following it.
+fn parse_tag(input: &str, offset: usize) -> Option<(RawTag<'_>, usize)> {
+  let bytes = input.as_bytes();
+  let mut i = offset + 1;
+  let is_closing = bytes.get(i) == Some(&b'/');
+  if is_closing {
+    i += 1;
+  }
+  let name_start = i;
+  while i < bytes.len() && bytes[i].is_ascii_alphanumeric() {
+    i += 1;
+  }
+  if i == name_start {
+    return None;
+  }
+  let name = &input[name_start..i];
+  let mut attributes: Vec<(&str, usize, String)> = Vec::new();
+  loop {
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+      i += 1;
+    }
+    match bytes.get(i) {
+      None => return None,
+      Some(b'>') => {
+        return Some((
+          RawTag {
+            name,
+            is_closing,
+            attributes,
+          },
+          i + 1,
+        ))
+      }
+      Some(b'/') if bytes.get(i + 1) == Some(&b'>') => {
+        return Some((
+          RawTag {
+            name,
+            is_closing,
+            attributes,
+          },
+          i + 2,
+        ))
+      }
+      Some(_) => {}
+    }
+    if is_closing {
+      return None;
+    }
+    let attribute_start = i;
+    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'=' | b'>' | b'/' | b'<') {
+      i += 1;
+    }
+    if i == attribute_start {
+      return None;
+    }
+    let attribute_name = &input[attribute_start..i];
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+      i += 1;
+    }
+    if bytes.get(i) != Some(&b'=') {
+      attributes.push((attribute_name, attribute_start, String::new()));
+      continue;
+    }
+    i += 1;
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+      i += 1;
+    }
+    let (value_start, value_end) = match bytes.get(i) {
+      Some(quote @ (b'"' | b'\'')) => {
+        let end = i + 1 + input[i + 1..].find(char::from(*quote))?;
+        let range = (i + 1, end);
+        i = end + 1;
+        range
+      }
+      Some(_) => {
+        let start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+          i += 1;
+        }
+        (start, i)
+      }
+      None => return None,
+    };
+    // Unknown entities in attribute values are kept as text without diagnostics.
+    let value = decode_entities(input, value_start, value_end, &mut Vec::new());
+    attributes.push((attribute_name, attribute_start, value));
+  }
+}
+
+fn decode_entities(input: &str, start: usize, end: usize, diagnostics: &mut Vec<HtmlDiagnostic>) -> String {
+  let text = &input[start..end];
+  let mut decoded = String::with_capacity(text.len());
+  let mut offset = 0;
+  while let Some(amp) = text[offset..].find('&') {
+    let amp = offset + amp;
+    decoded.push_str(&text[offset..amp]);
+    let entity_end = text[amp..].find(';').map(|end| amp + end).filter(|end| {
+      let name = &text[amp + 1..*end];
+      !name.is_empty() && name.len() <= 10 && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'#')
+    });
+    let entity_end = match entity_end {
+      Some(entity_end) => entity_end,
+      None => {
+        decoded.push('&');
+        offset = amp + 1;
+        continue;
+      }
+    };
+    let name = &text[amp + 1..entity_end];
+    match decode_entity(name) {
+      Some(c) => decoded.push(c),
+      None => {
+        diagnostics.push(HtmlDiagnostic {
+          offset: start + amp,
+          kind: HtmlDiagnosticKind::UnknownEntity(String::from(name)),
+        });
+        decoded.push_str(&text[amp..=entity_end]);
+      }
+    }
+    offset = entity_end + 1;
+  }
+  decoded.push_str(&text[offset..]);
+  decoded
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+  match name {
+    "amp" => Some('&'),
+    "lt" => Some('<'),
+    "gt" => Some('>'),
+    "quot" => Some('"'),
+    "apos" => Some('\''),
+    "nbsp" => Some('\u{a0}'),
+    _ => {
+      let code = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        u32::from_str_radix(hex, 16).ok()?
+      } else {
+        name.strip_prefix('#')?.parse::<u32>().ok()?
+      };
+      char::from_u32(code).filter(|c| *c != '\0')
+    }
+  }
+}
+
+fn escape_into(output: &mut String, text: &str, is_attribute: bool) {
+  for c in text.chars() {
+    match c {
+      '&' => output.push_str("&amp;"),
+      '<' => output.push_str("&lt;"),
+      '>' => output.push_str("&gt;"),
+      '"' if is_attribute => output.push_str("&quot;"),
+      c => output.push(c),
+    }
+  }
+}
+
+/// Returns the normalized attribute value, or `None` if it is invalid.
+fn normalize_attribute_value(attribute_type: AttributeType, value: &str) -> Option<String> {
+  let value = value.trim();
+  match attribute_type {
+    AttributeType::Text => Some(String::from(value)),
+    AttributeType::Integer => value.parse::<i32>().ok().map(|value| value.to_string()),
+    AttributeType::FontSize => {
+      let (sign, digits) = match value.strip_prefix('+') {
+        Some(digits) => ("+", digits),
+        None => match value.strip_prefix('-') {
+          Some(digits) => ("-", digits),
+          None => ("", value),
+        },
+      };
+      digits.parse::<u16>().ok().map(|size| format!("{}{}", sign, size))
+    }
+    AttributeType::Number => value
+      .parse::<f64>()
+      .ok()
+      .filter(|value| value.is_finite())
+      .map(|value| value.to_string()),
+    AttributeType::Color => {
+      let hex = value.strip_prefix('#')?;
+      if hex.len() == 6 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Some(format!("#{}", hex.to_ascii_uppercase()))
+      } else {
+        None
+      }
+    }
+    AttributeType::Boolean => match value.to_ascii_lowercase().as_str() {
+      "true" | "1" => Some(String::from("true")),
+      "false" | "0" => Some(String::from("false")),
+      _ => None,
+    },
+    AttributeType::Flag => match value.to_ascii_lowercase().as_str() {
+      "true" | "1" => Some(String::from("1")),
+      "false" | "0" => Some(String::from("0")),
+      _ => None,
+    },
+    AttributeType::TabStops => {
+      let stops: Option<Vec<String>> = value
+        .split(',')
+        .map(|stop| stop.trim().parse::<u32>().ok().map(|stop| stop.to_string()))
+        .collect();
+      stops.map(|stops| stops.join(","))
+    }
+    AttributeType::Align | AttributeType::ImageAlign => {
+      let value = value.to_ascii_lowercase();
+      let allowed: &[&str] = if attribute_type == AttributeType::Align {
+        &["left", "right", "center", "justify"]
+      } else {
+        &["left", "right"]
+      };
+      if allowed.contains(&value.as_str()) {
+        Some(value)
+      } else {
+        None
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn kinds(diagnostics: &[HtmlDiagnostic]) -> Vec<HtmlDiagnosticKind> {
+    diagnostics.iter().map(|diagnostic| diagnostic.kind.clone()).collect()
+  }
+
+  #[test]
+  fn test_normalize_valid_html() {
+    let text = r##"<p align="center"><font face="_sans" size="+2" color="#ff0000">a &amp; b</font><br/></p>"##;
+    let (normalized, diagnostics) = normalize_html_text(text);
+    assert_eq!(
+      normalized,
+      r##"<p align="center"><font face="_sans" size="+2" color="#FF0000">a &amp; b</font><br/></p>"##
+    );
+    assert_eq!(diagnostics, Vec::new());
+  }
+
+  #[test]
+  fn test_normalize_entities_and_attributes() {
+    let (normalized, diagnostics) =
+      normalize_html_text("<FONT Color=#00ff00 LETTERSPACING='1.50' kerning=true>&#65;&#x42;&copy; 1 < 2</FONT><BR>");
+    assert_eq!(
+      normalized,
+      r##"<font color="#00FF00" letterSpacing="1.5" kerning="1">AB&amp;copy; 1 &lt; 2</font><br/>"##
+    );
+    assert_eq!(
+      kinds(&diagnostics),
+      vec![
+        HtmlDiagnosticKind::UnknownEntity(String::from("copy")),
+        HtmlDiagnosticKind::MalformedTag,
+      ]
+    );
+    assert_eq!(diagnostics[0].offset, 65);
+  }
+
+  #[test]
+  fn test_normalize_invalid_markup() {
+    let (normalized, diagnostics) =
+      normalize_html_text(r#"<div><p align="middle" style="x"><b>bold<i>both</b></u><textformat indent="2">x"#);
+    assert_eq!(
+      normalized,
+      r#"<p><b>bold<i>both</i></b><textformat indent="2">x</textformat></p>"#
+    );
+    assert_eq!(
+      kinds(&diagnostics),
+      vec![
+        HtmlDiagnosticKind::UnsupportedTag(String::from("div")),
+        HtmlDiagnosticKind::InvalidAttributeValue {
+          tag: String::from("p"),
+          name: String::from("align"),
+          value: String::from("middle"),
+        },
+        HtmlDiagnosticKind::UnsupportedAttribute {
+          tag: String::from("p"),
+          name: String::from("style"),
+        },
+        HtmlDiagnosticKind::UnclosedTag(String::from("i")),
+        HtmlDiagnosticKind::UnexpectedClosingTag(String::from("u")),
+        HtmlDiagnosticKind::UnclosedTag(String::from("textformat")),
+        HtmlDiagnosticKind::UnclosedTag(String::from("p")),
+      ]
+    );
+  }
+}
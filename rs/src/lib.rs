@@ -1,17 +1,40 @@
+pub mod abc;
+pub mod avm1;
 pub mod basic_data_types;
 pub mod bit_count;
 pub mod button;
+pub mod constant_pool;
+pub mod deduplicate;
+pub mod dictionary;
 pub mod display;
 mod error;
+#[cfg(feature = "font")]
+pub mod font;
+pub mod font_align_zones;
+pub mod font_subset;
 pub mod gradient;
+pub mod html_text;
 pub mod io_bits;
+pub mod merge;
 pub mod morph_shape;
 pub mod movie;
+pub mod mp3;
+#[cfg(any(feature = "font", feature = "svg"))]
+mod path;
 pub mod primitives;
 pub mod shape;
 pub mod sound;
+pub mod sound_encoder;
+#[cfg(feature = "svg")]
+pub mod svg;
+pub mod symbol_class;
 pub mod tags;
 pub mod text;
+pub mod text_layout;
+pub mod timeline;
+pub mod unused_characters;
+#[cfg(feature = "wav")]
+pub mod wav;
 
 use crate::movie::emit_swf as write_swf;
 use crate::tags::emit_tag as write_tag;
@@ -53,7 +76,7 @@ mod tests {
     let path: &Path = Path::new(path);
     let _name = path
       .components()
-      .last()
+      .next_back()
       .unwrap()
       .as_os_str()
       .to_str()
@@ -89,7 +112,7 @@ mod tests {
     let path: &Path = Path::new(path);
     let name = path
       .components()
-      .last()
+      .next_back()
       .unwrap()
       .as_os_str()
       .to_str()
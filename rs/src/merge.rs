@@ -0,0 +1,328 @@
+//! Merge the libraries of several movies into a single movie.
+
+use crate::dictionary::{get_attached_character, get_defined_character, remap_character_ids, MAIN_TIMELINE_ID};
+use crate::SwfEmitError;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use swf_types as ast;
+
+/// Merges several movies into one, remapping their character ids so they do not collide.
+///
+/// The first movie provides the header, the timeline and the movie-level tags (`FileAttributes`,
+/// `SetBackgroundColor`, `Metadata`, ...). The definitions and library tags of the other movies (`Define*`,
+/// `DefineFontInfo`, `DefineScalingGrid`, `DoInitAction`, `DoAbc`, `ExportAssets`, `SymbolClass` and `ImportAssets`)
+/// are added to the first frame; their timelines are dropped, as well as the `SymbolClass` bindings of their main
+/// timelines.
+///
+/// The characters are renumbered from 1, in definition order. An exported name used by a previous movie is renamed
+/// with a numeric suffix: `logo` becomes `logo_2`.
+///
+/// Returns an `InvalidInput` error if the movies mix AVM1 and AVM2 code, use different JPEG tables, bind the same class
+/// name with `SymbolClass`, define a character twice, reference an undefined character or define more than 65535
+/// characters.
+pub fn merge_movies(movies: &[ast::Movie]) -> io::Result<ast::Movie> {
+  let first = match movies.first() {
+    Some(first) => first,
+    None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "no movie to merge")),
+  };
+  let use_as3 = get_use_as3(first);
+  let mut jpeg_tables: Option<&ast::tags::DefineJpegTables> = None;
+  let mut export_names: HashSet<String> = HashSet::new();
+  let mut class_names: HashSet<String> = HashSet::new();
+  let mut next_id: usize = 1;
+  let mut swf_version = first.header.swf_version;
+  let mut library: Vec<ast::Tag> = Vec::new();
+  let mut timeline: Vec<ast::Tag> = Vec::new();
+
+  for (index, movie) in movies.iter().enumerate() {
+    if get_use_as3(movie) != use_as3 {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "cannot merge AVM1 and AVM2 movies",
+      ));
+    }
+    swf_version = swf_version.max(movie.header.swf_version);
+
+    let mut tags: Vec<ast::Tag> = Vec::with_capacity(movie.tags.len());
+    for tag in movie.tags.iter() {
+      if let ast::Tag::DefineJpegTables(ref tables) = tag {
+        match jpeg_tables {
+          Some(previous) if previous.data != tables.data => {
+            return Err(io::Error::new(
+              io::ErrorKind::InvalidInput,
+              "cannot merge movies with different JPEG tables",
+            ));
+          }
+          Some(_) => continue,
+          None => jpeg_tables = Some(tables),
+        }
+      }
+      if index == 0 || is_library_tag(tag) {
+        tags.push(tag.clone());
+      }
+    }
+    remap_movie_ids(&mut tags, &mut next_id)?;
+
+    let mut movie_class_names: Vec<String> = Vec::new();
+    for tag in tags.iter_mut() {
+      match tag {
+        ast::Tag::ExportAssets(ref mut tag) => {
+          for asset in tag.assets.iter_mut() {
+            asset.name = get_unique_name(&mut export_names, &asset.name);
+          }
+        }
+        ast::Tag::SymbolClass(ref mut tag) => {
+          if index > 0 {
+            tag.symbols.retain(|symbol| symbol.id != MAIN_TIMELINE_ID);
+          }
+          for symbol in tag.symbols.iter() {
+            if class_names.contains(&symbol.name) {
+              return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("class bound by several movies: {}", symbol.name),
+              ));
+            }
+            movie_class_names.push(symbol.name.clone());
+          }
+        }
+        _ => {}
+      }
+    }
+    class_names.extend(movie_class_names);
+
+    if index == 0 {
+      timeline = tags;
+    } else {
+      library.extend(tags);
+    }
+  }
+
+  // Insert the libraries before the end of the first frame
+  let first_frame_end = timeline
+    .iter()
+    .position(|tag| matches!(tag, ast::Tag::ShowFrame))
+    .unwrap_or(timeline.len());
+  timeline.splice(first_frame_end..first_frame_end, library);
+
+  Ok(ast::Movie {
+    header: ast::Header {
+      swf_version,
+      ..first.header
+    },
+    tags: timeline,
+  })
+}
+
+/// Merges several movies with `merge_movies`, and emits the result.
+pub fn emit_merged_swf(
+  movies: &[ast::Movie],
+  compression_method: ast::CompressionMethod,
+) -> Result<Vec<u8>, SwfEmitError> {
+  let movie = merge_movies(movies).map_err(SwfEmitError::Io)?;
+  crate::emit_swf(&movie, compression_method)
+}
+
+fn get_use_as3(movie: &ast::Movie) -> bool {
+  movie.tags.iter().any(|tag| match tag {
+    ast::Tag::FileAttributes(ref tag) => tag.use_as3,
+    ast::Tag::DoAbc(_) => true,
+    _ => false,
+  })
+}
+
+/// Returns whether the tag is kept when merging a movie into the first one.
+fn is_library_tag(tag: &ast::Tag) -> bool {
+  match tag {
+    ast::Tag::DefineJpegTables(_)
+    | ast::Tag::DoAbc(_)
+    | ast::Tag::ExportAssets(_)
+    | ast::Tag::ImportAssets(_)
+    | ast::Tag::SymbolClass(_) => true,
+    tag => get_defined_character(tag).is_some() || get_attached_character(tag).is_some(),
+  }
+}
+
+/// Renumbers the characters defined by the tags from `next_id`, and updates `next_id`.
+fn remap_movie_ids(tags: &mut [ast::Tag], next_id: &mut usize) -> io::Result<()> {
+  let mut ids: HashMap<u16, u16> = HashMap::new();
+  for tag in tags.iter() {
+    let defined: Vec<u16> = match tag {
+      ast::Tag::ImportAssets(ref tag) => tag.assets.iter().map(|asset| asset.id).collect(),
+      tag => get_defined_character(tag).map(|(id, _)| id).into_iter().collect(),
+    };
+    for id in defined {
+      let new_id = u16::try_from(*next_id)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many characters to merge"))?;
+      if ids.insert(id, new_id).is_some() {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidInput,
+          format!("duplicate character id: {}", id),
+        ));
+      }
+      *next_id += 1;
+    }
+  }
+
+  let mut undefined: Option<u16> = None;
+  remap_character_ids(tags, &mut |id| match ids.get(&id) {
+    Some(new_id) => *new_id,
+    None => {
+      undefined = Some(id);
+      id
+    }
+  });
+  match undefined {
+    Some(id) => Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("reference to undefined character: {}", id),
+    )),
+    None => Ok(()),
+  }
+}
+
+/// Returns `name`, or `name` with the lowest numeric suffix not in `names`, and adds it to `names`.
+fn get_unique_name(names: &mut HashSet<String>, name: &str) -> String {
+  let mut unique_name = String::from(name);
+  let mut suffix: usize = 2;
+  while names.contains(&unique_name) {
+    unique_name = format!("{}_{}", name, suffix);
+    suffix += 1;
+  }
+  names.insert(unique_name.clone());
+  unique_name
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn movie(tags: Vec<ast::Tag>) -> ast::Movie {
+    ast::Movie {
+      header: ast::Header {
+        swf_version: 8,
+        frame_size: ast::Rect {
+          x_min: 0,
+          x_max: 2000,
+          y_min: 0,
+          y_max: 2000,
+        },
+        frame_rate: swf_fixed::Ufixed8P8::from_epsilons(24 * 256),
+        frame_count: 1,
+      },
+      tags,
+    }
+  }
+
+  fn define_binary_data(id: u16, data: &[u8]) -> ast::Tag {
+    ast::Tag::DefineBinaryData(ast::tags::DefineBinaryData {
+      id,
+      data: data.to_vec(),
+    })
+  }
+
+  fn export(id: u16, name: &str) -> ast::Tag {
+    ast::Tag::ExportAssets(ast::tags::ExportAssets {
+      assets: vec![ast::NamedId {
+        id,
+        name: String::from(name),
+      }],
+    })
+  }
+
+  fn place(character_id: u16) -> ast::Tag {
+    ast::Tag::PlaceObject(ast::tags::PlaceObject {
+      is_update: false,
+      depth: 1,
+      character_id: Some(character_id),
+      class_name: None,
+      matrix: None,
+      color_transform: None,
+      ratio: None,
+      name: None,
+      clip_depth: None,
+      filters: None,
+      blend_mode: None,
+      bitmap_cache: None,
+      visible: None,
+      background_color: None,
+      clip_actions: None,
+    })
+  }
+
+  fn scaling_grid(character_id: u16) -> ast::Tag {
+    ast::Tag::DefineScalingGrid(ast::tags::DefineScalingGrid {
+      character_id,
+      splitter: ast::Rect {
+        x_min: 20,
+        x_max: 100,
+        y_min: 20,
+        y_max: 100,
+      },
+    })
+  }
+
+  #[test]
+  fn test_merge_movies() {
+    let first = movie(vec![
+      define_binary_data(1, &[1]),
+      export(1, "data"),
+      place(1),
+      ast::Tag::ShowFrame,
+    ]);
+    let second = movie(vec![
+      define_binary_data(1, &[2]),
+      scaling_grid(1),
+      export(1, "data"),
+      place(1),
+      ast::Tag::ShowFrame,
+    ]);
+    let movies = [first, second];
+    let merged = merge_movies(&movies).unwrap();
+    assert_eq!(
+      merged.tags,
+      vec![
+        define_binary_data(1, &[1]),
+        export(1, "data"),
+        place(1),
+        define_binary_data(2, &[2]),
+        scaling_grid(2),
+        export(2, "data_2"),
+        ast::Tag::ShowFrame,
+      ]
+    );
+
+    let bytes = emit_merged_swf(&movies, ast::CompressionMethod::None).unwrap();
+    assert_eq!(swf_parser::parse_swf(&bytes).unwrap(), merged);
+  }
+
+  #[test]
+  fn test_merge_movies_errors() {
+    assert!(merge_movies(&[]).is_err());
+    let dangling = movie(vec![place(3), ast::Tag::ShowFrame]);
+    assert!(merge_movies(&[dangling]).is_err());
+    let as3 = movie(vec![ast::Tag::DoAbc(ast::tags::DoAbc {
+      header: None,
+      data: Vec::new(),
+    })]);
+    assert!(merge_movies(&[movie(Vec::new()), as3.clone()]).is_err());
+
+    let bind_class = |name: &str| {
+      movie(vec![
+        ast::Tag::DoAbc(ast::tags::DoAbc {
+          header: None,
+          data: Vec::new(),
+        }),
+        define_binary_data(1, &[1]),
+        ast::Tag::SymbolClass(ast::tags::SymbolClass {
+          symbols: vec![ast::NamedId {
+            id: 1,
+            name: String::from(name),
+          }],
+        }),
+      ])
+    };
+    assert!(merge_movies(&[bind_class("Logo"), bind_class("Icon")]).is_ok());
+    let error = merge_movies(&[bind_class("Logo"), as3, bind_class("Logo")]).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+  }
+}
@@ -1,4 +1,7 @@
+use std::cmp::max;
 use std::convert::{TryFrom, TryInto};
+use std::error::Error;
+use std::fmt;
 use std::io;
 
 use swf_types as ast;
@@ -8,7 +11,7 @@ use crate::bit_count::{get_i32_min_bit_count, get_u32_bit_count};
 use crate::gradient::emit_morph_gradient;
 use crate::io_bits::{BitsWriter, WriteBits};
 use crate::primitives::{emit_le_i16, emit_le_u16, emit_le_u32, emit_u8};
-use crate::shape::{cap_style_to_code, emit_edge_bits, emit_list_length, join_style_to_code};
+use crate::shape::{cap_style_to_code, emit_edge_bits, emit_list_length, get_shape_bounds, join_style_to_code};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MorphShapeVersion {
@@ -325,3 +328,670 @@ pub(crate) fn emit_morph_line_style2<W: io::Write + ?Sized>(
     style => emit_morph_fill_style(writer, style),
   }
 }
+
+pub(crate) fn get_min_morph_shape_version(value: &ast::MorphShape) -> MorphShapeVersion {
+  value.records.iter().fold(
+    get_morph_shape_styles_min_version(&value.initial_styles),
+    |acc, record| match record {
+      ast::MorphShapeRecord::StyleChange(ref record) => match &record.new_styles {
+        Some(ref styles) => max(acc, get_morph_shape_styles_min_version(styles)),
+        _ => acc,
+      },
+      _ => acc,
+    },
+  )
+}
+
+pub(crate) fn get_morph_shape_styles_min_version(value: &ast::MorphShapeStyles) -> MorphShapeVersion {
+  let fill_version = value
+    .fill
+    .iter()
+    .map(get_morph_fill_style_min_version)
+    .fold(MorphShapeVersion::MorphShape1, max);
+  let line_version = value
+    .line
+    .iter()
+    .map(get_morph_line_style_min_version)
+    .fold(MorphShapeVersion::MorphShape1, max);
+  max(fill_version, line_version)
+}
+
+pub(crate) fn get_morph_fill_style_min_version(value: &ast::MorphFillStyle) -> MorphShapeVersion {
+  match value {
+    ast::MorphFillStyle::FocalGradient(_) => MorphShapeVersion::MorphShape2,
+    _ => MorphShapeVersion::MorphShape1,
+  }
+}
+
+pub(crate) fn get_morph_line_style_min_version(value: &ast::MorphLineStyle) -> MorphShapeVersion {
+  let is_solid_fill = matches!(&value.fill, ast::MorphFillStyle::Solid(_));
+  let is_line_style2 = value.start_cap != ast::CapStyle::Round
+    || value.end_cap != ast::CapStyle::Round
+    || value.join != ast::JoinStyle::Round
+    || value.no_h_scale
+    || value.no_v_scale
+    || value.no_close
+    || value.pixel_hinting
+    || !is_solid_fill;
+
+  if is_line_style2 {
+    MorphShapeVersion::MorphShape2
+  } else {
+    MorphShapeVersion::MorphShape1
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MorphShapeBuildError {
+  /// The start and end shapes do not have the same style change records (style selections or new styles).
+  StyleChangeMismatch,
+  /// The start and end style lists do not have the same length.
+  StyleCountMismatch,
+  /// The fill style at the provided index (in its style list) cannot be morphed.
+  FillStyleMismatch(usize),
+  /// The line style at the provided index (in its style list) cannot be morphed.
+  LineStyleMismatch(usize),
+}
+
+impl fmt::Display for MorphShapeBuildError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MorphShapeBuildError::StyleChangeMismatch => f.write_str("start and end shapes have different style changes"),
+      MorphShapeBuildError::StyleCountMismatch => f.write_str("start and end shapes have different style counts"),
+      MorphShapeBuildError::FillStyleMismatch(index) => {
+        write!(f, "start and end fill styles at index {} are incompatible", index)
+      }
+      MorphShapeBuildError::LineStyleMismatch(index) => {
+        write!(f, "start and end line styles at index {} are incompatible", index)
+      }
+    }
+  }
+}
+
+impl Error for MorphShapeBuildError {}
+
+/// Builds a `DefineMorphShape` tag morphing from the `start` shape to the `end` shape.
+///
+/// Both shapes must have the same style change records (same style selections and new styles at the same
+/// positions) and compatible styles at the same indices. The edges between two style changes are normalized to
+/// the same count by splitting the longest edges, and straight edges paired with curves are converted to curves.
+///
+/// Edge bounds are left empty: they are computed when emitting the tag if `DefineMorphShape2` is required.
+pub fn build_define_morph_shape(
+  id: u16,
+  start: &ast::Shape,
+  end: &ast::Shape,
+) -> Result<ast::tags::DefineMorphShape, MorphShapeBuildError> {
+  let initial_styles = morph_shape_styles(&start.initial_styles, &end.initial_styles)?;
+  let start_runs = split_edge_runs(&start.records);
+  let end_runs = split_edge_runs(&end.records);
+  if start_runs.len() != end_runs.len() {
+    return Err(MorphShapeBuildError::StyleChangeMismatch);
+  }
+
+  let mut records: Vec<ast::MorphShapeRecord> = Vec::new();
+  let mut start_position = ast::Vector2D { x: 0, y: 0 };
+  let mut end_position = ast::Vector2D { x: 0, y: 0 };
+  for (start_run, end_run) in start_runs.into_iter().zip(end_runs) {
+    match (start_run.style_change, end_run.style_change) {
+      (None, None) => {}
+      (Some(start_change), Some(end_change)) => {
+        if start_change.left_fill != end_change.left_fill
+          || start_change.right_fill != end_change.right_fill
+          || start_change.line_style != end_change.line_style
+        {
+          return Err(MorphShapeBuildError::StyleChangeMismatch);
+        }
+        let new_styles = match (&start_change.new_styles, &end_change.new_styles) {
+          (None, None) => None,
+          (Some(ref start_styles), Some(ref end_styles)) => Some(morph_shape_styles(start_styles, end_styles)?),
+          _ => return Err(MorphShapeBuildError::StyleChangeMismatch),
+        };
+        let (move_to, morph_move_to) = if start_change.move_to.is_some() || end_change.move_to.is_some() {
+          start_position = start_change.move_to.unwrap_or(start_position);
+          end_position = end_change.move_to.unwrap_or(end_position);
+          (Some(start_position), Some(end_position))
+        } else {
+          (None, None)
+        };
+        records.push(ast::MorphShapeRecord::StyleChange(
+          ast::shape_records::MorphStyleChange {
+            move_to,
+            morph_move_to,
+            left_fill: start_change.left_fill,
+            right_fill: start_change.right_fill,
+            line_style: start_change.line_style,
+            new_styles,
+          },
+        ));
+      }
+      _ => return Err(MorphShapeBuildError::StyleChangeMismatch),
+    }
+
+    let (start_edges, end_edges) = normalize_edge_runs(start_run.edges, end_run.edges);
+    for (start_edge, end_edge) in start_edges.into_iter().zip(end_edges) {
+      let (control_delta, morph_control_delta) = match (start_edge.control_delta, end_edge.control_delta) {
+        (None, None) => (None, None),
+        (start_control, end_control) => (
+          Some(start_control.unwrap_or_else(|| half_delta(start_edge.delta))),
+          Some(end_control.unwrap_or_else(|| half_delta(end_edge.delta))),
+        ),
+      };
+      start_position.x += start_edge.delta.x;
+      start_position.y += start_edge.delta.y;
+      end_position.x += end_edge.delta.x;
+      end_position.y += end_edge.delta.y;
+      records.push(ast::MorphShapeRecord::Edge(ast::shape_records::MorphEdge {
+        delta: start_edge.delta,
+        morph_delta: end_edge.delta,
+        control_delta,
+        morph_control_delta,
+      }));
+    }
+  }
+
+  let shape = ast::MorphShape {
+    initial_styles,
+    records,
+  };
+  let (has_non_scaling_strokes, has_scaling_strokes) = get_morph_stroke_scaling(&shape);
+  Ok(ast::tags::DefineMorphShape {
+    id,
+    bounds: get_shape_bounds(&get_morph_shape_start(&shape)),
+    morph_bounds: get_shape_bounds(&get_morph_shape_end(&shape)),
+    edge_bounds: None,
+    morph_edge_bounds: None,
+    has_scaling_strokes,
+    has_non_scaling_strokes,
+    shape,
+  })
+}
+
+/// Edges following a style change record (or the start of the shape).
+struct EdgeRun<'a> {
+  style_change: Option<&'a ast::shape_records::StyleChange>,
+  edges: Vec<ast::shape_records::Edge>,
+}
+
+fn split_edge_runs(records: &[ast::ShapeRecord]) -> Vec<EdgeRun<'_>> {
+  let mut runs = vec![EdgeRun {
+    style_change: None,
+    edges: Vec::new(),
+  }];
+  for record in records {
+    match record {
+      ast::ShapeRecord::StyleChange(ref style_change) => runs.push(EdgeRun {
+        style_change: Some(style_change),
+        edges: Vec::new(),
+      }),
+      ast::ShapeRecord::Edge(ref edge) => runs.last_mut().unwrap().edges.push(*edge),
+    }
+  }
+  runs
+}
+
+/// Splits edges until both runs have the same number of edges.
+fn normalize_edge_runs(
+  mut start: Vec<ast::shape_records::Edge>,
+  mut end: Vec<ast::shape_records::Edge>,
+) -> (Vec<ast::shape_records::Edge>, Vec<ast::shape_records::Edge>) {
+  let count = std::cmp::max(start.len(), end.len());
+  split_edges(&mut start, count);
+  split_edges(&mut end, count);
+  (start, end)
+}
+
+fn split_edges(edges: &mut Vec<ast::shape_records::Edge>, count: usize) {
+  let zero = ast::Vector2D { x: 0, y: 0 };
+  if edges.is_empty() {
+    // A point growing into edges.
+    edges.resize(
+      count,
+      ast::shape_records::Edge {
+        delta: zero,
+        control_delta: None,
+      },
+    );
+    return;
+  }
+  while edges.len() < count {
+    let (index, _) = edges
+      .iter()
+      .enumerate()
+      .max_by_key(|(_, edge)| get_edge_squared_length(edge))
+      .unwrap();
+    let (first, second) = split_edge(&edges[index]);
+    edges[index] = first;
+    edges.insert(index + 1, second);
+  }
+}
+
+/// Returns the squared length of the control polygon of the edge.
+fn get_edge_squared_length(edge: &ast::shape_records::Edge) -> i64 {
+  let squared_length = |x: i32, y: i32| i64::from(x) * i64::from(x) + i64::from(y) * i64::from(y);
+  match edge.control_delta {
+    Some(control) => {
+      squared_length(control.x, control.y) + squared_length(edge.delta.x - control.x, edge.delta.y - control.y)
+    }
+    None => squared_length(edge.delta.x, edge.delta.y),
+  }
+}
+
+/// Splits an edge at its middle (`t = 0.5`).
+fn split_edge(edge: &ast::shape_records::Edge) -> (ast::shape_records::Edge, ast::shape_records::Edge) {
+  match edge.control_delta {
+    None => {
+      let first = half_delta(edge.delta);
+      let second = ast::Vector2D {
+        x: edge.delta.x - first.x,
+        y: edge.delta.y - first.y,
+      };
+      (
+        ast::shape_records::Edge {
+          delta: first,
+          control_delta: None,
+        },
+        ast::shape_records::Edge {
+          delta: second,
+          control_delta: None,
+        },
+      )
+    }
+    Some(control) => {
+      // De Casteljau subdivision, with coordinates relative to the edge start.
+      let control0 = half_delta(control);
+      let control1 = ast::Vector2D {
+        x: control.x + (edge.delta.x - control.x) / 2,
+        y: control.y + (edge.delta.y - control.y) / 2,
+      };
+      let middle = ast::Vector2D {
+        x: control0.x + (control1.x - control0.x) / 2,
+        y: control0.y + (control1.y - control0.y) / 2,
+      };
+      (
+        ast::shape_records::Edge {
+          delta: middle,
+          control_delta: Some(control0),
+        },
+        ast::shape_records::Edge {
+          delta: ast::Vector2D {
+            x: edge.delta.x - middle.x,
+            y: edge.delta.y - middle.y,
+          },
+          control_delta: Some(ast::Vector2D {
+            x: control1.x - middle.x,
+            y: control1.y - middle.y,
+          }),
+        },
+      )
+    }
+  }
+}
+
+fn half_delta(delta: ast::Vector2D) -> ast::Vector2D {
+  ast::Vector2D {
+    x: delta.x / 2,
+    y: delta.y / 2,
+  }
+}
+
+fn morph_shape_styles(
+  start: &ast::ShapeStyles,
+  end: &ast::ShapeStyles,
+) -> Result<ast::MorphShapeStyles, MorphShapeBuildError> {
+  if start.fill.len() != end.fill.len() || start.line.len() != end.line.len() {
+    return Err(MorphShapeBuildError::StyleCountMismatch);
+  }
+  let fill = start
+    .fill
+    .iter()
+    .zip(end.fill.iter())
+    .enumerate()
+    .map(|(index, (start, end))| morph_fill_style(start, end).ok_or(MorphShapeBuildError::FillStyleMismatch(index)))
+    .collect::<Result<Vec<_>, _>>()?;
+  let line = start
+    .line
+    .iter()
+    .zip(end.line.iter())
+    .enumerate()
+    .map(|(index, (start, end))| morph_line_style(start, end).ok_or(MorphShapeBuildError::LineStyleMismatch(index)))
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(ast::MorphShapeStyles { fill, line })
+}
+
+fn morph_fill_style(start: &ast::FillStyle, end: &ast::FillStyle) -> Option<ast::MorphFillStyle> {
+  let style = match (start, end) {
+    (ast::FillStyle::Solid(ref start), ast::FillStyle::Solid(ref end)) => {
+      ast::MorphFillStyle::Solid(ast::fill_styles::MorphSolid {
+        color: start.color,
+        morph_color: end.color,
+      })
+    }
+    (ast::FillStyle::LinearGradient(ref start), ast::FillStyle::LinearGradient(ref end)) => {
+      ast::MorphFillStyle::LinearGradient(ast::fill_styles::MorphLinearGradient {
+        matrix: start.matrix,
+        morph_matrix: end.matrix,
+        gradient: morph_gradient(&start.gradient, &end.gradient)?,
+      })
+    }
+    (ast::FillStyle::RadialGradient(ref start), ast::FillStyle::RadialGradient(ref end)) => {
+      ast::MorphFillStyle::RadialGradient(ast::fill_styles::MorphRadialGradient {
+        matrix: start.matrix,
+        morph_matrix: end.matrix,
+        gradient: morph_gradient(&start.gradient, &end.gradient)?,
+      })
+    }
+    (ast::FillStyle::FocalGradient(ref start), ast::FillStyle::FocalGradient(ref end)) => {
+      ast::MorphFillStyle::FocalGradient(ast::fill_styles::MorphFocalGradient {
+        matrix: start.matrix,
+        morph_matrix: end.matrix,
+        gradient: morph_gradient(&start.gradient, &end.gradient)?,
+        focal_point: start.focal_point,
+        morph_focal_point: end.focal_point,
+      })
+    }
+    (ast::FillStyle::Bitmap(ref start), ast::FillStyle::Bitmap(ref end))
+      if start.bitmap_id == end.bitmap_id && start.repeating == end.repeating && start.smoothed == end.smoothed =>
+    {
+      ast::MorphFillStyle::Bitmap(ast::fill_styles::MorphBitmap {
+        bitmap_id: start.bitmap_id,
+        matrix: start.matrix,
+        morph_matrix: end.matrix,
+        repeating: start.repeating,
+        smoothed: start.smoothed,
+      })
+    }
+    _ => return None,
+  };
+  Some(style)
+}
+
+fn morph_gradient(start: &ast::Gradient, end: &ast::Gradient) -> Option<ast::MorphGradient> {
+  if start.spread != end.spread || start.color_space != end.color_space || start.colors.len() != end.colors.len() {
+    return None;
+  }
+  Some(ast::MorphGradient {
+    spread: start.spread,
+    color_space: start.color_space,
+    colors: start
+      .colors
+      .iter()
+      .zip(end.colors.iter())
+      .map(|(start, end)| ast::MorphColorStop {
+        ratio: start.ratio,
+        morph_ratio: end.ratio,
+        color: start.color,
+        morph_color: end.color,
+      })
+      .collect(),
+  })
+}
+
+fn morph_line_style(start: &ast::LineStyle, end: &ast::LineStyle) -> Option<ast::MorphLineStyle> {
+  let is_compatible = start.start_cap == end.start_cap
+    && start.end_cap == end.end_cap
+    && start.join == end.join
+    && start.no_h_scale == end.no_h_scale
+    && start.no_v_scale == end.no_v_scale
+    && start.no_close == end.no_close
+    && start.pixel_hinting == end.pixel_hinting;
+  if !is_compatible {
+    return None;
+  }
+  Some(ast::MorphLineStyle {
+    width: start.width,
+    morph_width: end.width,
+    start_cap: start.start_cap,
+    end_cap: start.end_cap,
+    join: start.join,
+    no_h_scale: start.no_h_scale,
+    no_v_scale: start.no_v_scale,
+    no_close: start.no_close,
+    pixel_hinting: start.pixel_hinting,
+    fill: morph_fill_style(&start.fill, &end.fill)?,
+  })
+}
+
+fn get_morph_stroke_scaling(shape: &ast::MorphShape) -> (bool, bool) {
+  let mut has_non_scaling_strokes = false;
+  let mut has_scaling_strokes = false;
+  let new_styles = shape.records.iter().filter_map(|record| match record {
+    ast::MorphShapeRecord::StyleChange(ref record) => record.new_styles.as_ref(),
+    _ => None,
+  });
+  for styles in std::iter::once(&shape.initial_styles).chain(new_styles) {
+    for line in &styles.line {
+      if line.no_h_scale || line.no_v_scale {
+        has_non_scaling_strokes = true;
+      } else {
+        has_scaling_strokes = true;
+      }
+    }
+  }
+  (has_non_scaling_strokes, has_scaling_strokes)
+}
+
+/// Returns the start state of a morph shape, keeping only the style data needed for its bounds (line widths).
+pub(crate) fn get_morph_shape_start(shape: &ast::MorphShape) -> ast::Shape {
+  get_morph_shape_state(shape, false)
+}
+
+/// Returns the end state of a morph shape, keeping only the style data needed for its bounds (line widths).
+pub(crate) fn get_morph_shape_end(shape: &ast::MorphShape) -> ast::Shape {
+  get_morph_shape_state(shape, true)
+}
+
+fn get_morph_shape_state(shape: &ast::MorphShape, is_end: bool) -> ast::Shape {
+  let styles = |styles: &ast::MorphShapeStyles| ast::ShapeStyles {
+    fill: Vec::new(),
+    line: styles
+      .line
+      .iter()
+      .map(|line| ast::LineStyle {
+        width: if is_end { line.morph_width } else { line.width },
+        start_cap: line.start_cap,
+        end_cap: line.end_cap,
+        join: line.join,
+        no_h_scale: line.no_h_scale,
+        no_v_scale: line.no_v_scale,
+        no_close: line.no_close,
+        pixel_hinting: line.pixel_hinting,
+        fill: ast::FillStyle::Solid(ast::fill_styles::Solid {
+          color: ast::StraightSRgba8 { r: 0, g: 0, b: 0, a: 0 },
+        }),
+      })
+      .collect(),
+  };
+  let records = shape
+    .records
+    .iter()
+    .map(|record| match record {
+      ast::MorphShapeRecord::Edge(ref edge) => ast::ShapeRecord::Edge(if is_end {
+        ast::shape_records::Edge {
+          delta: edge.morph_delta,
+          control_delta: edge.morph_control_delta,
+        }
+      } else {
+        ast::shape_records::Edge {
+          delta: edge.delta,
+          control_delta: edge.control_delta,
+        }
+      }),
+      ast::MorphShapeRecord::StyleChange(ref style_change) => {
+        ast::ShapeRecord::StyleChange(ast::shape_records::StyleChange {
+          move_to: if is_end {
+            style_change.morph_move_to
+          } else {
+            style_change.move_to
+          },
+          left_fill: style_change.left_fill,
+          right_fill: style_change.right_fill,
+          line_style: style_change.line_style,
+          new_styles: style_change.new_styles.as_ref().map(styles),
+        })
+      }
+    })
+    .collect();
+  ast::Shape {
+    initial_styles: styles(&shape.initial_styles),
+    records,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn solid(r: u8) -> ast::FillStyle {
+    ast::FillStyle::Solid(ast::fill_styles::Solid {
+      color: ast::StraightSRgba8 { r, g: 0, b: 0, a: 255 },
+    })
+  }
+
+  fn move_to(x: i32, y: i32) -> ast::ShapeRecord {
+    ast::ShapeRecord::StyleChange(ast::shape_records::StyleChange {
+      move_to: Some(ast::Vector2D { x, y }),
+      left_fill: Some(1),
+      right_fill: None,
+      line_style: None,
+      new_styles: None,
+    })
+  }
+
+  fn edge(x: i32, y: i32, control: Option<(i32, i32)>) -> ast::ShapeRecord {
+    ast::ShapeRecord::Edge(ast::shape_records::Edge {
+      delta: ast::Vector2D { x, y },
+      control_delta: control.map(|(x, y)| ast::Vector2D { x, y }),
+    })
+  }
+
+  #[test]
+  fn test_build_define_morph_shape() {
+    let start = ast::Shape {
+      initial_styles: ast::ShapeStyles {
+        fill: vec![solid(0)],
+        line: Vec::new(),
+      },
+      records: vec![
+        move_to(0, 0),
+        edge(100, 0, None),
+        edge(0, 100, None),
+        edge(-100, -100, None),
+      ],
+    };
+    let end = ast::Shape {
+      initial_styles: ast::ShapeStyles {
+        fill: vec![solid(255)],
+        line: Vec::new(),
+      },
+      records: vec![
+        move_to(50, 50),
+        edge(200, 0, None),
+        edge(0, 200, Some((50, 100))),
+        edge(-200, 0, None),
+        edge(0, -200, None),
+      ],
+    };
+    let tag = build_define_morph_shape(1, &start, &end).unwrap();
+    let edges: Vec<&ast::shape_records::MorphEdge> = tag
+      .shape
+      .records
+      .iter()
+      .filter_map(|record| match record {
+        ast::MorphShapeRecord::Edge(ref edge) => Some(edge),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(edges.len(), 4);
+    assert!(edges
+      .iter()
+      .all(|edge| edge.control_delta.is_some() == edge.morph_control_delta.is_some()));
+    assert_eq!(
+      tag.bounds,
+      ast::Rect {
+        x_min: 0,
+        x_max: 100,
+        y_min: 0,
+        y_max: 100
+      }
+    );
+
+    let bytes = crate::emit_tag(&ast::Tag::DefineMorphShape(tag.clone()), 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(ast::Tag::DefineMorphShape(tag)));
+  }
+
+  #[test]
+  fn test_min_morph_shape_version() {
+    let line = |start_cap: ast::CapStyle| ast::LineStyle {
+      width: 20,
+      start_cap,
+      end_cap: ast::CapStyle::Round,
+      join: ast::JoinStyle::Round,
+      no_h_scale: false,
+      no_v_scale: false,
+      no_close: false,
+      pixel_hinting: false,
+      fill: solid(0),
+    };
+    let shape = |start_cap: ast::CapStyle| ast::Shape {
+      initial_styles: ast::ShapeStyles {
+        fill: Vec::new(),
+        line: vec![line(start_cap)],
+      },
+      records: vec![
+        ast::ShapeRecord::StyleChange(ast::shape_records::StyleChange {
+          move_to: Some(ast::Vector2D { x: 0, y: 0 }),
+          left_fill: None,
+          right_fill: None,
+          line_style: Some(1),
+          new_styles: None,
+        }),
+        edge(100, 0, None),
+      ],
+    };
+    let tag = build_define_morph_shape(1, &shape(ast::CapStyle::Round), &shape(ast::CapStyle::Round)).unwrap();
+    assert_eq!(get_min_morph_shape_version(&tag.shape), MorphShapeVersion::MorphShape1);
+
+    let tag = build_define_morph_shape(1, &shape(ast::CapStyle::Square), &shape(ast::CapStyle::Square)).unwrap();
+    assert_eq!(get_min_morph_shape_version(&tag.shape), MorphShapeVersion::MorphShape2);
+    let bytes = crate::emit_tag(&ast::Tag::DefineMorphShape(tag.clone()), 10).unwrap();
+    match swf_parser::parse_tag(&bytes, 10) {
+      (_, Some(ast::Tag::DefineMorphShape(parsed))) => {
+        let edge_bounds = ast::Rect {
+          x_min: 0,
+          x_max: 100,
+          y_min: 0,
+          y_max: 0,
+        };
+        assert_eq!(parsed.edge_bounds, Some(edge_bounds));
+        assert_eq!(parsed.morph_edge_bounds, Some(edge_bounds));
+        assert_eq!(parsed.shape, tag.shape);
+      }
+      tag => panic!("unexpected tag: {:?}", tag),
+    }
+  }
+
+  #[test]
+  fn test_build_define_morph_shape_style_mismatch() {
+    let start = ast::Shape {
+      initial_styles: ast::ShapeStyles {
+        fill: vec![solid(0)],
+        line: Vec::new(),
+      },
+      records: vec![move_to(0, 0), edge(100, 0, None)],
+    };
+    let end = ast::Shape {
+      initial_styles: ast::ShapeStyles {
+        fill: Vec::new(),
+        line: Vec::new(),
+      },
+      records: vec![move_to(0, 0), edge(100, 0, None)],
+    };
+    assert_eq!(
+      build_define_morph_shape(1, &start, &end),
+      Err(MorphShapeBuildError::StyleCountMismatch)
+    );
+  }
+}
@@ -0,0 +1,395 @@
+//! Import MP3 files as `DefineSound` tags or sound streams.
+//!
+//! The MP3 data is not decoded: the frame headers are parsed to split the data in frames and compute the sample
+//! counts. Leading ID3v2 tags, trailing ID3v1 tags and Xing/Info frames are removed.
+
+use crate::sound_encoder::get_sound_rate_hz;
+use std::error::Error;
+use std::fmt;
+use swf_fixed::Ufixed8P8;
+use swf_types as ast;
+
+/// Delay introduced by MP3 decoders, in samples.
+const DECODER_DELAY: u32 = 529;
+
+const MPEG1_BITRATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+const MPEG2_BITRATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mp3Error {
+  /// Invalid frame header, at the provided byte offset.
+  InvalidFrame(usize),
+  /// The frame at the provided offset is not an MPEG layer III frame.
+  UnsupportedLayer(usize),
+  /// The sample rate does not match a SWF sound rate (only 11025, 22050 and 44100 Hz are supported).
+  UnsupportedSampleRate(u32),
+  /// The frame at the provided offset does not have the sample rate or channel count of the first frame.
+  InconsistentFormat(usize),
+  /// The data does not contain any frame.
+  NoFrames,
+  /// The sound is too long for a SWF sound.
+  TooManySamples,
+}
+
+impl fmt::Display for Mp3Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Mp3Error::InvalidFrame(offset) => write!(f, "invalid MP3 frame at offset {}", offset),
+      Mp3Error::UnsupportedLayer(offset) => write!(f, "MP3 frame at offset {} is not a layer III frame", offset),
+      Mp3Error::UnsupportedSampleRate(rate) => write!(f, "unsupported MP3 sample rate: {} Hz", rate),
+      Mp3Error::InconsistentFormat(offset) => write!(f, "MP3 frame at offset {} changes the sound format", offset),
+      Mp3Error::NoFrames => f.write_str("MP3 data does not contain any frame"),
+      Mp3Error::TooManySamples => f.write_str("MP3 sound is too long"),
+    }
+  }
+}
+
+impl Error for Mp3Error {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct FrameHeader {
+  sample_rate: u32,
+  samples: u32,
+  size: usize,
+  is_mono: bool,
+  /// Size of the side information (including the CRC), before the main data.
+  side_info_size: usize,
+}
+
+/// Frames of an MP3 file.
+struct Mp3Frames<'a> {
+  frames: Vec<&'a [u8]>,
+  sound_rate: ast::SoundRate,
+  sound_type: ast::SoundType,
+  samples_per_frame: u32,
+  /// Number of samples to skip at the start of the decoded data.
+  latency_seek: u32,
+}
+
+/// Builds a `DefineSound` tag from MP3 data.
+///
+/// The sound data starts with the number of samples to skip (the encoder delay read from the LAME header, if any,
+/// plus the decoder delay), followed by the MP3 frames.
+pub fn mp3_to_define_sound(id: u16, data: &[u8]) -> Result<ast::tags::DefineSound, Mp3Error> {
+  let mp3 = parse_frames(data)?;
+  let sample_count = u32::try_from(mp3.frames.len())
+    .ok()
+    .and_then(|count| count.checked_mul(mp3.samples_per_frame))
+    .ok_or(Mp3Error::TooManySamples)?;
+  let latency_seek = i16::try_from(mp3.latency_seek).map_err(|_| Mp3Error::TooManySamples)?;
+
+  let mut sound_data: Vec<u8> = latency_seek.to_le_bytes().to_vec();
+  for frame in &mp3.frames {
+    sound_data.extend_from_slice(frame);
+  }
+  Ok(ast::tags::DefineSound {
+    id,
+    sound_type: mp3.sound_type,
+    sound_size: ast::SoundSize::SoundSize16,
+    sound_rate: mp3.sound_rate,
+    format: ast::AudioCodingFormat::Mp3,
+    sample_count,
+    data: sound_data,
+  })
+}
+
+/// Builds a sound stream from MP3 data: the `SoundStreamHead` tag and the `SoundStreamBlock` tag of each movie frame.
+///
+/// The blocks hold the MP3 frames needed to reach the end of their movie frame, so the stream stays ahead of the
+/// timeline; movie frames already covered by the previous blocks get no block (`None`). The seek value of a block is
+/// the number of samples from the start of its first MP3 frame to the start of the movie frame (it is negative when
+/// the movie frame starts in the previous block).
+pub fn mp3_to_sound_stream(
+  data: &[u8],
+  frame_rate: Ufixed8P8,
+) -> Result<(ast::tags::SoundStreamHead, Vec<Option<ast::tags::SoundStreamBlock>>), Mp3Error> {
+  let mp3 = parse_frames(data)?;
+  let sample_rate = get_sound_rate_hz(mp3.sound_rate);
+  let frame_rate = if frame_rate.epsilons == 0 {
+    1.0
+  } else {
+    f64::from(frame_rate.epsilons) / 256.0
+  };
+  let samples_per_movie_frame = sample_rate / frame_rate;
+
+  let head = ast::tags::SoundStreamHead {
+    playback_sound_type: mp3.sound_type,
+    playback_sound_size: ast::SoundSize::SoundSize16,
+    playback_sound_rate: mp3.sound_rate,
+    stream_sound_type: mp3.sound_type,
+    stream_sound_size: ast::SoundSize::SoundSize16,
+    stream_sound_rate: mp3.sound_rate,
+    stream_format: ast::AudioCodingFormat::Mp3,
+    stream_sample_count: u16::try_from(samples_per_movie_frame.round() as u64).map_err(|_| Mp3Error::TooManySamples)?,
+    latency_seek: Some(i16::try_from(mp3.latency_seek).map_err(|_| Mp3Error::TooManySamples)?),
+  };
+
+  let mut blocks: Vec<Option<ast::tags::SoundStreamBlock>> = Vec::new();
+  let mut frames = mp3.frames.iter();
+  let mut remaining_frames = mp3.frames.len();
+  let mut written_samples: i64 = 0;
+  let samples_per_frame = i64::from(mp3.samples_per_frame);
+  while remaining_frames > 0 {
+    let movie_frame = blocks.len() as f64;
+    let frame_start = (movie_frame * samples_per_movie_frame).round() as i64;
+    let frame_end = ((movie_frame + 1.0) * samples_per_movie_frame).round() as i64;
+    if written_samples >= frame_end {
+      blocks.push(None);
+      continue;
+    }
+    let block_start = written_samples;
+    let mut block_data: Vec<u8> = vec![0; 4];
+    while remaining_frames > 0 && written_samples < frame_end {
+      block_data.extend_from_slice(frames.next().unwrap());
+      remaining_frames -= 1;
+      written_samples += samples_per_frame;
+    }
+    let block_samples = u16::try_from(written_samples - block_start).map_err(|_| Mp3Error::TooManySamples)?;
+    let seek = i16::try_from(frame_start - block_start).map_err(|_| Mp3Error::TooManySamples)?;
+    block_data[..2].copy_from_slice(&block_samples.to_le_bytes());
+    block_data[2..4].copy_from_slice(&seek.to_le_bytes());
+    blocks.push(Some(ast::tags::SoundStreamBlock { data: block_data }));
+  }
+  Ok((head, blocks))
+}
+
+fn parse_frames(data: &[u8]) -> Result<Mp3Frames<'_>, Mp3Error> {
+  let mut offset = get_id3v2_size(data);
+  let mut end = data.len();
+  if end >= offset + 128 && &data[end - 128..end - 125] == b"TAG" {
+    end -= 128;
+  }
+
+  let mut frames: Vec<&[u8]> = Vec::new();
+  let mut first: Option<FrameHeader> = None;
+  let mut encoder_delay: u32 = 0;
+  while offset < end {
+    let header = parse_frame_header(&data[offset..end], offset)?;
+    let frame = data
+      .get(offset..offset + header.size)
+      .ok_or(Mp3Error::InvalidFrame(offset))?;
+    match first {
+      None => {
+        first = Some(header);
+        if let Some(delay) = get_xing_encoder_delay(frame, &header) {
+          encoder_delay = delay;
+          offset += header.size;
+          continue;
+        }
+      }
+      Some(first) => {
+        if header.sample_rate != first.sample_rate || header.is_mono != first.is_mono {
+          return Err(Mp3Error::InconsistentFormat(offset));
+        }
+      }
+    }
+    frames.push(frame);
+    offset += header.size;
+  }
+
+  let first = match first {
+    Some(first) if !frames.is_empty() => first,
+    _ => return Err(Mp3Error::NoFrames),
+  };
+  let sound_rate = match first.sample_rate {
+    11025 => ast::SoundRate::SoundRate11000,
+    22050 => ast::SoundRate::SoundRate22000,
+    44100 => ast::SoundRate::SoundRate44000,
+    rate => return Err(Mp3Error::UnsupportedSampleRate(rate)),
+  };
+  Ok(Mp3Frames {
+    frames,
+    sound_rate,
+    sound_type: if first.is_mono {
+      ast::SoundType::Mono
+    } else {
+      ast::SoundType::Stereo
+    },
+    samples_per_frame: first.samples,
+    latency_seek: encoder_delay + DECODER_DELAY,
+  })
+}
+
+/// Returns the size of the ID3v2 tag at the start of the data (0 if there is none).
+fn get_id3v2_size(data: &[u8]) -> usize {
+  if data.len() < 10 || &data[..3] != b"ID3" {
+    return 0;
+  }
+  // Synchsafe integer (7 bits per byte), excluding the header and footer
+  let size = data[6..10]
+    .iter()
+    .fold(0usize, |size, byte| (size << 7) | usize::from(byte & 0x7f));
+  let has_footer = data[5] & 0x10 != 0;
+  10 + size + if has_footer { 10 } else { 0 }
+}
+
+fn parse_frame_header(data: &[u8], offset: usize) -> Result<FrameHeader, Mp3Error> {
+  if data.len() < 4 || data[0] != 0xff || data[1] & 0xe0 != 0xe0 {
+    return Err(Mp3Error::InvalidFrame(offset));
+  }
+  let version = (data[1] >> 3) & 0b11;
+  let layer = (data[1] >> 1) & 0b11;
+  let has_crc = data[1] & 1 == 0;
+  let bitrate_index = usize::from(data[2] >> 4);
+  let sample_rate_index = usize::from((data[2] >> 2) & 0b11);
+  let padding = usize::from((data[2] >> 1) & 1);
+  let is_mono = data[3] >> 6 == 0b11;
+  if version == 0b01 || bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+    return Err(Mp3Error::InvalidFrame(offset));
+  }
+  if layer != 0b01 {
+    return Err(Mp3Error::UnsupportedLayer(offset));
+  }
+
+  let is_mpeg1 = version == 0b11;
+  let base_rate = [44100, 48000, 32000][sample_rate_index];
+  let sample_rate = match version {
+    0b11 => base_rate,
+    0b10 => base_rate / 2,
+    _ => base_rate / 4,
+  };
+  let (bitrate, samples) = if is_mpeg1 {
+    (MPEG1_BITRATES[bitrate_index] * 1000, 1152)
+  } else {
+    (MPEG2_BITRATES[bitrate_index] * 1000, 576)
+  };
+  let size = (samples / 8 * bitrate / sample_rate) as usize + padding;
+  let side_info_size = match (is_mpeg1, is_mono) {
+    (true, false) => 32,
+    (true, true) | (false, false) => 17,
+    (false, true) => 9,
+  } + if has_crc { 2 } else { 0 };
+  Ok(FrameHeader {
+    sample_rate,
+    samples,
+    size,
+    is_mono,
+    side_info_size,
+  })
+}
+
+/// Returns the encoder delay if the frame is a Xing/Info frame (0 if it does not have a LAME header).
+fn get_xing_encoder_delay(frame: &[u8], header: &FrameHeader) -> Option<u32> {
+  let tag_offset = 4 + header.side_info_size;
+  let tag = frame.get(tag_offset..tag_offset + 8)?;
+  if &tag[..4] != b"Xing" && &tag[..4] != b"Info" {
+    return None;
+  }
+  let flags = tag[7];
+  // Optional frame count, byte count, table of contents and quality fields
+  let lame_offset = tag_offset
+    + 8
+    + (if flags & 1 != 0 { 4 } else { 0 })
+    + (if flags & 2 != 0 { 4 } else { 0 })
+    + (if flags & 4 != 0 { 100 } else { 0 })
+    + (if flags & 8 != 0 { 4 } else { 0 });
+  let delay = match frame.get(lame_offset..lame_offset + 24) {
+    Some(lame) if &lame[..4] == b"LAME" => (u32::from(lame[21]) << 4) | (u32::from(lame[22]) >> 4),
+    _ => 0,
+  };
+  Some(delay)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// MPEG-1 layer III frame at 44100 Hz and 128 kbit/s (417 bytes).
+  fn frame(is_mono: bool) -> Vec<u8> {
+    let mut frame = vec![0xff, 0xfb, 0x90, if is_mono { 0xc0 } else { 0x00 }];
+    frame.resize(417, 0);
+    frame
+  }
+
+  fn info_frame(encoder_delay: u16) -> Vec<u8> {
+    let mut frame = frame(false);
+    frame[36..40].copy_from_slice(b"Info");
+    // Frame count only
+    frame[43] = 1;
+    frame[48..52].copy_from_slice(b"LAME");
+    frame[48 + 21] = (encoder_delay >> 4) as u8;
+    frame[48 + 22] = ((encoder_delay & 0xf) << 4) as u8;
+    frame
+  }
+
+  #[test]
+  fn test_mp3_to_define_sound() {
+    let mut data: Vec<u8> = vec![b'I', b'D', b'3', 4, 0, 0, 0, 0, 0, 2, 0, 0];
+    data.extend(info_frame(576));
+    data.extend(frame(false));
+    data.extend(frame(false));
+    let tag = mp3_to_define_sound(1, &data).unwrap();
+    assert_eq!(tag.sound_rate, ast::SoundRate::SoundRate44000);
+    assert_eq!(tag.sound_type, ast::SoundType::Stereo);
+    assert_eq!(tag.format, ast::AudioCodingFormat::Mp3);
+    assert_eq!(tag.sample_count, 2 * 1152);
+    assert_eq!(tag.data.len(), 2 + 2 * 417);
+    assert_eq!(&tag.data[..2], &(576u16 + 529).to_le_bytes());
+
+    let bytes = crate::emit_tag(&ast::Tag::DefineSound(tag.clone()), 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(ast::Tag::DefineSound(tag)));
+  }
+
+  #[test]
+  fn test_mp3_errors() {
+    assert_eq!(mp3_to_define_sound(1, &[]), Err(Mp3Error::NoFrames));
+    assert_eq!(mp3_to_define_sound(1, &[0xff, 0xfb]), Err(Mp3Error::InvalidFrame(0)));
+    let mut data = frame(false);
+    data.extend(frame(true));
+    assert_eq!(mp3_to_define_sound(1, &data), Err(Mp3Error::InconsistentFormat(417)));
+    // MPEG-1 at 48000 Hz
+    let mut data = frame(false);
+    data[2] = 0x94;
+    data.truncate(384);
+    assert_eq!(
+      mp3_to_define_sound(1, &data),
+      Err(Mp3Error::UnsupportedSampleRate(48000))
+    );
+  }
+
+  #[test]
+  fn test_mp3_to_sound_stream() {
+    let data: Vec<u8> = (0..5).flat_map(|_| frame(true)).collect();
+    // 44100 / 50 = 882 samples per movie frame
+    let (head, blocks) = mp3_to_sound_stream(&data, Ufixed8P8::from_epsilons(50 << 8)).unwrap();
+    assert_eq!(head.stream_sample_count, 882);
+    assert_eq!(head.latency_seek, Some(529));
+    let block_info: Vec<Option<(u16, i16, usize)>> = blocks
+      .iter()
+      .map(|block| {
+        block.as_ref().map(|block| {
+          (
+            u16::from_le_bytes([block.data[0], block.data[1]]),
+            i16::from_le_bytes([block.data[2], block.data[3]]),
+            (block.data.len() - 4) / 417,
+          )
+        })
+      })
+      .collect();
+    // MP3 frames end at 1152, 2304, 3456, 4608 and 5760; movie frames at 882, 1764, 2646, 3528, 4410 and 5292
+    assert_eq!(
+      block_info,
+      vec![
+        Some((1152, 0, 1)),
+        Some((1152, 882 - 1152, 1)),
+        Some((1152, 1764 - 2304, 1)),
+        Some((1152, 2646 - 3456, 1)),
+        None,
+        Some((1152, 4410 - 4608, 1)),
+      ]
+    );
+
+    let tags = [
+      ast::Tag::SoundStreamHead(head),
+      ast::Tag::SoundStreamBlock(blocks[1].clone().unwrap()),
+    ];
+    for tag in tags {
+      let bytes = crate::emit_tag(&tag, 10).unwrap();
+      let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+      assert!(rest.is_empty());
+      assert_eq!(parsed, Some(tag));
+    }
+  }
+}
@@ -0,0 +1,201 @@
+use swf_types as ast;
+
+use crate::shape::MAX_EDGE_DELTA;
+
+/// Maximum distance (in output units) between a cubic curve and its quadratic approximation.
+const CUBIC_TOLERANCE: f64 = 0.5;
+
+/// Maximum recursion depth when splitting curves.
+const MAX_SPLIT_DEPTH: u32 = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Point {
+  pub(crate) x: f64,
+  pub(crate) y: f64,
+}
+
+impl Point {
+  pub(crate) fn new(x: f64, y: f64) -> Self {
+    Self { x, y }
+  }
+
+  pub(crate) fn lerp(self, other: Self, t: f64) -> Self {
+    Self::new(self.x + (other.x - self.x) * t, self.y + (other.y - self.y) * t)
+  }
+}
+
+/// Builds a shape record string from absolute floating point coordinates.
+///
+/// Coordinates are rounded to integers (twips for shapes, EM units for glyphs). The builder tracks
+/// the rounded pen position so rounding errors do not accumulate across edges.
+pub(crate) struct ShapeRecordsBuilder {
+  records: Vec<ast::ShapeRecord>,
+  position: ast::Vector2D,
+  subpath_start: ast::Vector2D,
+}
+
+impl ShapeRecordsBuilder {
+  pub(crate) fn new() -> Self {
+    Self {
+      records: Vec::new(),
+      position: ast::Vector2D { x: 0, y: 0 },
+      subpath_start: ast::Vector2D { x: 0, y: 0 },
+    }
+  }
+
+  /// Returns the trailing style change record, creating an empty one if needed.
+  ///
+  /// A record defining new styles is never reused: its style indices are encoded with the bit counts
+  /// of the previous styles, so selecting one of the new styles requires a separate record.
+  pub(crate) fn style_change_mut(&mut self) -> &mut ast::shape_records::StyleChange {
+    let reusable = matches!(
+      self.records.last(),
+      Some(ast::ShapeRecord::StyleChange(ast::shape_records::StyleChange {
+        new_styles: None,
+        ..
+      }))
+    );
+    if !reusable {
+      self
+        .records
+        .push(ast::ShapeRecord::StyleChange(ast::shape_records::StyleChange {
+          move_to: None,
+          left_fill: None,
+          right_fill: None,
+          line_style: None,
+          new_styles: None,
+        }));
+    }
+    match self.records.last_mut() {
+      Some(ast::ShapeRecord::StyleChange(ref mut record)) => record,
+      _ => unreachable!(),
+    }
+  }
+
+  pub(crate) fn move_to(&mut self, p: Point) {
+    let target = round_point(p);
+    self.style_change_mut().move_to = Some(target);
+    self.position = target;
+    self.subpath_start = target;
+  }
+
+  pub(crate) fn line_to(&mut self, p: Point) {
+    self.line_to_depth(p, 0);
+  }
+
+  fn line_to_depth(&mut self, p: Point, depth: u32) {
+    let target = round_point(p);
+    let delta = ast::Vector2D {
+      x: target.x - self.position.x,
+      y: target.y - self.position.y,
+    };
+    if delta.x == 0 && delta.y == 0 {
+      return;
+    }
+    if is_delta_too_large(delta) && depth < MAX_SPLIT_DEPTH {
+      let start = self.position();
+      self.line_to_depth(start.lerp(p, 0.5), depth + 1);
+      self.line_to_depth(p, depth + 1);
+      return;
+    }
+    self.push_edge(delta, None);
+    self.position = target;
+  }
+
+  pub(crate) fn quad_to(&mut self, control: Point, p: Point) {
+    self.quad_to_depth(control, p, 0);
+  }
+
+  fn quad_to_depth(&mut self, control: Point, p: Point, depth: u32) {
+    let start = self.position();
+    let target = round_point(p);
+    let control_target = round_point(control);
+    let delta = ast::Vector2D {
+      x: target.x - self.position.x,
+      y: target.y - self.position.y,
+    };
+    let control_delta = ast::Vector2D {
+      x: control_target.x - self.position.x,
+      y: control_target.y - self.position.y,
+    };
+    if (is_delta_too_large(delta) || is_delta_too_large(control_delta)) && depth < MAX_SPLIT_DEPTH {
+      let c0 = start.lerp(control, 0.5);
+      let c1 = control.lerp(p, 0.5);
+      let mid = c0.lerp(c1, 0.5);
+      self.quad_to_depth(c0, mid, depth + 1);
+      self.quad_to_depth(c1, p, depth + 1);
+      return;
+    }
+    if delta.x == 0 && delta.y == 0 && control_delta.x == 0 && control_delta.y == 0 {
+      return;
+    }
+    self.push_edge(delta, Some(control_delta));
+    self.position = target;
+  }
+
+  /// Appends a cubic Bézier curve, approximated with quadratic curves.
+  pub(crate) fn cubic_to(&mut self, control1: Point, control2: Point, p: Point) {
+    let start = self.position();
+    self.cubic_to_depth(start, control1, control2, p, 0);
+  }
+
+  fn cubic_to_depth(&mut self, p0: Point, p1: Point, p2: Point, p3: Point, depth: u32) {
+    // Distance between the cubic and the quadratic curve with control point `(3 * (p1 + p2) - p0 - p3) / 4`.
+    let dx = p3.x - 3.0 * p2.x + 3.0 * p1.x - p0.x;
+    let dy = p3.y - 3.0 * p2.y + 3.0 * p1.y - p0.y;
+    let error = (dx * dx + dy * dy).sqrt() * 3.0_f64.sqrt() / 36.0;
+    if error <= CUBIC_TOLERANCE || depth >= MAX_SPLIT_DEPTH {
+      let control = Point::new(
+        (3.0 * (p1.x + p2.x) - p0.x - p3.x) / 4.0,
+        (3.0 * (p1.y + p2.y) - p0.y - p3.y) / 4.0,
+      );
+      self.quad_to(control, p3);
+      return;
+    }
+    let p01 = p0.lerp(p1, 0.5);
+    let p12 = p1.lerp(p2, 0.5);
+    let p23 = p2.lerp(p3, 0.5);
+    let p012 = p01.lerp(p12, 0.5);
+    let p123 = p12.lerp(p23, 0.5);
+    let mid = p012.lerp(p123, 0.5);
+    self.cubic_to_depth(p0, p01, p012, mid, depth + 1);
+    self.cubic_to_depth(mid, p123, p23, p3, depth + 1);
+  }
+
+  /// Closes the current subpath with a straight edge back to its start.
+  pub(crate) fn close(&mut self) {
+    let start = self.subpath_start;
+    self.line_to(Point::new(f64::from(start.x), f64::from(start.y)));
+  }
+
+  #[cfg(feature = "svg")]
+  pub(crate) fn is_closed(&self) -> bool {
+    self.position == self.subpath_start
+  }
+
+  pub(crate) fn position(&self) -> Point {
+    Point::new(f64::from(self.position.x), f64::from(self.position.y))
+  }
+
+  pub(crate) fn into_records(self) -> Vec<ast::ShapeRecord> {
+    self.records
+  }
+
+  fn push_edge(&mut self, delta: ast::Vector2D, control_delta: Option<ast::Vector2D>) {
+    self.records.push(ast::ShapeRecord::Edge(ast::shape_records::Edge {
+      delta,
+      control_delta,
+    }));
+  }
+}
+
+fn round_point(p: Point) -> ast::Vector2D {
+  ast::Vector2D {
+    x: p.x.round() as i32,
+    y: p.y.round() as i32,
+  }
+}
+
+fn is_delta_too_large(delta: ast::Vector2D) -> bool {
+  i64::from(delta.x).abs() > MAX_EDGE_DELTA || i64::from(delta.y).abs() > MAX_EDGE_DELTA
+}
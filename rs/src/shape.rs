@@ -6,7 +6,7 @@ use swf_types as ast;
 
 use crate::basic_data_types::{emit_matrix, emit_s_rgb8, emit_straight_s_rgba8};
 use crate::bit_count::{get_i32_min_bit_count, get_u32_bit_count};
-use crate::gradient::emit_gradient;
+use crate::gradient::{emit_gradient, MAX_LEGACY_GRADIENT_STOPS};
 use crate::io_bits::{BitsWriter, WriteBits};
 use crate::primitives::{emit_le_i16, emit_le_u16, emit_u8};
 
@@ -25,12 +25,36 @@ pub(crate) fn emit_glyph<W: io::Write>(writer: &mut W, value: &ast::Glyph) -> io
 }
 
 pub(crate) fn emit_glyph_bits<W: WriteBits>(writer: &mut W, value: &ast::Glyph) -> io::Result<()> {
-  // TODO: Check how to determine the bit count (scan records?)
-  let fill_bits: u32 = 1; // 2 styles (empty and filled) -> 1 bit
-  let line_bits: u32 = 0; // no line styles
+  let mut max_fill_id: usize = 0;
+  for record in &value.records {
+    if let ast::ShapeRecord::StyleChange(ref record) = record {
+      if record.new_styles.is_some() {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidInput,
+          "glyph records must not define new styles",
+        ));
+      }
+      if record.line_style.map_or(false, |line_style| line_style != 0) {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidInput,
+          "glyph records must not use line styles",
+        ));
+      }
+      max_fill_id = max(max_fill_id, record.left_fill.unwrap_or(0));
+      max_fill_id = max(max_fill_id, record.right_fill.unwrap_or(0));
+    }
+  }
+  let fill_bits: u32 = u32::try_from(max_fill_id)
+    .ok()
+    .map(get_u32_bit_count)
+    .filter(|bits| *bits <= 0x0f)
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "glyph fill style index is too large"))?;
+  // Glyphs use at least 1 fill bit: the single implicit fill style is selected with `1`.
+  let fill_bits: u32 = max(1, fill_bits);
+  let line_bits: u32 = 0;
   writer.write_u32_bits(4, fill_bits)?;
   writer.write_u32_bits(4, line_bits)?;
-  // TODO: Check which shape version to use
+  // Glyphs define no styles, so the shape version has no effect on their records.
   emit_shape_record_string_bits(writer, &value.records, fill_bits, line_bits, ShapeVersion::Shape1)
 }
 
@@ -287,7 +311,7 @@ pub(crate) fn emit_solid_fill<W: io::Write + ?Sized>(
   if with_alpha {
     emit_straight_s_rgba8(writer, value.color)
   } else {
-    assert!(value.color.a == u8::max_value());
+    assert!(value.color.a == u8::MAX);
     emit_s_rgb8(
       writer,
       ast::SRgb8 {
@@ -381,7 +405,10 @@ pub(crate) fn get_min_shape_version(value: &ast::Shape) -> ShapeVersion {
     get_shape_styles_min_shape_version(&value.initial_styles),
     |acc, record| match record {
       ast::ShapeRecord::StyleChange(ref record) => match &record.new_styles {
-        Some(ref styles) => max(acc, get_shape_styles_min_shape_version(styles)),
+        Some(ref styles) => max(
+          acc,
+          max(ShapeVersion::Shape2, get_shape_styles_min_shape_version(styles)),
+        ),
         _ => acc,
       },
       _ => acc,
@@ -409,14 +436,23 @@ pub(crate) fn get_fill_style_list_min_shape_version(value: &[ast::FillStyle]) ->
 
 pub(crate) fn get_fill_style_min_shape_version(value: &ast::FillStyle) -> ShapeVersion {
   let has_alpha = match value {
-    ast::FillStyle::Solid(ref style) => style.color.a != u8::max_value(),
-    ast::FillStyle::FocalGradient(ref style) => style.gradient.colors.iter().any(|cs| cs.color.a != u8::max_value()),
-    ast::FillStyle::LinearGradient(ref style) => style.gradient.colors.iter().any(|cs| cs.color.a != u8::max_value()),
-    ast::FillStyle::RadialGradient(ref style) => style.gradient.colors.iter().any(|cs| cs.color.a != u8::max_value()),
+    ast::FillStyle::Solid(ref style) => style.color.a != u8::MAX,
+    ast::FillStyle::FocalGradient(ref style) => style.gradient.colors.iter().any(|cs| cs.color.a != u8::MAX),
+    ast::FillStyle::LinearGradient(ref style) => style.gradient.colors.iter().any(|cs| cs.color.a != u8::MAX),
+    ast::FillStyle::RadialGradient(ref style) => style.gradient.colors.iter().any(|cs| cs.color.a != u8::MAX),
+    _ => false,
+  };
+
+  let needs_shape4 = match value {
+    ast::FillStyle::FocalGradient(_) => true,
+    ast::FillStyle::LinearGradient(ref style) => style.gradient.colors.len() > MAX_LEGACY_GRADIENT_STOPS,
+    ast::FillStyle::RadialGradient(ref style) => style.gradient.colors.len() > MAX_LEGACY_GRADIENT_STOPS,
     _ => false,
   };
 
-  if has_alpha {
+  if needs_shape4 {
+    ShapeVersion::Shape4
+  } else if has_alpha {
     ShapeVersion::Shape3
   } else {
     ShapeVersion::Shape1
@@ -435,7 +471,7 @@ pub(crate) fn get_line_style_list_min_shape_version(value: &[ast::LineStyle]) ->
 }
 
 pub(crate) fn get_line_style_min_shape_version(value: &ast::LineStyle) -> ShapeVersion {
-  let is_solid_fill = !matches!(&value.fill, ast::FillStyle::Solid(_));
+  let is_solid_fill = matches!(&value.fill, ast::FillStyle::Solid(_));
   let is_line_style2 = value.start_cap != ast::CapStyle::Round
     || value.end_cap != ast::CapStyle::Round
     || value.join != ast::JoinStyle::Round
@@ -449,8 +485,548 @@ pub(crate) fn get_line_style_min_shape_version(value: &ast::LineStyle) -> ShapeV
     ShapeVersion::Shape4
   } else {
     match &value.fill {
-      ast::FillStyle::Solid(ref style) if style.color.a != u8::max_value() => ShapeVersion::Shape3,
+      ast::FillStyle::Solid(ref style) if style.color.a != u8::MAX => ShapeVersion::Shape3,
       _ => ShapeVersion::Shape1,
     }
   }
 }
+
+/// Returns the bounds of the shape edges, ignoring line widths.
+pub(crate) fn get_shape_edge_bounds(value: &ast::Shape) -> ast::Rect {
+  get_shape_bounds_with_lines(value, false)
+}
+
+/// Returns the bounds of the shape, including half of the width of the lines.
+pub(crate) fn get_shape_bounds(value: &ast::Shape) -> ast::Rect {
+  get_shape_bounds_with_lines(value, true)
+}
+
+fn get_shape_bounds_with_lines(value: &ast::Shape, with_lines: bool) -> ast::Rect {
+  let mut bounds = BoundsBuilder::new();
+  let mut line_styles: &[ast::LineStyle] = &value.initial_styles.line;
+  let mut half_width: i32 = 0;
+  let mut x: i32 = 0;
+  let mut y: i32 = 0;
+  for record in &value.records {
+    match record {
+      ast::ShapeRecord::Edge(ref record) => {
+        let end_x = x + record.delta.x;
+        let end_y = y + record.delta.y;
+        bounds.add_point(x, y, half_width);
+        bounds.add_point(end_x, end_y, half_width);
+        if let Some(control_delta) = record.control_delta {
+          let control_x = x + control_delta.x;
+          let control_y = y + control_delta.y;
+          let (min_x, max_x) = get_quadratic_extent(x, control_x, end_x);
+          let (min_y, max_y) = get_quadratic_extent(y, control_y, end_y);
+          bounds.add_point(min_x, min_y, half_width);
+          bounds.add_point(max_x, max_y, half_width);
+        }
+        x = end_x;
+        y = end_y;
+      }
+      ast::ShapeRecord::StyleChange(ref record) => {
+        if let Some(ref new_styles) = &record.new_styles {
+          line_styles = &new_styles.line;
+          half_width = 0;
+        }
+        if let Some(line_style) = record.line_style {
+          half_width = match line_style.checked_sub(1).and_then(|index| line_styles.get(index)) {
+            Some(style) if with_lines => i32::from(style.width / 2),
+            _ => 0,
+          };
+        }
+        if let Some(move_to) = record.move_to {
+          x = move_to.x;
+          y = move_to.y;
+        }
+      }
+    }
+  }
+  bounds.into_rect()
+}
+
+/// Returns the `(min, max)` extent of a quadratic Bézier curve along a single axis.
+fn get_quadratic_extent(start: i32, control: i32, end: i32) -> (i32, i32) {
+  let mut min = std::cmp::min(start, end);
+  let mut max = std::cmp::max(start, end);
+  let denominator = f64::from(start) - 2.0 * f64::from(control) + f64::from(end);
+  if denominator != 0.0 {
+    let t = (f64::from(start) - f64::from(control)) / denominator;
+    if t > 0.0 && t < 1.0 {
+      let u = 1.0 - t;
+      let extremum = u * u * f64::from(start) + 2.0 * u * t * f64::from(control) + t * t * f64::from(end);
+      min = std::cmp::min(min, extremum.floor() as i32);
+      max = std::cmp::max(max, extremum.ceil() as i32);
+    }
+  }
+  (min, max)
+}
+
+struct BoundsBuilder {
+  rect: Option<ast::Rect>,
+}
+
+impl BoundsBuilder {
+  fn new() -> Self {
+    Self { rect: None }
+  }
+
+  fn add_point(&mut self, x: i32, y: i32, margin: i32) {
+    let rect = self.rect.get_or_insert(ast::Rect {
+      x_min: x,
+      x_max: x,
+      y_min: y,
+      y_max: y,
+    });
+    rect.x_min = std::cmp::min(rect.x_min, x - margin);
+    rect.x_max = std::cmp::max(rect.x_max, x + margin);
+    rect.y_min = std::cmp::min(rect.y_min, y - margin);
+    rect.y_max = std::cmp::max(rect.y_max, y + margin);
+  }
+
+  fn into_rect(self) -> ast::Rect {
+    self.rect.unwrap_or(ast::Rect {
+      x_min: 0,
+      x_max: 0,
+      y_min: 0,
+      y_max: 0,
+    })
+  }
+}
+
+/// Maximum absolute value of an edge delta component.
+///
+/// Edge deltas use at most 17 bits (4-bit length field storing `bits - 2`).
+pub(crate) const MAX_EDGE_DELTA: i64 = 65535;
+
+/// Returns an equivalent shape whose records use fewer bits.
+///
+/// Consecutive collinear straight edges are merged, curves whose control point lies between their anchors are
+/// converted to straight edges, redundant zero-length edges are removed and style changes selecting the current
+/// styles are dropped. The rendering of the shape is unchanged.
+pub fn optimize_shape(value: &ast::Shape) -> ast::Shape {
+  ast::Shape {
+    initial_styles: value.initial_styles.clone(),
+    records: optimize_shape_records(&value.records),
+  }
+}
+
+fn optimize_shape_records(records: &[ast::ShapeRecord]) -> Vec<ast::ShapeRecord> {
+  let mut result: Vec<ast::ShapeRecord> = Vec::with_capacity(records.len());
+  // Current style selections, `None` when unknown (after new styles are defined).
+  let mut left_fill: Option<usize> = Some(0);
+  let mut right_fill: Option<usize> = Some(0);
+  let mut line_style: Option<usize> = Some(0);
+
+  for (i, record) in records.iter().enumerate() {
+    match record {
+      ast::ShapeRecord::Edge(ref edge) => {
+        let edge = simplify_edge(*edge);
+        if is_zero_length_edge(&edge) {
+          // Keep isolated zero-length edges: they may be rendered as a dot by the line caps.
+          let has_adjacent_edge = matches!(result.last(), Some(ast::ShapeRecord::Edge(_)))
+            || matches!(records.get(i + 1), Some(ast::ShapeRecord::Edge(_)));
+          if has_adjacent_edge {
+            continue;
+          }
+        }
+        if let Some(ast::ShapeRecord::Edge(ref mut last)) = result.last_mut() {
+          if let Some(merged) = merge_straight_edges(last, &edge) {
+            *last = merged;
+            continue;
+          }
+        }
+        result.push(ast::ShapeRecord::Edge(edge));
+      }
+      ast::ShapeRecord::StyleChange(ref style_change) => {
+        if style_change.new_styles.is_some() {
+          left_fill = None;
+          right_fill = None;
+          line_style = None;
+          result.push(record.clone());
+          continue;
+        }
+        let style_change = ast::shape_records::StyleChange {
+          move_to: style_change.move_to,
+          left_fill: select_style(&mut left_fill, style_change.left_fill),
+          right_fill: select_style(&mut right_fill, style_change.right_fill),
+          line_style: select_style(&mut line_style, style_change.line_style),
+          new_styles: None,
+        };
+        if style_change.move_to.is_none()
+          && style_change.left_fill.is_none()
+          && style_change.right_fill.is_none()
+          && style_change.line_style.is_none()
+        {
+          continue;
+        }
+        match result.last_mut() {
+          Some(ast::ShapeRecord::StyleChange(ref mut last)) if last.new_styles.is_none() => {
+            last.move_to = style_change.move_to.or(last.move_to);
+            last.left_fill = style_change.left_fill.or(last.left_fill);
+            last.right_fill = style_change.right_fill.or(last.right_fill);
+            last.line_style = style_change.line_style.or(last.line_style);
+          }
+          _ => result.push(ast::ShapeRecord::StyleChange(style_change)),
+        }
+      }
+    }
+  }
+  result
+}
+
+/// Updates the current style selection, returning `None` if the selection is a no-op.
+fn select_style(current: &mut Option<usize>, selected: Option<usize>) -> Option<usize> {
+  match selected {
+    Some(style) if *current != Some(style) => {
+      *current = Some(style);
+      Some(style)
+    }
+    _ => None,
+  }
+}
+
+fn is_zero_length_edge(edge: &ast::shape_records::Edge) -> bool {
+  edge.delta.x == 0 && edge.delta.y == 0 && edge.control_delta.is_none()
+}
+
+/// Converts a curve to a straight edge if its control point lies on the segment between its anchors.
+fn simplify_edge(edge: ast::shape_records::Edge) -> ast::shape_records::Edge {
+  let control = match edge.control_delta {
+    Some(control) => control,
+    None => return edge,
+  };
+  let (dx, dy) = (i64::from(edge.delta.x), i64::from(edge.delta.y));
+  let (cx, cy) = (i64::from(control.x), i64::from(control.y));
+  let squared_length = dx * dx + dy * dy;
+  let is_degenerate = if squared_length == 0 {
+    cx == 0 && cy == 0
+  } else {
+    let cross = dx * cy - dy * cx;
+    let dot = dx * cx + dy * cy;
+    // The curve is monotonic along the segment when the control point projects inside of it.
+    cross == 0 && (0..=squared_length).contains(&dot)
+  };
+  if is_degenerate {
+    ast::shape_records::Edge {
+      delta: edge.delta,
+      control_delta: None,
+    }
+  } else {
+    edge
+  }
+}
+
+/// Merges two straight edges pointing in the same direction.
+fn merge_straight_edges(
+  first: &ast::shape_records::Edge,
+  second: &ast::shape_records::Edge,
+) -> Option<ast::shape_records::Edge> {
+  if first.control_delta.is_some() || second.control_delta.is_some() {
+    return None;
+  }
+  let (x1, y1) = (i64::from(first.delta.x), i64::from(first.delta.y));
+  let (x2, y2) = (i64::from(second.delta.x), i64::from(second.delta.y));
+  let is_same_direction = x1 * y2 - y1 * x2 == 0 && x1 * x2 + y1 * y2 > 0;
+  let (x, y) = (x1 + x2, y1 + y2);
+  if !is_same_direction || x.abs() > MAX_EDGE_DELTA || y.abs() > MAX_EDGE_DELTA {
+    return None;
+  }
+  Some(ast::shape_records::Edge {
+    delta: ast::Vector2D {
+      x: i32::try_from(x).ok()?,
+      y: i32::try_from(y).ok()?,
+    },
+    control_delta: None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::BTreeMap;
+
+  /// Style selected on one side of an edge or for its stroke: `(kind, style generation, style index)`.
+  type StyleKey = (u8, usize, usize);
+
+  /// Flattened edge segment with the styles applied to it.
+  struct Segment {
+    start: (f64, f64),
+    end: (f64, f64),
+    left_fill: StyleKey,
+    right_fill: StyleKey,
+    line_style: StyleKey,
+    half_width: f64,
+  }
+
+  fn flatten(shape: &ast::Shape) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut generation = 0;
+    let mut line_styles = &shape.initial_styles.line;
+    let (mut left, mut right, mut line) = (0, 0, 0);
+    let (mut x, mut y) = (0.0, 0.0);
+    for record in &shape.records {
+      match record {
+        ast::ShapeRecord::StyleChange(ref record) => {
+          if let Some(ref styles) = record.new_styles {
+            generation += 1;
+            line_styles = &styles.line;
+          }
+          left = record.left_fill.unwrap_or(left);
+          right = record.right_fill.unwrap_or(right);
+          line = record.line_style.unwrap_or(line);
+          if let Some(move_to) = record.move_to {
+            x = f64::from(move_to.x);
+            y = f64::from(move_to.y);
+          }
+        }
+        ast::ShapeRecord::Edge(ref edge) => {
+          let end = (x + f64::from(edge.delta.x), y + f64::from(edge.delta.y));
+          let control = match edge.control_delta {
+            Some(c) => (x + f64::from(c.x), y + f64::from(c.y)),
+            None => ((x + end.0) / 2.0, (y + end.1) / 2.0),
+          };
+          let half_width = match line.checked_sub(1).and_then(|i| line_styles.get(i)) {
+            Some(style) => f64::from(style.width) / 2.0,
+            None => 0.0,
+          };
+          const STEPS: usize = 16;
+          let mut previous = (x, y);
+          for step in 1..=STEPS {
+            let t = step as f64 / STEPS as f64;
+            let u = 1.0 - t;
+            let point = (
+              u * u * x + 2.0 * u * t * control.0 + t * t * end.0,
+              u * u * y + 2.0 * u * t * control.1 + t * t * end.1,
+            );
+            segments.push(Segment {
+              start: previous,
+              end: point,
+              left_fill: (0, generation, left),
+              right_fill: (1, generation, right),
+              line_style: (2, generation, line),
+              half_width,
+            });
+            previous = point;
+          }
+          x = end.0;
+          y = end.1;
+        }
+      }
+    }
+    segments
+  }
+
+  fn distance_to_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let squared_length = dx * dx + dy * dy;
+    let t = if squared_length == 0.0 {
+      0.0
+    } else {
+      (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / squared_length).clamp(0.0, 1.0)
+    };
+    let (x, y) = (a.0 + t * dx - p.0, a.1 + t * dy - p.1);
+    (x * x + y * y).sqrt()
+  }
+
+  /// Rasterizes the shape on a grid of sample points.
+  ///
+  /// Each sample holds the winding number of every fill style (per edge side), and the set of line styles
+  /// covering the sample.
+  fn rasterize(shape: &ast::Shape, min: i32, max: i32, step: i32) -> Vec<BTreeMap<StyleKey, i32>> {
+    let segments = flatten(shape);
+    let mut samples = Vec::new();
+    let mut y = min;
+    while y <= max {
+      let mut x = min;
+      while x <= max {
+        let p = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+        let mut sample: BTreeMap<StyleKey, i32> = BTreeMap::new();
+        for segment in &segments {
+          let (a, b) = (segment.start, segment.end);
+          if (a.1 <= p.1) != (b.1 <= p.1) {
+            let crossing_x = a.0 + (p.1 - a.1) * (b.0 - a.0) / (b.1 - a.1);
+            if crossing_x > p.0 {
+              let direction = if b.1 > a.1 { 1 } else { -1 };
+              *sample.entry(segment.left_fill).or_insert(0) += direction;
+              *sample.entry(segment.right_fill).or_insert(0) -= direction;
+            }
+          }
+          if segment.half_width > 0.0 && distance_to_segment(p, a, b) <= segment.half_width {
+            sample.insert(segment.line_style, 1);
+          }
+        }
+        sample.retain(|_, winding| *winding != 0);
+        samples.push(sample);
+        x += step;
+      }
+      y += step;
+    }
+    samples
+  }
+
+  fn style_change(
+    move_to: Option<(i32, i32)>,
+    left_fill: Option<usize>,
+    line_style: Option<usize>,
+  ) -> ast::ShapeRecord {
+    ast::ShapeRecord::StyleChange(ast::shape_records::StyleChange {
+      move_to: move_to.map(|(x, y)| ast::Vector2D { x, y }),
+      left_fill,
+      right_fill: None,
+      line_style,
+      new_styles: None,
+    })
+  }
+
+  fn line(x: i32, y: i32) -> ast::ShapeRecord {
+    ast::ShapeRecord::Edge(ast::shape_records::Edge {
+      delta: ast::Vector2D { x, y },
+      control_delta: None,
+    })
+  }
+
+  fn curve(cx: i32, cy: i32, x: i32, y: i32) -> ast::ShapeRecord {
+    ast::ShapeRecord::Edge(ast::shape_records::Edge {
+      delta: ast::Vector2D { x, y },
+      control_delta: Some(ast::Vector2D { x: cx, y: cy }),
+    })
+  }
+
+  fn styles() -> ast::ShapeStyles {
+    ast::ShapeStyles {
+      fill: vec![ast::FillStyle::Solid(ast::fill_styles::Solid {
+        color: ast::StraightSRgba8 {
+          r: 255,
+          g: 0,
+          b: 0,
+          a: 255,
+        },
+      })],
+      line: vec![ast::LineStyle {
+        width: 20,
+        start_cap: ast::CapStyle::Round,
+        end_cap: ast::CapStyle::Round,
+        join: ast::JoinStyle::Round,
+        no_h_scale: false,
+        no_v_scale: false,
+        no_close: false,
+        pixel_hinting: false,
+        fill: ast::FillStyle::Solid(ast::fill_styles::Solid {
+          color: ast::StraightSRgba8 {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 255,
+          },
+        }),
+      }],
+    }
+  }
+
+  fn assert_same_rendering(before: &ast::Shape, after: &ast::Shape) {
+    assert_eq!(rasterize(before, -20, 420, 7), rasterize(after, -20, 420, 7));
+  }
+
+  #[test]
+  fn test_emit_glyph_validates_styles() {
+    let glyph = ast::Glyph {
+      records: vec![style_change(Some((0, 0)), Some(1), None), line(100, 0), line(0, 100)],
+    };
+    let mut bytes = Vec::new();
+    emit_glyph(&mut bytes, &glyph).unwrap();
+    assert_eq!(bytes[0], 0x10);
+
+    let glyph = ast::Glyph {
+      records: vec![style_change(Some((0, 0)), Some(1), Some(1)), line(100, 0)],
+    };
+    let error = emit_glyph(&mut Vec::new(), &glyph).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+  }
+
+  #[test]
+  fn test_optimize_shape() {
+    let shape = ast::Shape {
+      initial_styles: styles(),
+      records: vec![
+        style_change(Some((0, 0)), Some(1), Some(1)),
+        line(100, 0),
+        line(100, 0),
+        line(0, 0),
+        line(100, 0),
+        curve(20, 50, 0, 100),
+        curve(0, 0, 0, 100),
+        style_change(None, Some(1), None),
+        line(-150, -100),
+        curve(-75, -50, -150, -100),
+        style_change(Some((100, 300)), None, Some(0)),
+        style_change(None, Some(0), None),
+        line(100, 0),
+        line(-50, 0),
+      ],
+    };
+    let optimized = optimize_shape(&shape);
+    assert_eq!(
+      optimized.records,
+      vec![
+        style_change(Some((0, 0)), Some(1), Some(1)),
+        line(300, 0),
+        curve(20, 50, 0, 100),
+        line(0, 100),
+        line(-300, -200),
+        style_change(Some((100, 300)), Some(0), Some(0)),
+        line(100, 0),
+        line(-50, 0),
+      ]
+    );
+    assert_same_rendering(&shape, &optimized);
+  }
+
+  #[test]
+  fn test_optimize_shape_keeps_curve_overshoot() {
+    let shape = ast::Shape {
+      initial_styles: styles(),
+      records: vec![
+        style_change(Some((100, 100)), Some(1), Some(1)),
+        curve(150, 0, 100, 0),
+        curve(0, 0, 0, 0),
+        line(0, 0),
+        line(-100, 100),
+        line(0, -100),
+      ],
+    };
+    let optimized = optimize_shape(&shape);
+    assert_eq!(optimized.records[1], curve(150, 0, 100, 0));
+    assert_same_rendering(&shape, &optimized);
+  }
+
+  #[test]
+  fn test_optimize_shape_random() {
+    // Linear congruential generator, to get reproducible shapes without extra dependencies.
+    let mut seed: u32 = 0x2545_f491;
+    let mut next = |bound: i32| -> i32 {
+      seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+      ((seed >> 16) % (bound as u32)) as i32
+    };
+    for _ in 0..20 {
+      let mut records = vec![style_change(Some((200, 200)), Some(1), Some(1))];
+      for _ in 0..12 {
+        let direction = (next(3) - 1, next(3) - 1);
+        let length = next(4) * 10;
+        let (dx, dy) = (direction.0 * length, direction.1 * length);
+        records.push(match next(5) {
+          0 => curve(dx / 2, dy / 2, dx, dy),
+          1 => curve(next(40) - 20, next(40) - 20, dx, dy),
+          2 => style_change(None, Some(next(2) as usize), Some(next(2) as usize)),
+          _ => line(dx, dy),
+        });
+      }
+      let shape = ast::Shape {
+        initial_styles: styles(),
+        records,
+      };
+      assert_same_rendering(&shape, &optimize_shape(&shape));
+    }
+  }
+}
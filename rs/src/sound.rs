@@ -4,6 +4,152 @@ use std::io;
 use swf_types as ast;
 
 use crate::primitives::{emit_le_u16, emit_le_u32, emit_u8};
+use crate::sound_encoder::get_sound_rate_hz;
+
+/// Maximum number of points of a sound envelope.
+pub const MAX_ENVELOPE_POINTS: usize = 255;
+
+/// Envelope level of a channel played at full volume.
+pub const MAX_ENVELOPE_LEVEL: u16 = 32768;
+
+/// Envelope effect, with durations in milliseconds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EnvelopeEffect {
+  /// Raises the volume from silence at the start of the playback.
+  FadeIn { duration: u32 },
+  /// Lowers the volume to silence at the end of the playback (the end of the last loop).
+  FadeOut { duration: u32 },
+  /// Moves the balance from `from` to `to` at the start of the playback. The balance ranges from -1 (left channel
+  /// only) to 1 (right channel only).
+  Pan { duration: u32, from: f32, to: f32 },
+}
+
+/// Playback options of a sound; times are in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SoundInfoOptions {
+  pub sync_stop: bool,
+  pub sync_no_multiple: bool,
+  /// Start of the played part of the sound.
+  pub in_point: Option<u32>,
+  /// End of the played part of the sound.
+  pub out_point: Option<u32>,
+  pub loop_count: Option<u16>,
+  /// Effects combined into the sound envelope.
+  pub effects: Vec<EnvelopeEffect>,
+}
+
+/// Builds the `SoundInfo` used to play `sound`.
+///
+/// Times are converted to positions in 44 kHz samples. Envelope positions are relative to the start of the playback
+/// (the in point) and span all the loops. Overlapping effects are multiplied at the envelope points.
+///
+/// Returns an `InvalidInput` error if the in and out points are outside of the sound or out of order, if the loop
+/// count is 0, if an effect is longer than the playback or has an invalid balance, or if the envelope would have more
+/// than `MAX_ENVELOPE_POINTS` points.
+pub fn build_sound_info(sound: &ast::tags::DefineSound, options: &SoundInfoOptions) -> io::Result<ast::SoundInfo> {
+  let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, String::from(message));
+
+  let sound_length = (f64::from(sound.sample_count) * 44100.0 / get_sound_rate_hz(sound.sound_rate)).round() as u64;
+  let in_point = options.in_point.map(ms_to_pos44);
+  let out_point = options.out_point.map(ms_to_pos44);
+  let start = in_point.unwrap_or(0);
+  let end = out_point.unwrap_or(sound_length);
+  if end > sound_length {
+    return Err(invalid("sound out point is after the end of the sound"));
+  }
+  if start >= end {
+    return Err(invalid("sound in point must be before the out point"));
+  }
+  if options.loop_count == Some(0) {
+    return Err(invalid("sound loop count must not be 0"));
+  }
+  let playback_length = (end - start) * u64::from(options.loop_count.unwrap_or(1));
+
+  let envelope_records = if options.effects.is_empty() {
+    None
+  } else {
+    let mut positions: Vec<u64> = vec![0];
+    for effect in &options.effects {
+      let (duration, balances) = match *effect {
+        EnvelopeEffect::FadeIn { duration } | EnvelopeEffect::FadeOut { duration } => (duration, None),
+        EnvelopeEffect::Pan { duration, from, to } => (duration, Some((from, to))),
+      };
+      let duration = ms_to_pos44(duration);
+      if duration > playback_length {
+        return Err(invalid("sound envelope effect is longer than the playback"));
+      }
+      if let Some((from, to)) = balances {
+        if !(-1.0..=1.0).contains(&from) || !(-1.0..=1.0).contains(&to) {
+          return Err(invalid("sound balance must be between -1 and 1"));
+        }
+      }
+      match effect {
+        EnvelopeEffect::FadeOut { .. } => positions.extend([playback_length - duration, playback_length]),
+        _ => positions.push(duration),
+      }
+    }
+    positions.sort_unstable();
+    positions.dedup();
+    if positions.len() > MAX_ENVELOPE_POINTS {
+      return Err(invalid("sound envelope has too many points"));
+    }
+    let mut records: Vec<ast::SoundEnvelope> = Vec::with_capacity(positions.len());
+    for position in positions {
+      let (left, right) = get_envelope_gains(&options.effects, position, playback_length);
+      records.push(ast::SoundEnvelope {
+        pos44: u32::try_from(position).map_err(|_| invalid("sound playback is too long for an envelope"))?,
+        left_level: (left * f64::from(MAX_ENVELOPE_LEVEL)).round() as u16,
+        right_level: (right * f64::from(MAX_ENVELOPE_LEVEL)).round() as u16,
+      });
+    }
+    Some(records)
+  };
+
+  let to_u32 = |position: u64| u32::try_from(position).map_err(|_| invalid("sound position is too large"));
+  Ok(ast::SoundInfo {
+    sync_stop: options.sync_stop,
+    sync_no_multiple: options.sync_no_multiple,
+    in_point: in_point.map(to_u32).transpose()?,
+    out_point: out_point.map(to_u32).transpose()?,
+    loop_count: options.loop_count,
+    envelope_records,
+  })
+}
+
+fn ms_to_pos44(ms: u32) -> u64 {
+  u64::from(ms) * 441 / 10
+}
+
+/// Returns the left and right gains (between 0 and 1) of the combined effects at the provided position.
+fn get_envelope_gains(effects: &[EnvelopeEffect], position: u64, playback_length: u64) -> (f64, f64) {
+  let mut gains = (1.0, 1.0);
+  for effect in effects {
+    let progress = |start: u64, duration: u32| {
+      let duration = ms_to_pos44(duration);
+      if duration == 0 {
+        1.0
+      } else {
+        (position.saturating_sub(start) as f64 / duration as f64).min(1.0)
+      }
+    };
+    let (left, right) = match *effect {
+      EnvelopeEffect::FadeIn { duration } => {
+        let gain = progress(0, duration);
+        (gain, gain)
+      }
+      EnvelopeEffect::FadeOut { duration } => {
+        let gain = 1.0 - progress(playback_length - ms_to_pos44(duration), duration);
+        (gain, gain)
+      }
+      EnvelopeEffect::Pan { duration, from, to } => {
+        let balance = f64::from(from) + (f64::from(to) - f64::from(from)) * progress(0, duration);
+        ((1.0 - balance).min(1.0), (1.0 + balance).min(1.0))
+      }
+    };
+    gains = (gains.0 * left, gains.1 * right);
+  }
+  gains
+}
 
 pub(crate) fn audio_coding_format_to_code(value: ast::AudioCodingFormat) -> u8 {
   match value {
@@ -63,7 +209,13 @@ pub(crate) fn emit_sound_info<W: io::Write>(writer: &mut W, value: &ast::SoundIn
 }
 
 pub(crate) fn emit_sound_envelope<W: io::Write>(writer: &mut W, value: &[ast::SoundEnvelope]) -> io::Result<()> {
-  emit_u8(writer, value.len().try_into().unwrap())?;
+  let record_count: u8 = value.len().try_into().map_err(|_| {
+    io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("sound envelopes are limited to {} points", MAX_ENVELOPE_POINTS),
+    )
+  })?;
+  emit_u8(writer, record_count)?;
   for record in value {
     emit_le_u32(writer, record.pos44)?;
     emit_le_u16(writer, record.left_level)?;
@@ -72,3 +224,114 @@ pub(crate) fn emit_sound_envelope<W: io::Write>(writer: &mut W, value: &[ast::So
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sound(sample_count: u32) -> ast::tags::DefineSound {
+    ast::tags::DefineSound {
+      id: 1,
+      sound_type: ast::SoundType::Stereo,
+      sound_size: ast::SoundSize::SoundSize16,
+      sound_rate: ast::SoundRate::SoundRate22000,
+      format: ast::AudioCodingFormat::UncompressedLittleEndian,
+      sample_count,
+      data: Vec::new(),
+    }
+  }
+
+  fn envelope(pos44: u32, left_level: u16, right_level: u16) -> ast::SoundEnvelope {
+    ast::SoundEnvelope {
+      pos44,
+      left_level,
+      right_level,
+    }
+  }
+
+  #[test]
+  fn test_build_sound_info() {
+    // 2 seconds at 22050 Hz, played from 0.5s to 1.5s twice
+    let options = SoundInfoOptions {
+      in_point: Some(500),
+      out_point: Some(1500),
+      loop_count: Some(2),
+      effects: vec![
+        EnvelopeEffect::FadeIn { duration: 100 },
+        EnvelopeEffect::FadeOut { duration: 1000 },
+        EnvelopeEffect::Pan {
+          duration: 1000,
+          from: -1.0,
+          to: 0.0,
+        },
+      ],
+      ..SoundInfoOptions::default()
+    };
+    let info = build_sound_info(&sound(44100), &options).unwrap();
+    assert_eq!(info.in_point, Some(22050));
+    assert_eq!(info.out_point, Some(66150));
+    assert_eq!(info.loop_count, Some(2));
+    assert_eq!(
+      info.envelope_records,
+      Some(vec![
+        envelope(0, 0, 0),
+        envelope(4410, 32768, 3277),
+        envelope(44100, 32768, 32768),
+        envelope(88200, 0, 0),
+      ])
+    );
+
+    let bytes = crate::emit_tag(
+      &ast::Tag::StartSound(ast::tags::StartSound {
+        sound_id: 1,
+        sound_info: info.clone(),
+      }),
+      10,
+    )
+    .unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(
+      parsed,
+      Some(ast::Tag::StartSound(ast::tags::StartSound {
+        sound_id: 1,
+        sound_info: info,
+      }))
+    );
+  }
+
+  #[test]
+  fn test_build_sound_info_errors() {
+    let build = |options: SoundInfoOptions| build_sound_info(&sound(22050), &options);
+    assert!(build(SoundInfoOptions {
+      out_point: Some(1001),
+      ..SoundInfoOptions::default()
+    })
+    .is_err());
+    assert!(build(SoundInfoOptions {
+      in_point: Some(500),
+      out_point: Some(500),
+      ..SoundInfoOptions::default()
+    })
+    .is_err());
+    assert!(build(SoundInfoOptions {
+      loop_count: Some(0),
+      ..SoundInfoOptions::default()
+    })
+    .is_err());
+    assert!(build(SoundInfoOptions {
+      effects: vec![EnvelopeEffect::FadeIn { duration: 1001 }],
+      ..SoundInfoOptions::default()
+    })
+    .is_err());
+    assert!(build(SoundInfoOptions {
+      loop_count: Some(2),
+      effects: vec![EnvelopeEffect::FadeIn { duration: 1001 }],
+      ..SoundInfoOptions::default()
+    })
+    .is_ok());
+
+    let records = vec![envelope(0, 0, 0); MAX_ENVELOPE_POINTS + 1];
+    assert!(emit_sound_envelope(&mut Vec::new(), &records).is_err());
+  }
+}
@@ -0,0 +1,350 @@
+//! Encode PCM samples as `DefineSound` tags.
+//!
+//! Samples are resampled (with linear interpolation) to the nearest SWF sound rate, then stored as uncompressed
+//! little-endian samples or with the SWF variant of ADPCM.
+
+use crate::io_bits::{BitsWriter, WriteBits};
+use std::io;
+use swf_types as ast;
+
+/// Number of samples per channel in an ADPCM packet (including the initial sample).
+const ADPCM_PACKET_SIZE: usize = 4096;
+
+const ADPCM_STEP_SIZES: [i32; 89] = [
+  7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107,
+  118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796, 876, 963,
+  1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894,
+  6484, 7132, 7845, 8630, 9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794,
+  32767,
+];
+
+/// Step index adjustments, by code size and code magnitude.
+const ADPCM_INDEX_TABLES: [&[i32]; 4] = [
+  &[-1, 2],
+  &[-1, -1, 2, 4],
+  &[-1, -1, -1, -1, 2, 4, 6, 8],
+  &[-1, -1, -1, -1, -1, -1, -1, -1, 1, 2, 4, 6, 8, 10, 13, 16],
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SoundEncoding {
+  /// Uncompressed little-endian samples (unsigned for 8-bit samples, signed for 16-bit samples).
+  Uncompressed(ast::SoundSize),
+  /// ADPCM with 2 to 5 bits per sample.
+  Adpcm { bits: u8 },
+}
+
+/// Returns the SWF sound rate closest to `sample_rate` (in Hz).
+pub fn get_nearest_sound_rate(sample_rate: u32) -> ast::SoundRate {
+  let rates = [
+    ast::SoundRate::SoundRate5500,
+    ast::SoundRate::SoundRate11000,
+    ast::SoundRate::SoundRate22000,
+    ast::SoundRate::SoundRate44000,
+  ];
+  let distance = |rate: ast::SoundRate| (get_sound_rate_hz(rate) - f64::from(sample_rate)).abs();
+  let mut nearest = rates[0];
+  for rate in rates {
+    if distance(rate) < distance(nearest) {
+      nearest = rate;
+    }
+  }
+  nearest
+}
+
+/// Returns the exact frequency of a SWF sound rate, in Hz.
+pub fn get_sound_rate_hz(rate: ast::SoundRate) -> f64 {
+  match rate {
+    ast::SoundRate::SoundRate5500 => 5512.5,
+    ast::SoundRate::SoundRate11000 => 11025.0,
+    ast::SoundRate::SoundRate22000 => 22050.0,
+    ast::SoundRate::SoundRate44000 => 44100.0,
+  }
+}
+
+/// Builds a `DefineSound` tag from interleaved 16-bit PCM samples.
+///
+/// `sound_type` gives the number of channels of `samples`. Returns an `InvalidInput` error for a null sample rate, a
+/// sample count which is not a multiple of the channel count or an unsupported ADPCM code size.
+pub fn encode_define_sound(
+  id: u16,
+  samples: &[i16],
+  sound_type: ast::SoundType,
+  sample_rate: u32,
+  encoding: SoundEncoding,
+) -> io::Result<ast::tags::DefineSound> {
+  let channels: usize = match sound_type {
+    ast::SoundType::Mono => 1,
+    ast::SoundType::Stereo => 2,
+  };
+  if sample_rate == 0 {
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, "sample rate must not be 0"));
+  }
+  if samples.len() % channels != 0 {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "sample count must be a multiple of the channel count",
+    ));
+  }
+
+  let sound_rate = get_nearest_sound_rate(sample_rate);
+  let samples = resample(samples, channels, f64::from(sample_rate), get_sound_rate_hz(sound_rate));
+  let sample_count: u32 = (samples.len() / channels)
+    .try_into()
+    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many samples"))?;
+
+  let (sound_size, format, data) = match encoding {
+    SoundEncoding::Uncompressed(ast::SoundSize::SoundSize8) => {
+      let data = samples.iter().map(|sample| ((sample >> 8) + 128) as u8).collect();
+      (
+        ast::SoundSize::SoundSize8,
+        ast::AudioCodingFormat::UncompressedLittleEndian,
+        data,
+      )
+    }
+    SoundEncoding::Uncompressed(ast::SoundSize::SoundSize16) => {
+      let data = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+      (
+        ast::SoundSize::SoundSize16,
+        ast::AudioCodingFormat::UncompressedLittleEndian,
+        data,
+      )
+    }
+    SoundEncoding::Adpcm { bits } => {
+      if !(2..=5).contains(&bits) {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidInput,
+          format!("ADPCM code size must be between 2 and 5 bits: {}", bits),
+        ));
+      }
+      let data = encode_adpcm(&samples, channels, bits)?;
+      (ast::SoundSize::SoundSize16, ast::AudioCodingFormat::Adpcm, data)
+    }
+  };
+
+  Ok(ast::tags::DefineSound {
+    id,
+    sound_type,
+    sound_size,
+    sound_rate,
+    format,
+    sample_count,
+    data,
+  })
+}
+
+/// Resamples interleaved samples with linear interpolation.
+fn resample(samples: &[i16], channels: usize, from_rate: f64, to_rate: f64) -> Vec<i16> {
+  if (from_rate - to_rate).abs() < f64::EPSILON {
+    return samples.to_vec();
+  }
+  let input_frames = samples.len() / channels;
+  if input_frames == 0 {
+    return Vec::new();
+  }
+  let output_frames = ((input_frames as f64) * to_rate / from_rate).round().max(1.0) as usize;
+  let mut output: Vec<i16> = Vec::with_capacity(output_frames * channels);
+  for frame in 0..output_frames {
+    let position = (frame as f64) * from_rate / to_rate;
+    let index = (position.floor() as usize).min(input_frames - 1);
+    let next_index = (index + 1).min(input_frames - 1);
+    let ratio = position - index as f64;
+    for channel in 0..channels {
+      let current = f64::from(samples[index * channels + channel]);
+      let next = f64::from(samples[next_index * channels + channel]);
+      output.push((current + (next - current) * ratio).round() as i16);
+    }
+  }
+  output
+}
+
+/// State of an ADPCM channel, shared by the encoder and the decoder.
+#[derive(Copy, Clone, Debug)]
+struct AdpcmChannel {
+  sample: i32,
+  step_index: i32,
+}
+
+impl AdpcmChannel {
+  /// Applies a code, returning the decoded sample.
+  fn decode(&mut self, code: u32, bits: u8) -> i32 {
+    let sign_mask = 1 << (bits - 1);
+    let mut step = ADPCM_STEP_SIZES[self.step_index as usize];
+    let mut delta = step >> (bits - 1);
+    let mut mask = 1 << (bits - 2);
+    while mask != 0 {
+      if code & mask != 0 {
+        delta += step;
+      }
+      step >>= 1;
+      mask >>= 1;
+    }
+    self.sample = if code & sign_mask != 0 {
+      self.sample - delta
+    } else {
+      self.sample + delta
+    }
+    .clamp(i32::from(i16::MIN), i32::from(i16::MAX));
+    let index_table = ADPCM_INDEX_TABLES[usize::from(bits - 2)];
+    self.step_index = (self.step_index + index_table[(code & !sign_mask) as usize]).clamp(0, 88);
+    self.sample
+  }
+
+  /// Returns the code whose decoded sample is the closest to `target`, and applies it.
+  fn encode(&mut self, target: i32, bits: u8) -> u32 {
+    let mut best: Option<(u32, i32, AdpcmChannel)> = None;
+    for code in 0..(1u32 << bits) {
+      let mut state = *self;
+      let error = (state.decode(code, bits) - target).abs();
+      if best.map_or(true, |(_, best_error, _)| error < best_error) {
+        best = Some((code, error, state));
+      }
+    }
+    let (code, _, state) = best.unwrap();
+    *self = state;
+    code
+  }
+}
+
+fn encode_adpcm(samples: &[i16], channels: usize, bits: u8) -> io::Result<Vec<u8>> {
+  let mut writer = BitsWriter::new(Vec::new());
+  writer.write_u32_bits(2, u32::from(bits - 2))?;
+  let mut states = vec![
+    AdpcmChannel {
+      sample: 0,
+      step_index: 0,
+    };
+    channels
+  ];
+  for (packet_index, packet) in samples.chunks(ADPCM_PACKET_SIZE * channels).enumerate() {
+    // Each packet starts with the exact first sample and the current step index (limited to 6 bits)
+    for (channel, state) in states.iter_mut().enumerate() {
+      state.sample = i32::from(packet[channel]);
+      if packet_index == 0 {
+        // Start with the step closest to the first difference, instead of ramping up from the smallest step
+        let difference = packet
+          .get(channels + channel)
+          .map_or(0, |next| (i32::from(*next) - state.sample).abs());
+        state.step_index = ADPCM_STEP_SIZES
+          .iter()
+          .position(|step| *step >= difference)
+          .unwrap_or(88) as i32;
+      }
+      state.step_index = state.step_index.min(63);
+      writer.write_i32_bits(16, state.sample)?;
+      writer.write_u32_bits(6, state.step_index as u32)?;
+    }
+    for frame in packet.chunks(channels).skip(1) {
+      for (state, sample) in states.iter_mut().zip(frame) {
+        let code = state.encode(i32::from(*sample), bits);
+        writer.write_u32_bits(u32::from(bits), code)?;
+      }
+    }
+  }
+  writer.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Decodes mono ADPCM data.
+  fn decode_adpcm(data: &[u8], sample_count: usize) -> Vec<i16> {
+    let mut bit_pos: usize = 0;
+    let mut read = |count: usize| -> u32 {
+      let mut value: u32 = 0;
+      for _ in 0..count {
+        let bit = (data[bit_pos / 8] >> (7 - bit_pos % 8)) & 1;
+        value = (value << 1) | u32::from(bit);
+        bit_pos += 1;
+      }
+      value
+    };
+    let bits = read(2) as u8 + 2;
+    let mut samples: Vec<i16> = Vec::new();
+    let mut state = AdpcmChannel {
+      sample: 0,
+      step_index: 0,
+    };
+    while samples.len() < sample_count {
+      if samples.len() % ADPCM_PACKET_SIZE == 0 {
+        state.sample = i32::from(read(16) as u16 as i16);
+        state.step_index = read(6) as i32;
+        samples.push(state.sample as i16);
+      } else {
+        samples.push(state.decode(read(usize::from(bits)), bits) as i16);
+      }
+    }
+    samples
+  }
+
+  #[test]
+  fn test_get_nearest_sound_rate() {
+    assert_eq!(get_nearest_sound_rate(8000), ast::SoundRate::SoundRate5500);
+    assert_eq!(get_nearest_sound_rate(16000), ast::SoundRate::SoundRate11000);
+    assert_eq!(get_nearest_sound_rate(32000), ast::SoundRate::SoundRate22000);
+    assert_eq!(get_nearest_sound_rate(48000), ast::SoundRate::SoundRate44000);
+  }
+
+  #[test]
+  fn test_encode_uncompressed() {
+    let samples: Vec<i16> = vec![0, -1, 256, -32768, 32767, 100];
+    let tag = encode_define_sound(
+      1,
+      &samples,
+      ast::SoundType::Stereo,
+      44100,
+      SoundEncoding::Uncompressed(ast::SoundSize::SoundSize16),
+    )
+    .unwrap();
+    assert_eq!(tag.sample_count, 3);
+    assert_eq!(tag.sound_rate, ast::SoundRate::SoundRate44000);
+    assert_eq!(tag.data, vec![0, 0, 0xff, 0xff, 0, 1, 0, 0x80, 0xff, 0x7f, 100, 0]);
+
+    let tag = encode_define_sound(
+      1,
+      &samples,
+      ast::SoundType::Mono,
+      88200,
+      SoundEncoding::Uncompressed(ast::SoundSize::SoundSize8),
+    )
+    .unwrap();
+    assert_eq!(tag.sample_count, 3);
+    assert_eq!(tag.sound_size, ast::SoundSize::SoundSize8);
+    assert_eq!(tag.data, vec![128, 129, 255]);
+
+    let bytes = crate::emit_tag(&ast::Tag::DefineSound(tag.clone()), 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(ast::Tag::DefineSound(tag)));
+  }
+
+  #[test]
+  fn test_encode_adpcm() {
+    let samples: Vec<i16> = (0..5000).map(|i| ((i as f64 * 0.05).sin() * 12000.0) as i16).collect();
+    for bits in 2..=5 {
+      let tag = encode_define_sound(1, &samples, ast::SoundType::Mono, 22050, SoundEncoding::Adpcm { bits }).unwrap();
+      assert_eq!(tag.format, ast::AudioCodingFormat::Adpcm);
+      assert_eq!(tag.sample_count, 5000);
+      // Code size, 2 packet headers and the codes
+      assert_eq!(tag.data.len(), (2 + 2 * 22 + 4998 * usize::from(bits) + 7) / 8);
+      let decoded = decode_adpcm(&tag.data, samples.len());
+      let max_error = samples
+        .iter()
+        .zip(decoded.iter())
+        .map(|(a, b)| (i32::from(*a) - i32::from(*b)).abs())
+        .max()
+        .unwrap();
+      assert!(max_error < 500, "bits = {}, max error = {}", bits, max_error);
+    }
+    assert!(encode_define_sound(
+      1,
+      &samples,
+      ast::SoundType::Mono,
+      22050,
+      SoundEncoding::Adpcm { bits: 6 }
+    )
+    .is_err());
+    assert!(encode_define_sound(1, &[0], ast::SoundType::Stereo, 22050, SoundEncoding::Adpcm { bits: 4 }).is_err());
+  }
+}
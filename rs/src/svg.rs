@@ -0,0 +1,2312 @@
+//! Import SVG documents as `DefineShape` tags.
+//!
+//! The importer supports the basic SVG shapes (`path`, `rect`, `circle`, `ellipse`, `line`, `polyline`, `polygon`)
+//! grouped with `g`, `svg` and `a` elements. Fills and strokes may use solid colors or linear and radial gradients.
+//! Presentation attributes and inline `style` declarations are supported; style sheets are ignored.
+//!
+//! Each painted element becomes a layer of the shape: it defines new styles and its subpaths are drawn after the
+//! subpaths of the previous elements, preserving the SVG painting order.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
+
+use swf_fixed::{Sfixed16P16, Sfixed8P8};
+use swf_types as ast;
+
+use crate::gradient::{resample_gradient, MAX_GRADIENT_STOPS};
+use crate::path::{Point, ShapeRecordsBuilder};
+use crate::shape::{get_min_shape_version, get_shape_bounds, get_shape_edge_bounds, ShapeVersion};
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+
+const TWIPS_PER_PIXEL: f64 = 20.0;
+
+/// Side of the gradient square, in twips. Gradients are defined on the square `[-16384, 16384]²`.
+const GRADIENT_SQUARE_HALF_SIZE: f64 = 16384.0;
+
+/// Maximum depth when following gradient `href` chains.
+const MAX_HREF_DEPTH: usize = 16;
+
+#[derive(Debug)]
+pub enum SvgError {
+  /// The input is not a well-formed XML document.
+  Xml(String),
+  /// The root element is not an `svg` element.
+  InvalidRoot,
+  /// An element is not supported by the importer.
+  UnsupportedElement(String),
+  /// An attribute or property has an invalid value.
+  InvalidAttribute { name: String, value: String },
+  /// A paint server reference (`url(#id)`) does not resolve to a gradient.
+  UnknownReference(String),
+  /// Self-overlapping fills use both the `nonzero` and `evenodd` fill rules, while SWF shapes use a single fill rule.
+  IncompatibleFillRules,
+}
+
+impl fmt::Display for SvgError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SvgError::Xml(ref message) => write!(f, "invalid XML: {}", message),
+      SvgError::InvalidRoot => f.write_str("root element is not an `svg` element"),
+      SvgError::UnsupportedElement(ref name) => write!(f, "unsupported SVG element: `{}`", name),
+      SvgError::InvalidAttribute { ref name, ref value } => {
+        write!(f, "invalid value for attribute `{}`: {:?}", name, value)
+      }
+      SvgError::UnknownReference(ref id) => write!(f, "unknown paint server reference: `#{}`", id),
+      SvgError::IncompatibleFillRules => {
+        f.write_str("overlapping fills use both the `nonzero` and `evenodd` fill rules")
+      }
+    }
+  }
+}
+
+impl Error for SvgError {}
+
+/// Converts an SVG document to a `DefineShape` tag with the provided character id.
+///
+/// Coordinates are converted from pixels to twips. The shape version is the minimal version supporting the
+/// resulting styles; edge bounds are only computed when `DefineShape4` is required.
+///
+/// The fill rule applies to the whole SWF shape: self-overlapping `nonzero` fills enable the fill winding flag, and
+/// documents where both `nonzero` and `evenodd` fills overlap themselves are rejected.
+pub fn svg_to_define_shape(id: u16, svg: &str) -> Result<ast::tags::DefineShape, SvgError> {
+  let document = roxmltree::Document::parse(svg).map_err(|e| SvgError::Xml(e.to_string()))?;
+  let root = document.root_element();
+  if !is_svg_element(root, "svg") {
+    return Err(SvgError::InvalidRoot);
+  }
+
+  let mut importer = SvgImporter::new(&document);
+  let (viewport_transform, viewport) = get_viewport_transform(root)?;
+  importer.viewport = viewport;
+  let root_style = Style::default().inherit(root, viewport)?;
+  let transform = viewport_transform.then(&get_transform(root)?);
+  importer.import_children(root, &root_style, &transform)?;
+
+  if importer.has_fill_winding && importer.has_even_odd_overlap {
+    return Err(SvgError::IncompatibleFillRules);
+  }
+  let has_fill_winding = importer.has_fill_winding;
+  let shape = importer.into_shape();
+  let version = if has_fill_winding {
+    ShapeVersion::Shape4
+  } else {
+    get_min_shape_version(&shape)
+  };
+  let (edge_bounds, has_non_scaling_strokes, has_scaling_strokes) = if version == ShapeVersion::Shape4 {
+    let (has_non_scaling_strokes, has_scaling_strokes) = get_stroke_scaling(&shape);
+    (
+      Some(get_shape_edge_bounds(&shape)),
+      has_non_scaling_strokes,
+      has_scaling_strokes,
+    )
+  } else {
+    (None, false, false)
+  };
+
+  Ok(ast::tags::DefineShape {
+    id,
+    bounds: get_shape_bounds(&shape),
+    edge_bounds,
+    has_fill_winding,
+    has_non_scaling_strokes,
+    has_scaling_strokes,
+    shape,
+  })
+}
+
+fn get_stroke_scaling(shape: &ast::Shape) -> (bool, bool) {
+  let mut has_non_scaling_strokes = false;
+  let mut has_scaling_strokes = false;
+  let new_styles = shape.records.iter().filter_map(|record| match record {
+    ast::ShapeRecord::StyleChange(ref record) => record.new_styles.as_ref(),
+    _ => None,
+  });
+  for styles in std::iter::once(&shape.initial_styles).chain(new_styles) {
+    for line in &styles.line {
+      if line.no_h_scale || line.no_v_scale {
+        has_non_scaling_strokes = true;
+      } else {
+        has_scaling_strokes = true;
+      }
+    }
+  }
+  (has_non_scaling_strokes, has_scaling_strokes)
+}
+
+fn is_svg_element(node: roxmltree::Node, name: &str) -> bool {
+  node.is_element()
+    && node.tag_name().name() == name
+    && matches!(node.tag_name().namespace(), None | Some(SVG_NAMESPACE))
+}
+
+struct SvgImporter<'a, 'input> {
+  nodes_by_id: HashMap<&'a str, roxmltree::Node<'a, 'input>>,
+  /// Viewport size, in user units, used to resolve percentages.
+  viewport: (f64, f64),
+  initial_styles: Option<ast::ShapeStyles>,
+  builder: ShapeRecordsBuilder,
+  has_fill_winding: bool,
+  /// Whether an `evenodd` fill overlaps itself, and would change with the nonzero fill rule.
+  has_even_odd_overlap: bool,
+}
+
+impl<'a, 'input> SvgImporter<'a, 'input> {
+  fn new(document: &'a roxmltree::Document<'input>) -> Self {
+    let nodes_by_id = document
+      .descendants()
+      .filter_map(|node| node.attribute("id").map(|id| (id, node)))
+      .collect();
+    Self {
+      nodes_by_id,
+      viewport: (100.0, 100.0),
+      initial_styles: None,
+      builder: ShapeRecordsBuilder::new(),
+      has_fill_winding: false,
+      has_even_odd_overlap: false,
+    }
+  }
+
+  fn into_shape(self) -> ast::Shape {
+    ast::Shape {
+      initial_styles: self.initial_styles.unwrap_or(ast::ShapeStyles {
+        fill: Vec::new(),
+        line: Vec::new(),
+      }),
+      records: self.builder.into_records(),
+    }
+  }
+
+  fn import_children(
+    &mut self,
+    parent: roxmltree::Node<'a, 'input>,
+    style: &Style,
+    transform: &Transform,
+  ) -> Result<(), SvgError> {
+    for child in parent.children().filter(|node| node.is_element()) {
+      self.import_element(child, style, transform)?;
+    }
+    Ok(())
+  }
+
+  fn import_element(
+    &mut self,
+    node: roxmltree::Node<'a, 'input>,
+    parent_style: &Style,
+    parent_transform: &Transform,
+  ) -> Result<(), SvgError> {
+    if !matches!(node.tag_name().namespace(), None | Some(SVG_NAMESPACE)) {
+      // Foreign elements (e.g. editor metadata) are ignored.
+      return Ok(());
+    }
+    let name = node.tag_name().name();
+    match name {
+      // Non-rendered elements
+      "defs" | "desc" | "linearGradient" | "metadata" | "radialGradient" | "style" | "title" | "clipPath" | "mask"
+      | "marker" | "pattern" | "symbol" => return Ok(()),
+      "g" | "a" | "svg" | "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => {}
+      _ => return Err(SvgError::UnsupportedElement(name.to_string())),
+    }
+    if get_property(node, "display") == Some("none") {
+      return Ok(());
+    }
+
+    let style = parent_style.inherit(node, self.viewport)?;
+    let transform = parent_transform.then(&get_transform(node)?);
+    match name {
+      "g" | "a" => self.import_children(node, &style, &transform),
+      "svg" => {
+        let x = self.get_length(node, "x", Axis::X)?.unwrap_or(0.0);
+        let y = self.get_length(node, "y", Axis::Y)?.unwrap_or(0.0);
+        let (viewport_transform, viewport) = get_viewport_transform(node)?;
+        let transform = transform.then(&Transform::translate(x, y)).then(&viewport_transform);
+        let parent_viewport = std::mem::replace(&mut self.viewport, viewport);
+        let result = self.import_children(node, &style, &transform);
+        self.viewport = parent_viewport;
+        result
+      }
+      _ => {
+        let segments = self.get_element_segments(node)?;
+        if style.visible {
+          self.draw(&segments, &style, &transform)?;
+        }
+        Ok(())
+      }
+    }
+  }
+
+  fn get_element_segments(&self, node: roxmltree::Node) -> Result<Vec<Segment>, SvgError> {
+    let segments = match node.tag_name().name() {
+      "path" => match node.attribute("d") {
+        Some(d) => parse_path_data(d),
+        None => Vec::new(),
+      },
+      "rect" => {
+        let x = self.get_length(node, "x", Axis::X)?.unwrap_or(0.0);
+        let y = self.get_length(node, "y", Axis::Y)?.unwrap_or(0.0);
+        let width = self.get_length(node, "width", Axis::X)?.unwrap_or(0.0);
+        let height = self.get_length(node, "height", Axis::Y)?.unwrap_or(0.0);
+        let rx = self.get_length(node, "rx", Axis::X)?;
+        let ry = self.get_length(node, "ry", Axis::Y)?;
+        let (rx, ry) = match (rx, ry) {
+          (Some(rx), Some(ry)) => (rx, ry),
+          (Some(r), None) | (None, Some(r)) => (r, r),
+          (None, None) => (0.0, 0.0),
+        };
+        get_rect_segments(x, y, width, height, rx.min(width / 2.0), ry.min(height / 2.0))
+      }
+      "circle" => {
+        let cx = self.get_length(node, "cx", Axis::X)?.unwrap_or(0.0);
+        let cy = self.get_length(node, "cy", Axis::Y)?.unwrap_or(0.0);
+        let r = self.get_length(node, "r", Axis::Other)?.unwrap_or(0.0);
+        get_ellipse_segments(cx, cy, r, r)
+      }
+      "ellipse" => {
+        let cx = self.get_length(node, "cx", Axis::X)?.unwrap_or(0.0);
+        let cy = self.get_length(node, "cy", Axis::Y)?.unwrap_or(0.0);
+        let rx = self.get_length(node, "rx", Axis::X)?.unwrap_or(0.0);
+        let ry = self.get_length(node, "ry", Axis::Y)?.unwrap_or(0.0);
+        get_ellipse_segments(cx, cy, rx, ry)
+      }
+      "line" => {
+        let x1 = self.get_length(node, "x1", Axis::X)?.unwrap_or(0.0);
+        let y1 = self.get_length(node, "y1", Axis::Y)?.unwrap_or(0.0);
+        let x2 = self.get_length(node, "x2", Axis::X)?.unwrap_or(0.0);
+        let y2 = self.get_length(node, "y2", Axis::Y)?.unwrap_or(0.0);
+        vec![Segment::MoveTo(Point::new(x1, y1)), Segment::LineTo(Point::new(x2, y2))]
+      }
+      "polyline" | "polygon" => {
+        let points = node.attribute("points").unwrap_or("");
+        let numbers = parse_number_list(points).ok_or_else(|| invalid_attribute("points", points))?;
+        let mut segments: Vec<Segment> = numbers
+          .chunks_exact(2)
+          .enumerate()
+          .map(|(i, pair)| {
+            let p = Point::new(pair[0], pair[1]);
+            if i == 0 {
+              Segment::MoveTo(p)
+            } else {
+              Segment::LineTo(p)
+            }
+          })
+          .collect();
+        if node.tag_name().name() == "polygon" && !segments.is_empty() {
+          segments.push(Segment::Close);
+        }
+        segments
+      }
+      _ => Vec::new(),
+    };
+    Ok(segments)
+  }
+
+  fn get_length(&self, node: roxmltree::Node, name: &str, axis: Axis) -> Result<Option<f64>, SvgError> {
+    let value = match node.attribute(name) {
+      Some(value) => value,
+      None => return Ok(None),
+    };
+    let length = parse_length(value).ok_or_else(|| invalid_attribute(name, value))?;
+    Ok(Some(length.resolve(self.viewport, axis)))
+  }
+
+  /// Appends the subpaths of an element as a new layer.
+  fn draw(&mut self, segments: &[Segment], style: &Style, transform: &Transform) -> Result<(), SvgError> {
+    let bbox = match get_segments_bbox(segments) {
+      Some(bbox) => bbox,
+      None => return Ok(()),
+    };
+    let transform = Transform::scale(TWIPS_PER_PIXEL, TWIPS_PER_PIXEL).then(transform);
+
+    let fill = match style.fill {
+      Some(ref paint) => self.get_fill_style(paint, style.fill_opacity * style.opacity, &bbox, &transform)?,
+      None => None,
+    };
+    let line = match style.stroke {
+      Some(ref paint) if style.stroke_width > 0.0 => {
+        let fill = self.get_fill_style(paint, style.stroke_opacity * style.opacity, &bbox, &transform)?;
+        fill.map(|fill| get_line_style(style, fill, &transform))
+      }
+      _ => None,
+    };
+    if fill.is_none() && line.is_none() {
+      return Ok(());
+    }
+
+    // The even-odd and nonzero rules only differ where the path overlaps itself.
+    if fill.is_some() && is_self_overlapping(segments) {
+      match style.fill_rule {
+        FillRule::NonZero => self.has_fill_winding = true,
+        FillRule::EvenOdd => self.has_even_odd_overlap = true,
+      }
+    }
+
+    let fill_index: usize = if fill.is_some() { 1 } else { 0 };
+    let line_index: usize = if line.is_some() { 1 } else { 0 };
+    let styles = ast::ShapeStyles {
+      fill: fill.into_iter().collect(),
+      line: line.into_iter().collect(),
+    };
+    if self.initial_styles.is_none() {
+      self.initial_styles = Some(styles);
+    } else {
+      self.builder.style_change_mut().new_styles = Some(styles);
+    }
+
+    let mut is_first_subpath = true;
+    let mut is_line_selected = false;
+    for segment in segments {
+      match *segment {
+        Segment::MoveTo(p) => {
+          if !is_first_subpath {
+            self.close_fill(fill_index, line_index, &mut is_line_selected);
+          }
+          let p = transform.apply(p);
+          self.builder.move_to(p);
+          let record = self.builder.style_change_mut();
+          if is_first_subpath {
+            record.left_fill = Some(fill_index);
+            record.right_fill = Some(0);
+          }
+          if !is_line_selected {
+            record.line_style = Some(line_index);
+            is_line_selected = true;
+          }
+          is_first_subpath = false;
+        }
+        Segment::LineTo(p) => self.builder.line_to(transform.apply(p)),
+        Segment::QuadTo(c, p) => self.builder.quad_to(transform.apply(c), transform.apply(p)),
+        Segment::CubicTo(c1, c2, p) => {
+          self
+            .builder
+            .cubic_to(transform.apply(c1), transform.apply(c2), transform.apply(p))
+        }
+        Segment::Close => self.builder.close(),
+      }
+    }
+    self.close_fill(fill_index, line_index, &mut is_line_selected);
+    Ok(())
+  }
+
+  /// Closes the current subpath for filling, without stroking the closing edge.
+  fn close_fill(&mut self, fill_index: usize, line_index: usize, is_line_selected: &mut bool) {
+    if fill_index == 0 || self.builder.is_closed() {
+      return;
+    }
+    if line_index != 0 {
+      self.builder.style_change_mut().line_style = Some(0);
+      *is_line_selected = false;
+    }
+    self.builder.close();
+  }
+
+  fn get_fill_style(
+    &self,
+    paint: &Paint,
+    opacity: f64,
+    bbox: &BoundingBox,
+    transform: &Transform,
+  ) -> Result<Option<ast::FillStyle>, SvgError> {
+    match paint {
+      Paint::Color(color) => Ok(Some(ast::FillStyle::Solid(ast::fill_styles::Solid {
+        color: color.to_rgba(opacity),
+      }))),
+      Paint::Url(ref id) => {
+        let node = match self.nodes_by_id.get(id.as_str()) {
+          Some(node) if is_svg_element(*node, "linearGradient") || is_svg_element(*node, "radialGradient") => *node,
+          _ => return Err(SvgError::UnknownReference(id.clone())),
+        };
+        self.get_gradient_fill_style(node, opacity, bbox, transform)
+      }
+    }
+  }
+
+  /// Resolves a gradient attribute, following `href` references.
+  fn get_gradient_attribute(&self, node: roxmltree::Node<'a, 'input>, name: &str) -> Option<&'a str> {
+    let mut current = node;
+    for _ in 0..MAX_HREF_DEPTH {
+      if let Some(value) = current.attribute(name) {
+        return Some(value);
+      }
+      current = self.get_href_target(current)?;
+    }
+    None
+  }
+
+  fn get_gradient_stops(&self, node: roxmltree::Node<'a, 'input>) -> Vec<roxmltree::Node<'a, 'input>> {
+    let mut current = node;
+    for _ in 0..MAX_HREF_DEPTH {
+      let stops: Vec<_> = current.children().filter(|n| is_svg_element(*n, "stop")).collect();
+      if !stops.is_empty() {
+        return stops;
+      }
+      current = match self.get_href_target(current) {
+        Some(target) => target,
+        None => break,
+      };
+    }
+    Vec::new()
+  }
+
+  fn get_href_target(&self, node: roxmltree::Node<'a, 'input>) -> Option<roxmltree::Node<'a, 'input>> {
+    let href = node
+      .attribute((XLINK_NAMESPACE, "href"))
+      .or_else(|| node.attribute("href"))?;
+    let id = href.trim().strip_prefix('#')?;
+    let target = *self.nodes_by_id.get(id)?;
+    if is_svg_element(target, "linearGradient") || is_svg_element(target, "radialGradient") {
+      Some(target)
+    } else {
+      None
+    }
+  }
+
+  fn get_gradient_fill_style(
+    &self,
+    node: roxmltree::Node<'a, 'input>,
+    opacity: f64,
+    bbox: &BoundingBox,
+    transform: &Transform,
+  ) -> Result<Option<ast::FillStyle>, SvgError> {
+    let stops = self.get_gradient_stops(node);
+    let colors = get_color_stops(&stops, opacity)?;
+    match colors.len() {
+      0 => return Ok(None),
+      1 => return Ok(Some(solid_fill(colors[0].color))),
+      _ => {}
+    }
+
+    let is_object_bbox = match self.get_gradient_attribute(node, "gradientUnits") {
+      None | Some("objectBoundingBox") => true,
+      Some("userSpaceOnUse") => false,
+      Some(value) => return Err(invalid_attribute("gradientUnits", value)),
+    };
+    let units_transform = if is_object_bbox {
+      if bbox.width() == 0.0 || bbox.height() == 0.0 {
+        // Bounding box units are undefined for empty boxes: the element is not painted.
+        return Ok(None);
+      }
+      Transform::new(bbox.width(), 0.0, 0.0, bbox.height(), bbox.x_min, bbox.y_min)
+    } else {
+      Transform::identity()
+    };
+    let gradient_transform = match self.get_gradient_attribute(node, "gradientTransform") {
+      Some(value) => parse_transform(value).ok_or_else(|| invalid_attribute("gradientTransform", value))?,
+      None => Transform::identity(),
+    };
+    let spread = match self.get_gradient_attribute(node, "spreadMethod") {
+      None | Some("pad") => ast::GradientSpread::Pad,
+      Some("reflect") => ast::GradientSpread::Reflect,
+      Some("repeat") => ast::GradientSpread::Repeat,
+      Some(value) => return Err(invalid_attribute("spreadMethod", value)),
+    };
+    let color_space = match get_property(node, "color-interpolation") {
+      Some("linearRGB") => ast::ColorSpace::LinearRgb,
+      _ => ast::ColorSpace::SRgb,
+    };
+    let gradient_coord = |name: &str, default: f64, axis: Axis| -> Result<f64, SvgError> {
+      match self.get_gradient_attribute(node, name) {
+        Some(value) => {
+          let length = parse_length(value).ok_or_else(|| invalid_attribute(name, value))?;
+          Ok(if is_object_bbox {
+            length.resolve_fraction()
+          } else {
+            length.resolve(self.viewport, axis)
+          })
+        }
+        None => Ok(if is_object_bbox {
+          default
+        } else {
+          default * Length::percentage_base(self.viewport, axis)
+        }),
+      }
+    };
+    let to_gradient = transform.then(&units_transform).then(&gradient_transform);
+    let last_color = colors[colors.len() - 1].color;
+    // Gradients with more stops than supported by `DefineShape4` are approximated.
+    let gradient = resample_gradient(
+      &ast::Gradient {
+        spread,
+        color_space,
+        colors,
+      },
+      MAX_GRADIENT_STOPS,
+    );
+
+    if node.tag_name().name() == "linearGradient" {
+      let x1 = gradient_coord("x1", 0.0, Axis::X)?;
+      let y1 = gradient_coord("y1", 0.0, Axis::Y)?;
+      let x2 = gradient_coord("x2", 1.0, Axis::X)?;
+      let y2 = gradient_coord("y2", 0.0, Axis::Y)?;
+      let (dx, dy) = (x2 - x1, y2 - y1);
+      if dx == 0.0 && dy == 0.0 {
+        return Ok(Some(solid_fill(last_color)));
+      }
+      // Map the gradient square to the vector `(x1, y1) -> (x2, y2)`: the x axis follows the vector and the
+      // y axis is perpendicular to it.
+      let square = Transform::new(dx, dy, -dy, dx, (x1 + x2) / 2.0, (y1 + y2) / 2.0).then(&Transform::scale(
+        0.5 / GRADIENT_SQUARE_HALF_SIZE,
+        0.5 / GRADIENT_SQUARE_HALF_SIZE,
+      ));
+      Ok(Some(ast::FillStyle::LinearGradient(ast::fill_styles::LinearGradient {
+        matrix: to_gradient.then(&square).to_matrix(),
+        gradient,
+      })))
+    } else {
+      let cx = gradient_coord("cx", 0.5, Axis::X)?;
+      let cy = gradient_coord("cy", 0.5, Axis::Y)?;
+      let r = gradient_coord("r", 0.5, Axis::Other)?;
+      let fx = match self.get_gradient_attribute(node, "fx") {
+        Some(_) => gradient_coord("fx", 0.5, Axis::X)?,
+        None => cx,
+      };
+      let fy = match self.get_gradient_attribute(node, "fy") {
+        Some(_) => gradient_coord("fy", 0.5, Axis::Y)?,
+        None => cy,
+      };
+      if r <= 0.0 {
+        return Ok(Some(solid_fill(last_color)));
+      }
+      let square = Transform::new(r, 0.0, 0.0, r, cx, cy).then(&Transform::scale(
+        1.0 / GRADIENT_SQUARE_HALF_SIZE,
+        1.0 / GRADIENT_SQUARE_HALF_SIZE,
+      ));
+      let matrix = to_gradient.then(&square).to_matrix();
+      if fx == cx && fy == cy {
+        return Ok(Some(ast::FillStyle::RadialGradient(ast::fill_styles::RadialGradient {
+          matrix,
+          gradient,
+        })));
+      }
+      // SWF focal gradients only support focal points on the x axis of the gradient: rotate the gradient so the
+      // focal point lies on it.
+      let (fdx, fdy) = (fx - cx, fy - cy);
+      let distance = (fdx * fdx + fdy * fdy).sqrt();
+      let focal_point = (distance / r).min(1.0);
+      let angle = fdy.atan2(fdx);
+      let rotated = Transform::translate(cx, cy)
+        .then(&Transform::rotate(angle))
+        .then(&Transform::scale(
+          r / GRADIENT_SQUARE_HALF_SIZE,
+          r / GRADIENT_SQUARE_HALF_SIZE,
+        ));
+      Ok(Some(ast::FillStyle::FocalGradient(ast::fill_styles::FocalGradient {
+        matrix: to_gradient.then(&rotated).to_matrix(),
+        gradient,
+        focal_point: Sfixed8P8::from_epsilons((focal_point * 256.0).round() as i16),
+      })))
+    }
+  }
+}
+
+fn solid_fill(color: ast::StraightSRgba8) -> ast::FillStyle {
+  ast::FillStyle::Solid(ast::fill_styles::Solid { color })
+}
+
+fn get_color_stops(stops: &[roxmltree::Node], opacity: f64) -> Result<Vec<ast::ColorStop>, SvgError> {
+  let mut colors: Vec<ast::ColorStop> = Vec::with_capacity(stops.len());
+  let mut last_offset: f64 = 0.0;
+  for stop in stops {
+    let offset = match stop.attribute("offset") {
+      Some(value) => parse_length(value)
+        .ok_or_else(|| invalid_attribute("offset", value))?
+        .resolve_fraction(),
+      None => 0.0,
+    };
+    // Offsets are clamped to `[0, 1]` and must be monotonic.
+    let offset = offset.clamp(0.0, 1.0).max(last_offset);
+    last_offset = offset;
+    let color = match get_property(*stop, "stop-color") {
+      None => Color::BLACK,
+      Some("currentColor") => match get_property(*stop, "color") {
+        None | Some("inherit") => Color::BLACK,
+        Some(value) => parse_color(value).ok_or_else(|| invalid_attribute("color", value))?,
+      },
+      Some(value) => parse_color(value).ok_or_else(|| invalid_attribute("stop-color", value))?,
+    };
+    let stop_opacity = match get_property(*stop, "stop-opacity") {
+      Some(value) => parse_opacity(value).ok_or_else(|| invalid_attribute("stop-opacity", value))?,
+      None => 1.0,
+    };
+    colors.push(ast::ColorStop {
+      ratio: (offset * 255.0).round() as u8,
+      color: color.to_rgba(stop_opacity * opacity),
+    });
+  }
+  Ok(colors)
+}
+
+fn get_line_style(style: &Style, fill: ast::FillStyle, transform: &Transform) -> ast::LineStyle {
+  let width = (style.stroke_width * transform.scale_factor()).round();
+  let width = if width < 1.0 {
+    1
+  } else {
+    width.min(f64::from(u16::MAX)) as u16
+  };
+  let cap = match style.stroke_linecap {
+    LineCap::Butt => ast::CapStyle::None,
+    LineCap::Round => ast::CapStyle::Round,
+    LineCap::Square => ast::CapStyle::Square,
+  };
+  let join = match style.stroke_linejoin {
+    LineJoin::Miter => ast::JoinStyle::Miter(ast::join_styles::Miter {
+      limit: (style.stroke_miterlimit * 256.0).round().min(f64::from(u16::MAX)) as u16,
+    }),
+    LineJoin::Round => ast::JoinStyle::Round,
+    LineJoin::Bevel => ast::JoinStyle::Bevel,
+  };
+  ast::LineStyle {
+    width,
+    fill,
+    pixel_hinting: false,
+    no_v_scale: style.non_scaling_stroke,
+    no_h_scale: style.non_scaling_stroke,
+    no_close: false,
+    join,
+    start_cap: cap,
+    end_cap: cap,
+  }
+}
+
+fn invalid_attribute(name: &str, value: &str) -> SvgError {
+  SvgError::InvalidAttribute {
+    name: name.to_string(),
+    value: value.to_string(),
+  }
+}
+
+/// Returns the value of a property, from the `style` attribute or the corresponding presentation attribute.
+fn get_property<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+  if let Some(style) = node.attribute("style") {
+    let declaration = style
+      .split(';')
+      .filter_map(|declaration| {
+        let mut parts = declaration.splitn(2, ':');
+        let key = parts.next()?.trim();
+        let value = parts.next()?.trim();
+        Some((key, value))
+      })
+      .rfind(|(key, _)| *key == name);
+    if let Some((_, value)) = declaration {
+      return Some(value.trim_end_matches("!important").trim_end());
+    }
+  }
+  node.attribute(name).map(|value| value.trim())
+}
+
+// Styles
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Color {
+  r: u8,
+  g: u8,
+  b: u8,
+  a: f64,
+}
+
+impl Color {
+  const BLACK: Self = Self {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 1.0,
+  };
+
+  fn to_rgba(self, opacity: f64) -> ast::StraightSRgba8 {
+    ast::StraightSRgba8 {
+      r: self.r,
+      g: self.g,
+      b: self.b,
+      a: (self.a * opacity * 255.0).round().clamp(0.0, 255.0) as u8,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Paint {
+  Color(Color),
+  Url(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum FillRule {
+  NonZero,
+  EvenOdd,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum LineCap {
+  Butt,
+  Round,
+  Square,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum LineJoin {
+  Miter,
+  Round,
+  Bevel,
+}
+
+/// Computed style of an element.
+#[derive(Clone, Debug)]
+struct Style {
+  color: Color,
+  fill: Option<Paint>,
+  fill_opacity: f64,
+  fill_rule: FillRule,
+  stroke: Option<Paint>,
+  stroke_width: f64,
+  stroke_opacity: f64,
+  stroke_linecap: LineCap,
+  stroke_linejoin: LineJoin,
+  stroke_miterlimit: f64,
+  visible: bool,
+  /// Product of the `opacity` of the element and its ancestors.
+  opacity: f64,
+  non_scaling_stroke: bool,
+}
+
+impl Default for Style {
+  fn default() -> Self {
+    Self {
+      color: Color::BLACK,
+      fill: Some(Paint::Color(Color::BLACK)),
+      fill_opacity: 1.0,
+      fill_rule: FillRule::NonZero,
+      stroke: None,
+      stroke_width: 1.0,
+      stroke_opacity: 1.0,
+      stroke_linecap: LineCap::Butt,
+      stroke_linejoin: LineJoin::Miter,
+      stroke_miterlimit: 4.0,
+      visible: true,
+      opacity: 1.0,
+      non_scaling_stroke: false,
+    }
+  }
+}
+
+impl Style {
+  /// Computes the style of `node`, inheriting from `self`.
+  ///
+  /// Percentage stroke widths are resolved against the `viewport` size.
+  fn inherit(&self, node: roxmltree::Node, viewport: (f64, f64)) -> Result<Style, SvgError> {
+    let mut style = self.clone();
+    style.non_scaling_stroke = false;
+
+    if let Some(value) = get_property(node, "color") {
+      if value != "inherit" {
+        style.color = parse_color(value).ok_or_else(|| invalid_attribute("color", value))?;
+      }
+    }
+    match get_property(node, "fill") {
+      None | Some("inherit") => {}
+      Some(value) => style.fill = parse_paint(value, &style).ok_or_else(|| invalid_attribute("fill", value))?,
+    }
+    match get_property(node, "stroke") {
+      None | Some("inherit") => {}
+      Some(value) => style.stroke = parse_paint(value, &style).ok_or_else(|| invalid_attribute("stroke", value))?,
+    }
+    if let Some(value) = get_property(node, "fill-opacity") {
+      style.fill_opacity = parse_opacity(value).ok_or_else(|| invalid_attribute("fill-opacity", value))?;
+    }
+    if let Some(value) = get_property(node, "stroke-opacity") {
+      style.stroke_opacity = parse_opacity(value).ok_or_else(|| invalid_attribute("stroke-opacity", value))?;
+    }
+    if let Some(value) = get_property(node, "opacity") {
+      style.opacity *= parse_opacity(value).ok_or_else(|| invalid_attribute("opacity", value))?;
+    }
+    match get_property(node, "fill-rule") {
+      None | Some("inherit") => {}
+      Some("nonzero") => style.fill_rule = FillRule::NonZero,
+      Some("evenodd") => style.fill_rule = FillRule::EvenOdd,
+      Some(value) => return Err(invalid_attribute("fill-rule", value)),
+    }
+    if let Some(value) = get_property(node, "stroke-width") {
+      let length = parse_length(value).ok_or_else(|| invalid_attribute("stroke-width", value))?;
+      style.stroke_width = length.resolve(viewport, Axis::Other);
+    }
+    match get_property(node, "stroke-linecap") {
+      None | Some("inherit") => {}
+      Some("butt") => style.stroke_linecap = LineCap::Butt,
+      Some("round") => style.stroke_linecap = LineCap::Round,
+      Some("square") => style.stroke_linecap = LineCap::Square,
+      Some(value) => return Err(invalid_attribute("stroke-linecap", value)),
+    }
+    match get_property(node, "stroke-linejoin") {
+      None | Some("inherit") => {}
+      Some("miter") | Some("miter-clip") | Some("arcs") => style.stroke_linejoin = LineJoin::Miter,
+      Some("round") => style.stroke_linejoin = LineJoin::Round,
+      Some("bevel") => style.stroke_linejoin = LineJoin::Bevel,
+      Some(value) => return Err(invalid_attribute("stroke-linejoin", value)),
+    }
+    if let Some(value) = get_property(node, "stroke-miterlimit") {
+      style.stroke_miterlimit = parse_number(value)
+        .filter(|limit| *limit >= 1.0)
+        .ok_or_else(|| invalid_attribute("stroke-miterlimit", value))?;
+    }
+    match get_property(node, "visibility") {
+      None | Some("inherit") => {}
+      Some("visible") => style.visible = true,
+      Some("hidden") | Some("collapse") => style.visible = false,
+      Some(value) => return Err(invalid_attribute("visibility", value)),
+    }
+    if get_property(node, "vector-effect") == Some("non-scaling-stroke") {
+      style.non_scaling_stroke = true;
+    }
+    Ok(style)
+  }
+}
+
+/// Parses a paint value, `None` stands for `none`.
+fn parse_paint(value: &str, style: &Style) -> Option<Option<Paint>> {
+  match value {
+    "none" => return Some(None),
+    "currentColor" => return Some(Some(Paint::Color(style.color))),
+    _ => {}
+  }
+  if let Some(rest) = value.strip_prefix("url(") {
+    let end = rest.find(')')?;
+    let reference = rest[..end].trim().trim_matches(|c| c == '"' || c == '\'');
+    let id = reference.strip_prefix('#')?;
+    return Some(Some(Paint::Url(id.to_string())));
+  }
+  parse_color(value).map(|color| Some(Paint::Color(color)))
+}
+
+fn parse_opacity(value: &str) -> Option<f64> {
+  let length = parse_length(value)?;
+  let opacity = match length.unit {
+    LengthUnit::Percent => length.value / 100.0,
+    LengthUnit::None => length.value,
+    _ => return None,
+  };
+  Some(opacity.clamp(0.0, 1.0))
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+  let value = value.trim();
+  if let Some(hex) = value.strip_prefix('#') {
+    let digits: Vec<u8> = hex
+      .chars()
+      .map(|c| c.to_digit(16).map(|d| d as u8))
+      .collect::<Option<Vec<u8>>>()?;
+    let (r, g, b, a) = match digits.len() {
+      3 => (digits[0] * 17, digits[1] * 17, digits[2] * 17, 255),
+      4 => (digits[0] * 17, digits[1] * 17, digits[2] * 17, digits[3] * 17),
+      6 => (
+        digits[0] * 16 + digits[1],
+        digits[2] * 16 + digits[3],
+        digits[4] * 16 + digits[5],
+        255,
+      ),
+      8 => (
+        digits[0] * 16 + digits[1],
+        digits[2] * 16 + digits[3],
+        digits[4] * 16 + digits[5],
+        digits[6] * 16 + digits[7],
+      ),
+      _ => return None,
+    };
+    return Some(Color {
+      r,
+      g,
+      b,
+      a: f64::from(a) / 255.0,
+    });
+  }
+  let lower = value.to_ascii_lowercase();
+  for prefix in &["rgba(", "rgb("] {
+    if let Some(rest) = lower.strip_prefix(prefix) {
+      let args = rest.strip_suffix(')')?;
+      let args: Vec<&str> = args
+        .split(|c: char| c == ',' || c == '/' || c.is_ascii_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .collect();
+      if args.len() != 3 && args.len() != 4 {
+        return None;
+      }
+      let mut channels = [0u8; 3];
+      for (channel, arg) in channels.iter_mut().zip(args.iter()) {
+        let length = parse_length(arg)?;
+        let value = match length.unit {
+          LengthUnit::Percent => length.value * 255.0 / 100.0,
+          LengthUnit::None => length.value,
+          _ => return None,
+        };
+        *channel = value.round().clamp(0.0, 255.0) as u8;
+      }
+      let a = match args.get(3) {
+        Some(arg) => parse_opacity(arg)?,
+        None => 1.0,
+      };
+      return Some(Color {
+        r: channels[0],
+        g: channels[1],
+        b: channels[2],
+        a,
+      });
+    }
+  }
+  if lower == "transparent" {
+    return Some(Color {
+      r: 0,
+      g: 0,
+      b: 0,
+      a: 0.0,
+    });
+  }
+  let index = NAMED_COLORS
+    .binary_search_by(|(name, _)| (*name).cmp(lower.as_str()))
+    .ok()?;
+  let rgb = NAMED_COLORS[index].1;
+  Some(Color {
+    r: (rgb >> 16) as u8,
+    g: (rgb >> 8) as u8,
+    b: rgb as u8,
+    a: 1.0,
+  })
+}
+
+// Lengths
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum LengthUnit {
+  None,
+  Px,
+  Pt,
+  Pc,
+  Mm,
+  Cm,
+  In,
+  Em,
+  Ex,
+  Percent,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Length {
+  value: f64,
+  unit: LengthUnit,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Axis {
+  X,
+  Y,
+  /// Lengths which are neither horizontal nor vertical (e.g. radii): percentages use the normalized diagonal.
+  Other,
+}
+
+impl Length {
+  /// Resolves the length to user units.
+  fn resolve(self, viewport: (f64, f64), axis: Axis) -> f64 {
+    match self.unit {
+      LengthUnit::None | LengthUnit::Px => self.value,
+      LengthUnit::Pt => self.value * 4.0 / 3.0,
+      LengthUnit::Pc => self.value * 16.0,
+      LengthUnit::Mm => self.value * 96.0 / 25.4,
+      LengthUnit::Cm => self.value * 96.0 / 2.54,
+      LengthUnit::In => self.value * 96.0,
+      // Font sizes are not supported: use the default font size of 16px.
+      LengthUnit::Em => self.value * 16.0,
+      LengthUnit::Ex => self.value * 8.0,
+      LengthUnit::Percent => self.value / 100.0 * Length::percentage_base(viewport, axis),
+    }
+  }
+
+  /// Resolves the length as a fraction, where percentages are divided by 100.
+  fn resolve_fraction(self) -> f64 {
+    match self.unit {
+      LengthUnit::Percent => self.value / 100.0,
+      _ => self.value,
+    }
+  }
+
+  fn percentage_base(viewport: (f64, f64), axis: Axis) -> f64 {
+    let (width, height) = viewport;
+    match axis {
+      Axis::X => width,
+      Axis::Y => height,
+      Axis::Other => ((width * width + height * height) / 2.0).sqrt(),
+    }
+  }
+}
+
+fn parse_length(value: &str) -> Option<Length> {
+  let mut parser = NumberParser::new(value.trim());
+  let number = parser.number()?;
+  let unit = match parser.rest() {
+    "" => LengthUnit::None,
+    "px" => LengthUnit::Px,
+    "pt" => LengthUnit::Pt,
+    "pc" => LengthUnit::Pc,
+    "mm" => LengthUnit::Mm,
+    "cm" => LengthUnit::Cm,
+    "in" => LengthUnit::In,
+    "em" => LengthUnit::Em,
+    "ex" => LengthUnit::Ex,
+    "%" => LengthUnit::Percent,
+    _ => return None,
+  };
+  Some(Length { value: number, unit })
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+  let mut parser = NumberParser::new(value.trim());
+  let number = parser.number()?;
+  if parser.is_end() {
+    Some(number)
+  } else {
+    None
+  }
+}
+
+fn parse_number_list(value: &str) -> Option<Vec<f64>> {
+  let mut parser = NumberParser::new(value);
+  let mut numbers = Vec::new();
+  parser.skip_separators();
+  while !parser.is_end() {
+    numbers.push(parser.number()?);
+    parser.skip_separators();
+  }
+  Some(numbers)
+}
+
+/// Parser for SVG number lists, shared by lengths, transforms and path data.
+struct NumberParser<'a> {
+  input: &'a str,
+  position: usize,
+}
+
+impl<'a> NumberParser<'a> {
+  fn new(input: &'a str) -> Self {
+    Self { input, position: 0 }
+  }
+
+  fn is_end(&self) -> bool {
+    self.position >= self.input.len()
+  }
+
+  fn rest(&self) -> &'a str {
+    &self.input[self.position..]
+  }
+
+  fn peek(&self) -> Option<u8> {
+    self.input.as_bytes().get(self.position).copied()
+  }
+
+  fn skip_whitespace(&mut self) {
+    while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r' | b'\x0c')) {
+      self.position += 1;
+    }
+  }
+
+  /// Skips whitespace and at most one comma.
+  fn skip_separators(&mut self) {
+    self.skip_whitespace();
+    if self.peek() == Some(b',') {
+      self.position += 1;
+      self.skip_whitespace();
+    }
+  }
+
+  fn number(&mut self) -> Option<f64> {
+    let bytes = self.input.as_bytes();
+    let start = self.position;
+    let mut end = start;
+    if matches!(bytes.get(end), Some(b'+' | b'-')) {
+      end += 1;
+    }
+    let integer_start = end;
+    while matches!(bytes.get(end), Some(b'0'..=b'9')) {
+      end += 1;
+    }
+    let mut has_digits = end > integer_start;
+    if bytes.get(end) == Some(&b'.') {
+      let fraction_start = end + 1;
+      let mut fraction_end = fraction_start;
+      while matches!(bytes.get(fraction_end), Some(b'0'..=b'9')) {
+        fraction_end += 1;
+      }
+      if fraction_end > fraction_start {
+        has_digits = true;
+        end = fraction_end;
+      } else if has_digits {
+        end = fraction_start;
+      }
+    }
+    if !has_digits {
+      return None;
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+      let mut exponent_end = end + 1;
+      if matches!(bytes.get(exponent_end), Some(b'+' | b'-')) {
+        exponent_end += 1;
+      }
+      let exponent_start = exponent_end;
+      while matches!(bytes.get(exponent_end), Some(b'0'..=b'9')) {
+        exponent_end += 1;
+      }
+      // `1em` is a length, not an exponent.
+      if exponent_end > exponent_start {
+        end = exponent_end;
+      }
+    }
+    self.position = end;
+    self.input[start..end].parse().ok()
+  }
+
+  /// Parses an arc flag: flags are single characters and may be immediately followed by a number.
+  fn flag(&mut self) -> Option<bool> {
+    let flag = match self.peek()? {
+      b'0' => false,
+      b'1' => true,
+      _ => return None,
+    };
+    self.position += 1;
+    Some(flag)
+  }
+}
+
+// Transforms
+
+/// Affine transform `[a c e; b d f]`, mapping `(x, y)` to `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Transform {
+  a: f64,
+  b: f64,
+  c: f64,
+  d: f64,
+  e: f64,
+  f: f64,
+}
+
+impl Transform {
+  fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+    Self { a, b, c, d, e, f }
+  }
+
+  fn identity() -> Self {
+    Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+  }
+
+  fn translate(x: f64, y: f64) -> Self {
+    Self::new(1.0, 0.0, 0.0, 1.0, x, y)
+  }
+
+  fn scale(x: f64, y: f64) -> Self {
+    Self::new(x, 0.0, 0.0, y, 0.0, 0.0)
+  }
+
+  fn rotate(angle: f64) -> Self {
+    let (sin, cos) = angle.sin_cos();
+    Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+  }
+
+  /// Returns the transform applying `other` first, then `self`.
+  fn then(&self, other: &Transform) -> Transform {
+    Transform {
+      a: self.a * other.a + self.c * other.b,
+      b: self.b * other.a + self.d * other.b,
+      c: self.a * other.c + self.c * other.d,
+      d: self.b * other.c + self.d * other.d,
+      e: self.a * other.e + self.c * other.f + self.e,
+      f: self.b * other.e + self.d * other.f + self.f,
+    }
+  }
+
+  fn apply(&self, p: Point) -> Point {
+    Point::new(
+      self.a * p.x + self.c * p.y + self.e,
+      self.b * p.x + self.d * p.y + self.f,
+    )
+  }
+
+  /// Returns the factor applied to lengths (e.g. stroke widths).
+  fn scale_factor(&self) -> f64 {
+    (self.a * self.d - self.b * self.c).abs().sqrt()
+  }
+
+  fn to_matrix(self) -> ast::Matrix {
+    let to_fixed = |value: f64| {
+      let epsilons = (value * 65536.0)
+        .round()
+        .clamp(f64::from(i32::MIN), f64::from(i32::MAX));
+      Sfixed16P16::from_epsilons(epsilons as i32)
+    };
+    ast::Matrix {
+      scale_x: to_fixed(self.a),
+      scale_y: to_fixed(self.d),
+      rotate_skew0: to_fixed(self.b),
+      rotate_skew1: to_fixed(self.c),
+      translate_x: self.e.round() as i32,
+      translate_y: self.f.round() as i32,
+    }
+  }
+}
+
+fn get_transform(node: roxmltree::Node) -> Result<Transform, SvgError> {
+  match node.attribute("transform") {
+    Some(value) => parse_transform(value).ok_or_else(|| invalid_attribute("transform", value)),
+    None => Ok(Transform::identity()),
+  }
+}
+
+fn parse_transform(value: &str) -> Option<Transform> {
+  let mut transform = Transform::identity();
+  let mut rest = value.trim();
+  while !rest.is_empty() {
+    let open = rest.find('(')?;
+    let close = rest.find(')')?;
+    if close < open {
+      return None;
+    }
+    let name = rest[..open].trim();
+    let args = parse_number_list(&rest[open + 1..close])?;
+    let item = match (name, args.as_slice()) {
+      ("matrix", &[a, b, c, d, e, f]) => Transform::new(a, b, c, d, e, f),
+      ("translate", &[x]) => Transform::translate(x, 0.0),
+      ("translate", &[x, y]) => Transform::translate(x, y),
+      ("scale", &[s]) => Transform::scale(s, s),
+      ("scale", &[x, y]) => Transform::scale(x, y),
+      ("rotate", &[angle]) => Transform::rotate(angle.to_radians()),
+      ("rotate", &[angle, x, y]) => Transform::translate(x, y)
+        .then(&Transform::rotate(angle.to_radians()))
+        .then(&Transform::translate(-x, -y)),
+      ("skewX", &[angle]) => Transform::new(1.0, 0.0, angle.to_radians().tan(), 1.0, 0.0, 0.0),
+      ("skewY", &[angle]) => Transform::new(1.0, angle.to_radians().tan(), 0.0, 1.0, 0.0, 0.0),
+      _ => return None,
+    };
+    transform = transform.then(&item);
+    rest = rest[close + 1..].trim_start_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+  }
+  Some(transform)
+}
+
+/// Returns the transform from the `viewBox` of an `svg` element to its viewport, and the size of the user space
+/// used to resolve percentages.
+fn get_viewport_transform(node: roxmltree::Node) -> Result<(Transform, (f64, f64)), SvgError> {
+  let width = get_viewport_length(node, "width")?;
+  let height = get_viewport_length(node, "height")?;
+  let view_box = match node.attribute("viewBox") {
+    Some(value) => match parse_number_list(value).as_deref() {
+      Some(&[x, y, w, h]) if w > 0.0 && h > 0.0 => Some((x, y, w, h)),
+      _ => return Err(invalid_attribute("viewBox", value)),
+    },
+    None => None,
+  };
+  let (x, y, w, h) = match view_box {
+    Some(view_box) => view_box,
+    None => return Ok((Transform::identity(), (width.unwrap_or(100.0), height.unwrap_or(100.0)))),
+  };
+  let viewport_width = width.unwrap_or(w);
+  let viewport_height = height.unwrap_or(h);
+  let mut scale_x = viewport_width / w;
+  let mut scale_y = viewport_height / h;
+
+  let preserve_aspect_ratio = node.attribute("preserveAspectRatio").unwrap_or("xMidYMid meet");
+  let mut parts = preserve_aspect_ratio.split_ascii_whitespace();
+  let align = parts.next().unwrap_or("xMidYMid");
+  let is_slice = match parts.next() {
+    None | Some("meet") => false,
+    Some("slice") => true,
+    Some(_) => return Err(invalid_attribute("preserveAspectRatio", preserve_aspect_ratio)),
+  };
+  let (align_x, align_y) = if align == "none" {
+    (0.0, 0.0)
+  } else {
+    if align.len() != 8 {
+      return Err(invalid_attribute("preserveAspectRatio", preserve_aspect_ratio));
+    }
+    let align_x = match &align[0..4] {
+      "xMin" => 0.0,
+      "xMid" => 0.5,
+      "xMax" => 1.0,
+      _ => return Err(invalid_attribute("preserveAspectRatio", preserve_aspect_ratio)),
+    };
+    let align_y = match &align[4..8] {
+      "YMin" => 0.0,
+      "YMid" => 0.5,
+      "YMax" => 1.0,
+      _ => return Err(invalid_attribute("preserveAspectRatio", preserve_aspect_ratio)),
+    };
+    let scale = if is_slice {
+      scale_x.max(scale_y)
+    } else {
+      scale_x.min(scale_y)
+    };
+    scale_x = scale;
+    scale_y = scale;
+    (align_x, align_y)
+  };
+  let translate_x = (viewport_width - w * scale_x) * align_x - x * scale_x;
+  let translate_y = (viewport_height - h * scale_y) * align_y - y * scale_y;
+  Ok((
+    Transform::new(scale_x, 0.0, 0.0, scale_y, translate_x, translate_y),
+    (w, h),
+  ))
+}
+
+fn get_viewport_length(node: roxmltree::Node, name: &str) -> Result<Option<f64>, SvgError> {
+  match node.attribute(name) {
+    Some(value) => {
+      let length = parse_length(value).ok_or_else(|| invalid_attribute(name, value))?;
+      // Percentages are relative to an unknown outer viewport.
+      Ok(if length.unit == LengthUnit::Percent {
+        None
+      } else {
+        Some(length.resolve((100.0, 100.0), Axis::Other))
+      })
+    }
+    None => Ok(None),
+  }
+}
+
+// Paths
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Segment {
+  MoveTo(Point),
+  LineTo(Point),
+  QuadTo(Point, Point),
+  CubicTo(Point, Point, Point),
+  Close,
+}
+
+/// Parses SVG path data to absolute segments.
+///
+/// Following the SVG error handling rules, the path is rendered up to the first error: the segments parsed before an
+/// invalid number or an unknown command are returned.
+fn parse_path_data(data: &str) -> Vec<Segment> {
+  let mut parser = NumberParser::new(data);
+  let mut segments: Vec<Segment> = Vec::new();
+  let mut current = Point::new(0.0, 0.0);
+  let mut subpath_start = Point::new(0.0, 0.0);
+  // Reflected control point for smooth curves, with a flag telling if the previous segment was cubic.
+  let mut last_control: Option<(Point, bool)> = None;
+  let mut command: Option<u8> = None;
+  let mut has_subpath = false;
+
+  parser.skip_whitespace();
+  while !parser.is_end() {
+    let byte = match parser.peek() {
+      Some(byte) => byte,
+      None => break,
+    };
+    let cmd = if byte.is_ascii_alphabetic() {
+      parser.position += 1;
+      parser.skip_whitespace();
+      byte
+    } else {
+      match command {
+        // Implicit repetitions of moves are lines.
+        Some(b'M') => b'L',
+        Some(b'm') => b'l',
+        Some(b'Z' | b'z') | None => break,
+        Some(cmd) => cmd,
+      }
+    };
+    command = Some(cmd);
+    if !has_subpath && !matches!(cmd, b'M' | b'm') {
+      break;
+    }
+    let is_relative = cmd.is_ascii_lowercase();
+    let origin = if is_relative { current } else { Point::new(0.0, 0.0) };
+
+    let read_point = |parser: &mut NumberParser| -> Option<Point> {
+      let x = parser.number()?;
+      parser.skip_separators();
+      let y = parser.number()?;
+      parser.skip_separators();
+      Some(Point::new(origin.x + x, origin.y + y))
+    };
+
+    let segment = match cmd.to_ascii_uppercase() {
+      b'M' => match read_point(&mut parser) {
+        Some(p) => {
+          has_subpath = true;
+          subpath_start = p;
+          Segment::MoveTo(p)
+        }
+        None => break,
+      },
+      b'L' => match read_point(&mut parser) {
+        Some(p) => Segment::LineTo(p),
+        None => break,
+      },
+      b'H' => match parser.number() {
+        Some(x) => {
+          parser.skip_separators();
+          Segment::LineTo(Point::new(origin.x + x, current.y))
+        }
+        None => break,
+      },
+      b'V' => match parser.number() {
+        Some(y) => {
+          parser.skip_separators();
+          Segment::LineTo(Point::new(current.x, origin.y + y))
+        }
+        None => break,
+      },
+      b'Q' => match (read_point(&mut parser), read_point(&mut parser)) {
+        (Some(c), Some(p)) => Segment::QuadTo(c, p),
+        _ => break,
+      },
+      b'T' => match read_point(&mut parser) {
+        Some(p) => {
+          let c = match last_control {
+            Some((control, false)) => reflect(control, current),
+            _ => current,
+          };
+          Segment::QuadTo(c, p)
+        }
+        None => break,
+      },
+      b'C' => match (
+        read_point(&mut parser),
+        read_point(&mut parser),
+        read_point(&mut parser),
+      ) {
+        (Some(c1), Some(c2), Some(p)) => Segment::CubicTo(c1, c2, p),
+        _ => break,
+      },
+      b'S' => match (read_point(&mut parser), read_point(&mut parser)) {
+        (Some(c2), Some(p)) => {
+          let c1 = match last_control {
+            Some((control, true)) => reflect(control, current),
+            _ => current,
+          };
+          Segment::CubicTo(c1, c2, p)
+        }
+        _ => break,
+      },
+      b'A' => {
+        let arc = (|| {
+          let rx = parser.number()?;
+          parser.skip_separators();
+          let ry = parser.number()?;
+          parser.skip_separators();
+          let rotation = parser.number()?;
+          parser.skip_separators();
+          let large_arc = parser.flag()?;
+          parser.skip_separators();
+          let sweep = parser.flag()?;
+          parser.skip_separators();
+          let p = read_point(&mut parser)?;
+          Some((rx, ry, rotation, large_arc, sweep, p))
+        })();
+        match arc {
+          Some((rx, ry, rotation, large_arc, sweep, p)) => {
+            let arc_segments = get_arc_segments(current, rx, ry, rotation, large_arc, sweep, p);
+            segments.extend_from_slice(&arc_segments);
+            current = p;
+            last_control = None;
+            continue;
+          }
+          None => break,
+        }
+      }
+      b'Z' => {
+        parser.skip_whitespace();
+        Segment::Close
+      }
+      _ => break,
+    };
+    match segment {
+      Segment::MoveTo(p) | Segment::LineTo(p) => {
+        current = p;
+        last_control = None;
+      }
+      Segment::QuadTo(c, p) => {
+        current = p;
+        last_control = Some((c, false));
+      }
+      Segment::CubicTo(_, c2, p) => {
+        current = p;
+        last_control = Some((c2, true));
+      }
+      Segment::Close => {
+        current = subpath_start;
+        last_control = None;
+      }
+    }
+    segments.push(segment);
+  }
+  segments
+}
+
+fn reflect(control: Point, center: Point) -> Point {
+  Point::new(2.0 * center.x - control.x, 2.0 * center.y - control.y)
+}
+
+/// Converts an elliptical arc to cubic curves, following the SVG implementation notes (F.6.5).
+fn get_arc_segments(
+  start: Point,
+  rx: f64,
+  ry: f64,
+  rotation: f64,
+  large_arc: bool,
+  sweep: bool,
+  end: Point,
+) -> Vec<Segment> {
+  if start == end {
+    return Vec::new();
+  }
+  let mut rx = rx.abs();
+  let mut ry = ry.abs();
+  if rx == 0.0 || ry == 0.0 {
+    return vec![Segment::LineTo(end)];
+  }
+  let (sin_phi, cos_phi) = rotation.to_radians().sin_cos();
+  let dx = (start.x - end.x) / 2.0;
+  let dy = (start.y - end.y) / 2.0;
+  let x1 = cos_phi * dx + sin_phi * dy;
+  let y1 = -sin_phi * dx + cos_phi * dy;
+
+  let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+  if lambda > 1.0 {
+    let scale = lambda.sqrt();
+    rx *= scale;
+    ry *= scale;
+  }
+  let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+  let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+  let mut factor = (numerator / denominator).max(0.0).sqrt();
+  if large_arc == sweep {
+    factor = -factor;
+  }
+  let cx1 = factor * rx * y1 / ry;
+  let cy1 = -factor * ry * x1 / rx;
+  let cx = cos_phi * cx1 - sin_phi * cy1 + (start.x + end.x) / 2.0;
+  let cy = sin_phi * cx1 + cos_phi * cy1 + (start.y + end.y) / 2.0;
+
+  let angle = |ux: f64, uy: f64, vx: f64, vy: f64| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+  let theta1 = angle(1.0, 0.0, (x1 - cx1) / rx, (y1 - cy1) / ry);
+  let mut delta = angle((x1 - cx1) / rx, (y1 - cy1) / ry, (-x1 - cx1) / rx, (-y1 - cy1) / ry);
+  if !sweep && delta > 0.0 {
+    delta -= 2.0 * PI;
+  } else if sweep && delta < 0.0 {
+    delta += 2.0 * PI;
+  }
+
+  let count = (delta.abs() / (PI / 2.0)).ceil().max(1.0) as usize;
+  let step = delta / count as f64;
+  let k = 4.0 / 3.0 * (step / 4.0).tan();
+  let map = |x: f64, y: f64| {
+    Point::new(
+      cx + rx * cos_phi * x - ry * sin_phi * y,
+      cy + rx * sin_phi * x + ry * cos_phi * y,
+    )
+  };
+  let mut segments = Vec::with_capacity(count);
+  let mut theta = theta1;
+  for i in 0..count {
+    let (sin1, cos1) = theta.sin_cos();
+    let (sin2, cos2) = (theta + step).sin_cos();
+    let c1 = map(cos1 - k * sin1, sin1 + k * cos1);
+    let c2 = map(cos2 + k * sin2, sin2 - k * cos2);
+    let p = if i + 1 == count { end } else { map(cos2, sin2) };
+    segments.push(Segment::CubicTo(c1, c2, p));
+    theta += step;
+  }
+  segments
+}
+
+fn get_rect_segments(x: f64, y: f64, width: f64, height: f64, rx: f64, ry: f64) -> Vec<Segment> {
+  if width <= 0.0 || height <= 0.0 {
+    return Vec::new();
+  }
+  if rx <= 0.0 || ry <= 0.0 {
+    return vec![
+      Segment::MoveTo(Point::new(x, y)),
+      Segment::LineTo(Point::new(x + width, y)),
+      Segment::LineTo(Point::new(x + width, y + height)),
+      Segment::LineTo(Point::new(x, y + height)),
+      Segment::Close,
+    ];
+  }
+  let (right, bottom) = (x + width, y + height);
+  let mut segments = vec![
+    Segment::MoveTo(Point::new(x + rx, y)),
+    Segment::LineTo(Point::new(right - rx, y)),
+  ];
+  segments.extend(get_arc_segments(
+    Point::new(right - rx, y),
+    rx,
+    ry,
+    0.0,
+    false,
+    true,
+    Point::new(right, y + ry),
+  ));
+  segments.push(Segment::LineTo(Point::new(right, bottom - ry)));
+  segments.extend(get_arc_segments(
+    Point::new(right, bottom - ry),
+    rx,
+    ry,
+    0.0,
+    false,
+    true,
+    Point::new(right - rx, bottom),
+  ));
+  segments.push(Segment::LineTo(Point::new(x + rx, bottom)));
+  segments.extend(get_arc_segments(
+    Point::new(x + rx, bottom),
+    rx,
+    ry,
+    0.0,
+    false,
+    true,
+    Point::new(x, bottom - ry),
+  ));
+  segments.push(Segment::LineTo(Point::new(x, y + ry)));
+  segments.extend(get_arc_segments(
+    Point::new(x, y + ry),
+    rx,
+    ry,
+    0.0,
+    false,
+    true,
+    Point::new(x + rx, y),
+  ));
+  segments.push(Segment::Close);
+  segments
+}
+
+fn get_ellipse_segments(cx: f64, cy: f64, rx: f64, ry: f64) -> Vec<Segment> {
+  if rx <= 0.0 || ry <= 0.0 {
+    return Vec::new();
+  }
+  let mut segments = vec![Segment::MoveTo(Point::new(cx + rx, cy))];
+  segments.extend(get_arc_segments(
+    Point::new(cx + rx, cy),
+    rx,
+    ry,
+    0.0,
+    false,
+    true,
+    Point::new(cx - rx, cy),
+  ));
+  segments.extend(get_arc_segments(
+    Point::new(cx - rx, cy),
+    rx,
+    ry,
+    0.0,
+    false,
+    true,
+    Point::new(cx + rx, cy),
+  ));
+  segments.push(Segment::Close);
+  segments
+}
+
+/// Bounding box of the control polygon of a path, in user units.
+struct BoundingBox {
+  x_min: f64,
+  x_max: f64,
+  y_min: f64,
+  y_max: f64,
+}
+
+impl BoundingBox {
+  fn width(&self) -> f64 {
+    self.x_max - self.x_min
+  }
+
+  fn height(&self) -> f64 {
+    self.y_max - self.y_min
+  }
+}
+
+/// Returns whether the filled area of the path overlaps itself, where the `nonzero` and `evenodd` fill rules differ.
+///
+/// The rules differ where the winding number is at least 2 in absolute value. Each region of the flattened path is
+/// either adjacent to an edge crossing, or bounded by whole subpaths: the winding number is sampled around each
+/// crossing, and on both sides of an edge of each subpath.
+fn is_self_overlapping(segments: &[Segment]) -> bool {
+  let bbox = match get_segments_bbox(segments) {
+    Some(bbox) => bbox,
+    None => return false,
+  };
+  let polylines = get_subpath_polylines(segments);
+  let edges: Vec<(Point, Point)> = polylines
+    .iter()
+    .flat_map(|polyline| (0..polyline.len()).map(move |i| (polyline[i], polyline[(i + 1) % polyline.len()])))
+    .collect();
+  // Distance between the sample points and the edges
+  let epsilon = 1e-6 * (bbox.x_max - bbox.x_min + bbox.y_max - bbox.y_min).max(1.0);
+  let is_overlapped =
+    |p: Point, (x, y): (f64, f64)| get_winding_number(&edges, Point::new(p.x + x, p.y + y)).abs() >= 2;
+
+  for polyline in &polylines {
+    let middle = polyline[0].lerp(polyline[1], 0.5);
+    let (dx, dy) = get_direction(polyline[0], polyline[1], epsilon);
+    if is_overlapped(middle, (-dy, dx)) || is_overlapped(middle, (dy, -dx)) {
+      return true;
+    }
+  }
+  for (a, b, crossing) in get_edge_crossings(&edges) {
+    let (ax, ay) = get_direction(edges[a].0, edges[a].1, epsilon);
+    let (bx, by) = get_direction(edges[b].0, edges[b].1, epsilon);
+    let offsets = [
+      (ax + bx, ay + by),
+      (ax - bx, ay - by),
+      (bx - ax, by - ay),
+      (-ax - bx, -ay - by),
+    ];
+    if offsets.iter().any(|offset| is_overlapped(crossing, *offset)) {
+      return true;
+    }
+  }
+  false
+}
+
+/// Flattens the segments to one closed polyline per subpath.
+fn get_subpath_polylines(segments: &[Segment]) -> Vec<Vec<Point>> {
+  const CURVE_STEPS: usize = 8;
+  let mut polylines: Vec<Vec<Point>> = Vec::new();
+  let mut current: Vec<Point> = Vec::new();
+  let mut subpath_start = Point::new(0.0, 0.0);
+  for segment in segments {
+    let last = current.last().copied().unwrap_or(subpath_start);
+    let mut add = |p: Point| {
+      if current.last() != Some(&p) {
+        current.push(p);
+      }
+    };
+    match *segment {
+      Segment::MoveTo(p) => {
+        polylines.push(std::mem::take(&mut current));
+        subpath_start = p;
+        current.push(p);
+      }
+      Segment::LineTo(p) => {
+        add(last);
+        add(p);
+      }
+      Segment::QuadTo(c, p) => {
+        add(last);
+        for step in 1..=CURVE_STEPS {
+          let t = step as f64 / CURVE_STEPS as f64;
+          add(get_quadratic_point(last, c, p, t));
+        }
+      }
+      Segment::CubicTo(c1, c2, p) => {
+        add(last);
+        for step in 1..=CURVE_STEPS {
+          let t = step as f64 / CURVE_STEPS as f64;
+          add(get_cubic_point(last, c1, c2, p, t));
+        }
+      }
+      Segment::Close => {
+        // Following commands start a new subpath at the same point.
+        polylines.push(std::mem::take(&mut current));
+      }
+    }
+  }
+  polylines.push(current);
+  for polyline in polylines.iter_mut() {
+    if polyline.len() > 1 && polyline.first() == polyline.last() {
+      polyline.pop();
+    }
+  }
+  polylines.retain(|polyline| polyline.len() > 2);
+  polylines
+}
+
+/// Returns the vector of length `length` from `start` to `end`.
+fn get_direction(start: Point, end: Point, length: f64) -> (f64, f64) {
+  let (dx, dy) = (end.x - start.x, end.y - start.y);
+  let scale = length / (dx * dx + dy * dy).sqrt();
+  (dx * scale, dy * scale)
+}
+
+/// Returns the sum of the windings of the closed edges around `p`.
+fn get_winding_number(edges: &[(Point, Point)], p: Point) -> i32 {
+  let mut winding: i32 = 0;
+  for (start, end) in edges {
+    let side = (end.x - start.x) * (p.y - start.y) - (p.x - start.x) * (end.y - start.y);
+    if start.y <= p.y {
+      if end.y > p.y && side > 0.0 {
+        winding += 1;
+      }
+    } else if end.y <= p.y && side < 0.0 {
+      winding -= 1;
+    }
+  }
+  winding
+}
+
+/// Returns the pairs of edges crossing each other, with their intersection point.
+///
+/// Touching and collinear edges are ignored.
+fn get_edge_crossings(edges: &[(Point, Point)]) -> Vec<(usize, usize, Point)> {
+  let x_min = |index: usize| edges[index].0.x.min(edges[index].1.x);
+  // Sweep the edges by increasing `x_min`, only testing the edges whose `x` ranges overlap.
+  let mut order: Vec<usize> = (0..edges.len()).collect();
+  order.sort_by(|&a, &b| x_min(a).partial_cmp(&x_min(b)).unwrap_or(Ordering::Equal));
+  let cross = |o: Point, a: Point, b: Point| (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x);
+  let mut crossings: Vec<(usize, usize, Point)> = Vec::new();
+  for (i, &a) in order.iter().enumerate() {
+    let (a_start, a_end) = edges[a];
+    let x_max = a_start.x.max(a_end.x);
+    for &b in order[i + 1..].iter() {
+      if x_min(b) > x_max {
+        break;
+      }
+      let (b_start, b_end) = edges[b];
+      let d1 = cross(b_start, b_end, a_start);
+      let d2 = cross(b_start, b_end, a_end);
+      let d3 = cross(a_start, a_end, b_start);
+      let d4 = cross(a_start, a_end, b_end);
+      if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0)) && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0)) {
+        crossings.push((a, b, a_start.lerp(a_end, d1 / (d1 - d2))));
+      }
+    }
+  }
+  crossings
+}
+
+fn get_quadratic_point(start: Point, control: Point, end: Point, t: f64) -> Point {
+  start.lerp(control, t).lerp(control.lerp(end, t), t)
+}
+
+fn get_cubic_point(start: Point, control1: Point, control2: Point, end: Point, t: f64) -> Point {
+  let (a, b, c) = (
+    start.lerp(control1, t),
+    control1.lerp(control2, t),
+    control2.lerp(end, t),
+  );
+  a.lerp(b, t).lerp(b.lerp(c, t), t)
+}
+
+/// Returns the parameters in `(0, 1)` where the derivative of the quadratic Bézier curve cancels, along one axis.
+fn get_quadratic_extrema(start: f64, control: f64, end: f64) -> Vec<f64> {
+  let denominator = start - 2.0 * control + end;
+  if denominator == 0.0 {
+    return Vec::new();
+  }
+  let t = (start - control) / denominator;
+  if t > 0.0 && t < 1.0 {
+    vec![t]
+  } else {
+    Vec::new()
+  }
+}
+
+/// Returns the parameters in `(0, 1)` where the derivative of the cubic Bézier curve cancels, along one axis.
+fn get_cubic_extrema(start: f64, control1: f64, control2: f64, end: f64) -> Vec<f64> {
+  // The derivative is `3 * (a * t² + b * t + c)`
+  let (d0, d1, d2) = (control1 - start, control2 - control1, end - control2);
+  let (a, b, c) = (d0 - 2.0 * d1 + d2, 2.0 * (d1 - d0), d0);
+  let roots: Vec<f64> = if a.abs() < 1e-12 {
+    if b == 0.0 {
+      Vec::new()
+    } else {
+      vec![-c / b]
+    }
+  } else {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+      Vec::new()
+    } else {
+      let root = discriminant.sqrt();
+      vec![(-b + root) / (2.0 * a), (-b - root) / (2.0 * a)]
+    }
+  };
+  roots.into_iter().filter(|t| *t > 0.0 && *t < 1.0).collect()
+}
+
+/// Returns the bounds of the segments, including the extrema of the curves.
+fn get_segments_bbox(segments: &[Segment]) -> Option<BoundingBox> {
+  let mut bbox: Option<BoundingBox> = None;
+  let mut add = |p: Point| {
+    let bbox = bbox.get_or_insert(BoundingBox {
+      x_min: p.x,
+      x_max: p.x,
+      y_min: p.y,
+      y_max: p.y,
+    });
+    bbox.x_min = bbox.x_min.min(p.x);
+    bbox.x_max = bbox.x_max.max(p.x);
+    bbox.y_min = bbox.y_min.min(p.y);
+    bbox.y_max = bbox.y_max.max(p.y);
+  };
+  let mut current = Point::new(0.0, 0.0);
+  let mut subpath_start = current;
+  for segment in segments {
+    match *segment {
+      Segment::MoveTo(p) => {
+        subpath_start = p;
+        add(p);
+      }
+      Segment::LineTo(p) => add(p),
+      Segment::QuadTo(c, p) => {
+        let extrema = get_quadratic_extrema(current.x, c.x, p.x);
+        for t in extrema.into_iter().chain(get_quadratic_extrema(current.y, c.y, p.y)) {
+          add(get_quadratic_point(current, c, p, t));
+        }
+        add(p);
+      }
+      Segment::CubicTo(c1, c2, p) => {
+        let extrema = get_cubic_extrema(current.x, c1.x, c2.x, p.x);
+        for t in extrema.into_iter().chain(get_cubic_extrema(current.y, c1.y, c2.y, p.y)) {
+          add(get_cubic_point(current, c1, c2, p, t));
+        }
+        add(p);
+      }
+      Segment::Close => {}
+    }
+    current = match *segment {
+      Segment::MoveTo(p) | Segment::LineTo(p) | Segment::QuadTo(_, p) | Segment::CubicTo(_, _, p) => p,
+      Segment::Close => subpath_start,
+    };
+  }
+  bbox
+}
+
+/// CSS named colors, sorted by name.
+const NAMED_COLORS: &[(&str, u32)] = &[
+  ("aliceblue", 0xf0f8ff),
+  ("antiquewhite", 0xfaebd7),
+  ("aqua", 0x00ffff),
+  ("aquamarine", 0x7fffd4),
+  ("azure", 0xf0ffff),
+  ("beige", 0xf5f5dc),
+  ("bisque", 0xffe4c4),
+  ("black", 0x000000),
+  ("blanchedalmond", 0xffebcd),
+  ("blue", 0x0000ff),
+  ("blueviolet", 0x8a2be2),
+  ("brown", 0xa52a2a),
+  ("burlywood", 0xdeb887),
+  ("cadetblue", 0x5f9ea0),
+  ("chartreuse", 0x7fff00),
+  ("chocolate", 0xd2691e),
+  ("coral", 0xff7f50),
+  ("cornflowerblue", 0x6495ed),
+  ("cornsilk", 0xfff8dc),
+  ("crimson", 0xdc143c),
+  ("cyan", 0x00ffff),
+  ("darkblue", 0x00008b),
+  ("darkcyan", 0x008b8b),
+  ("darkgoldenrod", 0xb8860b),
+  ("darkgray", 0xa9a9a9),
+  ("darkgreen", 0x006400),
+  ("darkgrey", 0xa9a9a9),
+  ("darkkhaki", 0xbdb76b),
+  ("darkmagenta", 0x8b008b),
+  ("darkolivegreen", 0x556b2f),
+  ("darkorange", 0xff8c00),
+  ("darkorchid", 0x9932cc),
+  ("darkred", 0x8b0000),
+  ("darksalmon", 0xe9967a),
+  ("darkseagreen", 0x8fbc8f),
+  ("darkslateblue", 0x483d8b),
+  ("darkslategray", 0x2f4f4f),
+  ("darkslategrey", 0x2f4f4f),
+  ("darkturquoise", 0x00ced1),
+  ("darkviolet", 0x9400d3),
+  ("deeppink", 0xff1493),
+  ("deepskyblue", 0x00bfff),
+  ("dimgray", 0x696969),
+  ("dimgrey", 0x696969),
+  ("dodgerblue", 0x1e90ff),
+  ("firebrick", 0xb22222),
+  ("floralwhite", 0xfffaf0),
+  ("forestgreen", 0x228b22),
+  ("fuchsia", 0xff00ff),
+  ("gainsboro", 0xdcdcdc),
+  ("ghostwhite", 0xf8f8ff),
+  ("gold", 0xffd700),
+  ("goldenrod", 0xdaa520),
+  ("gray", 0x808080),
+  ("green", 0x008000),
+  ("greenyellow", 0xadff2f),
+  ("grey", 0x808080),
+  ("honeydew", 0xf0fff0),
+  ("hotpink", 0xff69b4),
+  ("indianred", 0xcd5c5c),
+  ("indigo", 0x4b0082),
+  ("ivory", 0xfffff0),
+  ("khaki", 0xf0e68c),
+  ("lavender", 0xe6e6fa),
+  ("lavenderblush", 0xfff0f5),
+  ("lawngreen", 0x7cfc00),
+  ("lemonchiffon", 0xfffacd),
+  ("lightblue", 0xadd8e6),
+  ("lightcoral", 0xf08080),
+  ("lightcyan", 0xe0ffff),
+  ("lightgoldenrodyellow", 0xfafad2),
+  ("lightgray", 0xd3d3d3),
+  ("lightgreen", 0x90ee90),
+  ("lightgrey", 0xd3d3d3),
+  ("lightpink", 0xffb6c1),
+  ("lightsalmon", 0xffa07a),
+  ("lightseagreen", 0x20b2aa),
+  ("lightskyblue", 0x87cefa),
+  ("lightslategray", 0x778899),
+  ("lightslategrey", 0x778899),
+  ("lightsteelblue", 0xb0c4de),
+  ("lightyellow", 0xffffe0),
+  ("lime", 0x00ff00),
+  ("limegreen", 0x32cd32),
+  ("linen", 0xfaf0e6),
+  ("magenta", 0xff00ff),
+  ("maroon", 0x800000),
+  ("mediumaquamarine", 0x66cdaa),
+  ("mediumblue", 0x0000cd),
+  ("mediumorchid", 0xba55d3),
+  ("mediumpurple", 0x9370db),
+  ("mediumseagreen", 0x3cb371),
+  ("mediumslateblue", 0x7b68ee),
+  ("mediumspringgreen", 0x00fa9a),
+  ("mediumturquoise", 0x48d1cc),
+  ("mediumvioletred", 0xc71585),
+  ("midnightblue", 0x191970),
+  ("mintcream", 0xf5fffa),
+  ("mistyrose", 0xffe4e1),
+  ("moccasin", 0xffe4b5),
+  ("navajowhite", 0xffdead),
+  ("navy", 0x000080),
+  ("oldlace", 0xfdf5e6),
+  ("olive", 0x808000),
+  ("olivedrab", 0x6b8e23),
+  ("orange", 0xffa500),
+  ("orangered", 0xff4500),
+  ("orchid", 0xda70d6),
+  ("palegoldenrod", 0xeee8aa),
+  ("palegreen", 0x98fb98),
+  ("paleturquoise", 0xafeeee),
+  ("palevioletred", 0xdb7093),
+  ("papayawhip", 0xffefd5),
+  ("peachpuff", 0xffdab9),
+  ("peru", 0xcd853f),
+  ("pink", 0xffc0cb),
+  ("plum", 0xdda0dd),
+  ("powderblue", 0xb0e0e6),
+  ("purple", 0x800080),
+  ("rebeccapurple", 0x663399),
+  ("red", 0xff0000),
+  ("rosybrown", 0xbc8f8f),
+  ("royalblue", 0x4169e1),
+  ("saddlebrown", 0x8b4513),
+  ("salmon", 0xfa8072),
+  ("sandybrown", 0xf4a460),
+  ("seagreen", 0x2e8b57),
+  ("seashell", 0xfff5ee),
+  ("sienna", 0xa0522d),
+  ("silver", 0xc0c0c0),
+  ("skyblue", 0x87ceeb),
+  ("slateblue", 0x6a5acd),
+  ("slategray", 0x708090),
+  ("slategrey", 0x708090),
+  ("snow", 0xfffafa),
+  ("springgreen", 0x00ff7f),
+  ("steelblue", 0x4682b4),
+  ("tan", 0xd2b48c),
+  ("teal", 0x008080),
+  ("thistle", 0xd8bfd8),
+  ("tomato", 0xff6347),
+  ("turquoise", 0x40e0d0),
+  ("violet", 0xee82ee),
+  ("wheat", 0xf5deb3),
+  ("white", 0xffffff),
+  ("whitesmoke", 0xf5f5f5),
+  ("yellow", 0xffff00),
+  ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn round_trip(svg: &str) -> ast::tags::DefineShape {
+    let tag = svg_to_define_shape(1, svg).unwrap();
+    let bytes = crate::emit_tag(&ast::Tag::DefineShape(tag.clone()), 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(ast::Tag::DefineShape(tag.clone())));
+    tag
+  }
+
+  #[test]
+  fn test_parse_path_data() {
+    let segments = parse_path_data("M10-20l5.5.5h1V0zq1 1 2 2t3 3");
+    assert_eq!(
+      segments,
+      vec![
+        Segment::MoveTo(Point::new(10.0, -20.0)),
+        Segment::LineTo(Point::new(15.5, -19.5)),
+        Segment::LineTo(Point::new(16.5, -19.5)),
+        Segment::LineTo(Point::new(16.5, 0.0)),
+        Segment::Close,
+        Segment::QuadTo(Point::new(11.0, -19.0), Point::new(12.0, -18.0)),
+        Segment::QuadTo(Point::new(13.0, -17.0), Point::new(15.0, -15.0)),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_parse_path_data_errors() {
+    let expected = vec![
+      Segment::MoveTo(Point::new(0.0, 0.0)),
+      Segment::LineTo(Point::new(10.0, 0.0)),
+    ];
+    assert_eq!(parse_path_data("M0 0L10 0X20 20L0 10"), expected);
+    assert_eq!(parse_path_data("M0 0L10 0L20"), expected);
+    assert_eq!(parse_path_data("L10 0"), Vec::new());
+  }
+
+  #[test]
+  fn test_parse_color() {
+    let red = Color {
+      r: 255,
+      g: 0,
+      b: 0,
+      a: 1.0,
+    };
+    assert_eq!(parse_color("red"), Some(red));
+    assert_eq!(parse_color("#f00"), Some(red));
+    assert_eq!(parse_color("#FF0000"), Some(red));
+    assert_eq!(parse_color("rgb(255, 0, 0)"), Some(red));
+    assert_eq!(parse_color("rgb(100%,0%,0%)"), Some(red));
+    assert_eq!(parse_color("nope"), None);
+  }
+
+  #[test]
+  fn test_solid_rect() {
+    let tag = round_trip(
+      r##"<svg xmlns="http://www.w3.org/2000/svg"><rect x="1" y="2" width="10" height="5" fill="#00ff00"/></svg>"##,
+    );
+    assert_eq!(
+      tag.bounds,
+      ast::Rect {
+        x_min: 20,
+        x_max: 220,
+        y_min: 40,
+        y_max: 140
+      }
+    );
+    assert_eq!(tag.edge_bounds, None);
+    assert_eq!(
+      tag.shape.initial_styles.fill,
+      vec![solid_fill(ast::StraightSRgba8 {
+        r: 0,
+        g: 255,
+        b: 0,
+        a: 255
+      })]
+    );
+  }
+
+  #[test]
+  fn test_layers_and_strokes() {
+    let tag = round_trip(
+      r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100" width="200" height="200">
+        <circle cx="50" cy="50" r="40" fill="red" stroke="blue" stroke-width="4" stroke-linecap="round" stroke-linejoin="round"/>
+        <path d="M10 10 C 20 20, 40 20, 50 10" fill="none" stroke="black" vector-effect="non-scaling-stroke"/>
+      </svg>"##,
+    );
+    assert!(tag.edge_bounds.is_some());
+    assert!(tag.has_scaling_strokes);
+    assert!(tag.has_non_scaling_strokes);
+    assert_eq!(tag.shape.initial_styles.line[0].width, 160);
+  }
+
+  #[test]
+  fn test_percentage_stroke_width() {
+    let tag = round_trip(
+      r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 200 200">
+        <line x1="10" y1="10" x2="190" y2="10" stroke="black" stroke-width="10%"/>
+      </svg>"##,
+    );
+    assert_eq!(tag.shape.initial_styles.line[0].width, 400);
+  }
+
+  #[test]
+  fn test_fill_winding() {
+    let star = "M50 0L79 90L2 35L98 35L21 90Z";
+    assert!(is_self_overlapping(&parse_path_data(star)));
+    let same_direction_hole = "M0 0H100V100H0ZM10 10H90V90H10Z";
+    assert!(is_self_overlapping(&parse_path_data(same_direction_hole)));
+    assert!(is_self_overlapping(&parse_path_data("M0 0H60V60H0ZM40 40H100V100H40Z")));
+    // Opposite hole, disjoint subpaths, bow tie and curves
+    assert!(!is_self_overlapping(&parse_path_data(
+      "M0 0H100V100H0ZM10 10V90H90V10Z"
+    )));
+    assert!(!is_self_overlapping(&parse_path_data("M0 0H10V10H0ZM20 0H30V10H20Z")));
+    assert!(!is_self_overlapping(&parse_path_data("M0 0L100 100V0L0 100Z")));
+    assert!(!is_self_overlapping(&parse_path_data("M0 0H100V100H0Z")));
+    assert!(!is_self_overlapping(&parse_path_data("M0 0Q50 -50 100 0T200 0V100H0Z")));
+
+    let svg = |fill_rule: &str| {
+      format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg"><path d="{}" fill="red" fill-rule="{}"/></svg>"##,
+        star, fill_rule
+      )
+    };
+    let tag = round_trip(&svg("nonzero"));
+    assert!(tag.has_fill_winding);
+    assert!(tag.edge_bounds.is_some());
+    let tag = round_trip(&svg("evenodd"));
+    assert!(!tag.has_fill_winding);
+    assert_eq!(tag.edge_bounds, None);
+
+    let mixed = |hole: &str| {
+      format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg">
+          <path d="{}" fill="red"/>
+          <path d="{}" fill="blue" fill-rule="evenodd"/>
+        </svg>"##,
+        star, hole
+      )
+    };
+    let result = svg_to_define_shape(1, &mixed(same_direction_hole));
+    assert!(matches!(result, Err(SvgError::IncompatibleFillRules)));
+    assert!(round_trip(&mixed("M0 0H100V100H0ZM10 10V90H90V10Z")).has_fill_winding);
+  }
+
+  #[test]
+  fn test_get_segments_bbox() {
+    let bbox = get_segments_bbox(&parse_path_data("M0 0Q50 100 100 0C100 -100 200 -100 200 0")).unwrap();
+    assert_eq!((bbox.x_min, bbox.x_max), (0.0, 200.0));
+    assert_eq!((bbox.y_min, bbox.y_max), (-75.0, 50.0));
+  }
+
+  #[test]
+  fn test_linear_gradient() {
+    let tag = round_trip(
+      r##"<svg xmlns="http://www.w3.org/2000/svg">
+        <defs>
+          <linearGradient id="g" x1="0" y1="0" x2="1" y2="0">
+            <stop offset="0" stop-color="white"/>
+            <stop offset="100%" stop-color="black" stop-opacity="0.5"/>
+          </linearGradient>
+        </defs>
+        <rect width="100" height="50" fill="url(#g)"/>
+      </svg>"##,
+    );
+    match &tag.shape.initial_styles.fill[0] {
+      ast::FillStyle::LinearGradient(ref style) => {
+        assert_eq!(style.matrix.translate_x, 1000);
+        assert_eq!(style.matrix.translate_y, 0);
+        assert_eq!(style.gradient.colors.len(), 2);
+        assert_eq!(style.gradient.colors[1].ratio, 255);
+        assert_eq!(style.gradient.colors[1].color.a, 128);
+      }
+      style => panic!("unexpected fill style: {:?}", style),
+    }
+  }
+
+  #[test]
+  fn test_unsupported_element() {
+    let result = svg_to_define_shape(1, r#"<svg xmlns="http://www.w3.org/2000/svg"><text>Hi</text></svg>"#);
+    assert!(matches!(result, Err(SvgError::UnsupportedElement(ref name)) if name == "text"));
+  }
+}
@@ -0,0 +1,428 @@
+//! Generate the ActionScript 3 classes of library symbols.
+//!
+//! `SymbolClass` tags only bind character ids to class names: the classes must also be defined by a `DoAbc` tag for
+//! the symbols to be available through `getDefinitionByName`. The generated classes are empty subclasses of the
+//! base class, whose constructor calls the base constructor without arguments.
+
+use crate::abc::{
+  build_do_abc, emit_u30, AbcFile, Class, ConstantPool, Instance, Method, MethodBody, Multiname, Namespace,
+  NamespaceKind, Script, Trait, TraitKind, DO_ABC_LAZY_INITIALIZE,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use swf_types as ast;
+
+const OP_CONSTRUCT_SUPER: u8 = 0x49;
+const OP_GET_LEX: u8 = 0x60;
+const OP_GET_LOCAL0: u8 = 0xd0;
+const OP_GET_SCOPE_OBJECT: u8 = 0x65;
+const OP_INIT_PROPERTY: u8 = 0x68;
+const OP_NEW_CLASS: u8 = 0x58;
+const OP_PUSH_SCOPE: u8 = 0x30;
+const OP_RETURN_VOID: u8 = 0x47;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SymbolBaseClass {
+  /// `flash.display.MovieClip`, for sprites.
+  MovieClip,
+  /// `flash.display.Bitmap`, for bitmaps.
+  Bitmap,
+  /// `flash.media.Sound`, for sounds.
+  Sound,
+  /// `flash.utils.ByteArray`, for binary data.
+  ByteArray,
+}
+
+impl SymbolBaseClass {
+  fn get_qualified_name(self) -> (&'static str, &'static str) {
+    match self {
+      SymbolBaseClass::MovieClip => ("flash.display", "MovieClip"),
+      SymbolBaseClass::Bitmap => ("flash.display", "Bitmap"),
+      SymbolBaseClass::Sound => ("flash.media", "Sound"),
+      SymbolBaseClass::ByteArray => ("flash.utils", "ByteArray"),
+    }
+  }
+}
+
+/// Class of a library symbol.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SymbolClassStub {
+  /// Fully qualified class name, such as `assets.Logo`.
+  pub class_name: String,
+  pub base_class: SymbolBaseClass,
+}
+
+/// Builds the `DoAbc` tag defining the symbol classes and the `SymbolClass` tag binding them to the characters.
+///
+/// Returns an `InvalidInput` error for invalid or duplicate class names.
+pub fn build_symbol_classes(
+  symbols: &BTreeMap<u16, SymbolClassStub>,
+) -> io::Result<(ast::tags::DoAbc, ast::tags::SymbolClass)> {
+  let file = build_symbol_class_abc(symbols)?;
+  let do_abc = build_do_abc(&file, "", DO_ABC_LAZY_INITIALIZE)?;
+  let symbol_class = ast::tags::SymbolClass {
+    symbols: symbols
+      .iter()
+      .map(|(id, stub)| ast::NamedId {
+        id: *id,
+        name: stub.class_name.clone(),
+      })
+      .collect(),
+  };
+  Ok((do_abc, symbol_class))
+}
+
+/// Builds the ABC file defining the symbol classes, with a script initializer creating them in the global scope.
+fn build_symbol_class_abc(symbols: &BTreeMap<u16, SymbolClassStub>) -> io::Result<AbcFile> {
+  let mut pool = PoolBuilder::default();
+  let mut file = AbcFile::default();
+  let mut script_code: Vec<u8> = vec![OP_GET_LOCAL0, OP_PUSH_SCOPE];
+  let mut script_traits: Vec<Trait> = Vec::with_capacity(symbols.len());
+  let mut class_names: HashSet<&str> = HashSet::new();
+
+  for stub in symbols.values() {
+    let (package, name) = split_class_name(&stub.class_name)?;
+    if !class_names.insert(&stub.class_name) {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("duplicate symbol class: {}", stub.class_name),
+      ));
+    }
+    let (base_package, base_name) = stub.base_class.get_qualified_name();
+    let class_name = pool.qname(NamespaceKind::PackageNamespace, package, name);
+    let base_class_name = pool.qname(NamespaceKind::PackageNamespace, base_package, base_name);
+    let protected_namespace = pool.namespace(NamespaceKind::ProtectedNamespace, &stub.class_name.replace('.', ":"));
+    let class_index = file.classes.len() as u32;
+
+    let instance_initializer = add_method(
+      &mut file,
+      vec![
+        OP_GET_LOCAL0,
+        OP_PUSH_SCOPE,
+        OP_GET_LOCAL0,
+        OP_CONSTRUCT_SUPER,
+        0,
+        OP_RETURN_VOID,
+      ],
+    );
+    let class_initializer = add_method(&mut file, vec![OP_GET_LOCAL0, OP_PUSH_SCOPE, OP_RETURN_VOID]);
+    file.instances.push(Instance {
+      name: class_name,
+      super_name: base_class_name,
+      is_sealed: true,
+      is_final: false,
+      is_interface: false,
+      protected_namespace: Some(protected_namespace),
+      interfaces: Vec::new(),
+      initializer: instance_initializer,
+      traits: Vec::new(),
+    });
+    file.classes.push(Class {
+      initializer: class_initializer,
+      traits: Vec::new(),
+    });
+
+    // Create the class in the global scope: `newclass` takes the base class from the stack
+    script_code.extend_from_slice(&[OP_GET_SCOPE_OBJECT, 0, OP_GET_LEX]);
+    emit_u30(&mut script_code, base_class_name)?;
+    script_code.push(OP_NEW_CLASS);
+    emit_u30(&mut script_code, class_index)?;
+    script_code.push(OP_INIT_PROPERTY);
+    emit_u30(&mut script_code, class_name)?;
+    script_traits.push(Trait {
+      name: class_name,
+      kind: TraitKind::Class {
+        slot_id: class_index + 1,
+        class: class_index,
+      },
+      is_final: false,
+      is_override: false,
+      metadata: Vec::new(),
+    });
+  }
+  script_code.push(OP_RETURN_VOID);
+
+  let script_initializer = file.methods.len() as u32;
+  file.methods.push(Method::default());
+  file.method_bodies.push(MethodBody {
+    method: script_initializer,
+    max_stack: 2,
+    local_count: 1,
+    init_scope_depth: 1,
+    max_scope_depth: 2,
+    code: script_code,
+    exceptions: Vec::new(),
+    traits: Vec::new(),
+  });
+  file.scripts.push(Script {
+    initializer: script_initializer,
+    traits: script_traits,
+  });
+  file.constant_pool = pool.pool;
+  Ok(file)
+}
+
+/// Splits a fully qualified class name into its package and its local name.
+fn split_class_name(class_name: &str) -> io::Result<(&str, &str)> {
+  let is_valid = !class_name.is_empty()
+    && class_name.split('.').all(|part| {
+      let mut chars = part.chars();
+      match chars.next() {
+        Some(first) => {
+          (first.is_alphabetic() || first == '_' || first == '$')
+            && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        }
+        None => false,
+      }
+    });
+  if !is_valid {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("invalid symbol class name: {:?}", class_name),
+    ));
+  }
+  Ok(match class_name.rfind('.') {
+    Some(index) => (&class_name[..index], &class_name[index + 1..]),
+    None => ("", class_name),
+  })
+}
+
+/// Adds a method taking no parameters, returning its index.
+fn add_method(file: &mut AbcFile, code: Vec<u8>) -> u32 {
+  let method = file.methods.len() as u32;
+  file.methods.push(Method::default());
+  file.method_bodies.push(MethodBody {
+    method,
+    max_stack: 1,
+    local_count: 1,
+    init_scope_depth: 2,
+    max_scope_depth: 3,
+    code,
+    exceptions: Vec::new(),
+    traits: Vec::new(),
+  });
+  method
+}
+
+/// Constant pool builder, reusing the existing entries.
+#[derive(Default)]
+struct PoolBuilder {
+  pool: ConstantPool,
+  strings: HashMap<String, u32>,
+  namespaces: HashMap<Namespace, u32>,
+  multinames: HashMap<Multiname, u32>,
+}
+
+impl PoolBuilder {
+  fn string(&mut self, value: &str) -> u32 {
+    if let Some(index) = self.strings.get(value) {
+      return *index;
+    }
+    self.pool.strings.push(String::from(value));
+    let index = self.pool.strings.len() as u32;
+    self.strings.insert(String::from(value), index);
+    index
+  }
+
+  fn namespace(&mut self, kind: NamespaceKind, name: &str) -> u32 {
+    let namespace = Namespace {
+      kind,
+      name: self.string(name),
+    };
+    if let Some(index) = self.namespaces.get(&namespace) {
+      return *index;
+    }
+    self.pool.namespaces.push(namespace);
+    let index = self.pool.namespaces.len() as u32;
+    self.namespaces.insert(namespace, index);
+    index
+  }
+
+  fn qname(&mut self, kind: NamespaceKind, namespace: &str, name: &str) -> u32 {
+    let multiname = Multiname::QName {
+      namespace: self.namespace(kind, namespace),
+      name: self.string(name),
+    };
+    if let Some(index) = self.multinames.get(&multiname) {
+      return *index;
+    }
+    self.pool.multinames.push(multiname.clone());
+    let index = self.pool.multinames.len() as u32;
+    self.multinames.insert(multiname, index);
+    index
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn stub(class_name: &str, base_class: SymbolBaseClass) -> SymbolClassStub {
+    SymbolClassStub {
+      class_name: String::from(class_name),
+      base_class,
+    }
+  }
+
+  #[test]
+  fn test_build_symbol_classes() {
+    let mut symbols: BTreeMap<u16, SymbolClassStub> = BTreeMap::new();
+    symbols.insert(2, stub("Logo", SymbolBaseClass::Bitmap));
+    symbols.insert(1, stub("assets.Jingle", SymbolBaseClass::Sound));
+    let (do_abc, symbol_class) = build_symbol_classes(&symbols).unwrap();
+
+    assert_eq!(
+      symbol_class.symbols,
+      vec![
+        ast::NamedId {
+          id: 1,
+          name: String::from("assets.Jingle"),
+        },
+        ast::NamedId {
+          id: 2,
+          name: String::from("Logo"),
+        },
+      ]
+    );
+    let header = do_abc.header.as_ref().unwrap();
+    assert_eq!(header.flags, DO_ABC_LAZY_INITIALIZE);
+    let file = build_symbol_class_abc(&symbols).unwrap();
+    assert_eq!(do_abc, build_do_abc(&file, "", DO_ABC_LAZY_INITIALIZE).unwrap());
+
+    for tag in [ast::Tag::DoAbc(do_abc), ast::Tag::SymbolClass(symbol_class)] {
+      let bytes = crate::emit_tag(&tag, 10).unwrap();
+      let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+      assert!(rest.is_empty());
+      assert_eq!(parsed, Some(tag));
+    }
+  }
+
+  #[test]
+  fn test_build_symbol_class_abc() {
+    let mut symbols: BTreeMap<u16, SymbolClassStub> = BTreeMap::new();
+    symbols.insert(2, stub("Logo", SymbolBaseClass::Bitmap));
+    symbols.insert(1, stub("assets.Jingle", SymbolBaseClass::Sound));
+    let file = build_symbol_class_abc(&symbols).unwrap();
+
+    let pool = &file.constant_pool;
+    assert_eq!(
+      pool.strings,
+      [
+        "assets",
+        "Jingle",
+        "flash.media",
+        "Sound",
+        "assets:Jingle",
+        "",
+        "Logo",
+        "flash.display",
+        "Bitmap"
+      ]
+    );
+    let namespace = |kind: NamespaceKind, name: u32| Namespace { kind, name };
+    assert_eq!(
+      pool.namespaces,
+      vec![
+        namespace(NamespaceKind::PackageNamespace, 1),
+        namespace(NamespaceKind::PackageNamespace, 3),
+        namespace(NamespaceKind::ProtectedNamespace, 5),
+        namespace(NamespaceKind::PackageNamespace, 6),
+        namespace(NamespaceKind::PackageNamespace, 8),
+        namespace(NamespaceKind::ProtectedNamespace, 7),
+      ]
+    );
+    let qname = |namespace: u32, name: u32| Multiname::QName { namespace, name };
+    assert_eq!(
+      pool.multinames,
+      vec![qname(1, 2), qname(2, 4), qname(4, 7), qname(5, 9)]
+    );
+
+    // Classes in id order: `assets.Jingle` (methods 0 and 1) then `Logo` (methods 2 and 3)
+    let names: Vec<_> = file
+      .instances
+      .iter()
+      .map(|instance| {
+        (
+          instance.name,
+          instance.super_name,
+          instance.protected_namespace,
+          instance.initializer,
+        )
+      })
+      .collect();
+    assert_eq!(names, vec![(1, 2, Some(3), 0), (3, 4, Some(6), 2)]);
+    assert!(file
+      .instances
+      .iter()
+      .all(|instance| instance.is_sealed && instance.traits.is_empty()));
+    let class_initializers: Vec<u32> = file.classes.iter().map(|class| class.initializer).collect();
+    assert_eq!(class_initializers, vec![1, 3]);
+
+    let body = |method: u32, max_stack: u32, scope_depths: (u32, u32), code: &[u8]| MethodBody {
+      method,
+      max_stack,
+      local_count: 1,
+      init_scope_depth: scope_depths.0,
+      max_scope_depth: scope_depths.1,
+      code: code.to_vec(),
+      exceptions: Vec::new(),
+      traits: Vec::new(),
+    };
+    let instance_code = [
+      OP_GET_LOCAL0,
+      OP_PUSH_SCOPE,
+      OP_GET_LOCAL0,
+      OP_CONSTRUCT_SUPER,
+      0,
+      OP_RETURN_VOID,
+    ];
+    let class_code = [OP_GET_LOCAL0, OP_PUSH_SCOPE, OP_RETURN_VOID];
+    // `getlocal0`, `pushscope`, then `getscopeobject 0`, `getlex <base>`, `newclass <class>`, `initproperty <name>` for
+    // each class, and `returnvoid`
+    let script_code = [
+      0xd0, 0x30, 0x65, 0, 0x60, 2, 0x58, 0, 0x68, 1, 0x65, 0, 0x60, 4, 0x58, 1, 0x68, 3, 0x47,
+    ];
+    assert_eq!(file.methods.len(), 5);
+    assert_eq!(
+      file.method_bodies,
+      vec![
+        body(0, 1, (2, 3), &instance_code),
+        body(1, 1, (2, 3), &class_code),
+        body(2, 1, (2, 3), &instance_code),
+        body(3, 1, (2, 3), &class_code),
+        body(4, 2, (1, 2), &script_code),
+      ]
+    );
+
+    let class_trait = |name: u32, class: u32| Trait {
+      name,
+      kind: TraitKind::Class {
+        slot_id: class + 1,
+        class,
+      },
+      is_final: false,
+      is_override: false,
+      metadata: Vec::new(),
+    };
+    assert_eq!(
+      file.scripts,
+      vec![Script {
+        initializer: 4,
+        traits: vec![class_trait(1, 0), class_trait(3, 1)],
+      }]
+    );
+  }
+
+  #[test]
+  fn test_build_symbol_classes_rejects_invalid_names() {
+    for class_name in ["", "assets.", "1Logo", "assets..Logo", "assets.Lo-go"] {
+      let mut symbols: BTreeMap<u16, SymbolClassStub> = BTreeMap::new();
+      symbols.insert(1, stub(class_name, SymbolBaseClass::MovieClip));
+      assert!(build_symbol_classes(&symbols).is_err(), "{:?}", class_name);
+    }
+    let mut symbols: BTreeMap<u16, SymbolClassStub> = BTreeMap::new();
+    symbols.insert(1, stub("Logo", SymbolBaseClass::MovieClip));
+    symbols.insert(2, stub("Logo", SymbolBaseClass::ByteArray));
+    assert!(build_symbol_classes(&symbols).is_err());
+  }
+}
@@ -14,10 +14,12 @@ use crate::button::{
   emit_button2_cond_action_string, emit_button_record_string, emit_button_sound, get_min_button_version, ButtonVersion,
 };
 use crate::display::{emit_blend_mode, emit_clip_actions_string, emit_filter_list};
-use crate::morph_shape::{emit_morph_shape, MorphShapeVersion};
+use crate::morph_shape::{
+  emit_morph_shape, get_min_morph_shape_version, get_morph_shape_end, get_morph_shape_start, MorphShapeVersion,
+};
 use crate::primitives::{emit_le_f32, emit_le_i16, emit_le_u16, emit_le_u32, emit_u8};
 use crate::shape::emit_glyph;
-use crate::shape::{emit_shape, get_min_shape_version, ShapeVersion};
+use crate::shape::{emit_shape, get_min_shape_version, get_shape_edge_bounds, ShapeVersion};
 use crate::sound::{audio_coding_format_to_code, emit_sound_info, sound_rate_to_code};
 use crate::text::{
   csm_table_hint_to_code, emit_font_alignment_zone, emit_font_layout, emit_language_code, emit_offset_glyphs,
@@ -76,7 +78,10 @@ pub fn emit_tag<W: io::Write>(writer: &mut W, value: &ast::Tag, swf_version: u8)
       emit_csm_text_settings(&mut tag_writer, tag)?;
       74
     }
-    ast::Tag::DefineBinaryData(ref _tag) => unimplemented!(),
+    ast::Tag::DefineBinaryData(ref tag) => {
+      emit_define_binary_data(&mut tag_writer, tag)?;
+      87
+    }
     ast::Tag::DefineBitmap(ref tag) => match emit_define_bitmap_any(&mut tag_writer, tag)? {
       DefineBitmapVersion::DefineBitsJpeg1 => 6,
       DefineBitmapVersion::DefineBitsLossless1 => 20,
@@ -89,12 +94,18 @@ pub fn emit_tag<W: io::Write>(writer: &mut W, value: &ast::Tag, swf_version: u8)
       ButtonVersion::Button1 => 7,
       ButtonVersion::Button2 => 34,
     },
-    ast::Tag::DefineButtonColorTransform(ref _tag) => unimplemented!(),
+    ast::Tag::DefineButtonColorTransform(ref tag) => {
+      emit_define_button_color_transform(&mut tag_writer, tag)?;
+      23
+    }
     ast::Tag::DefineButtonSound(ref tag) => {
       emit_define_button_sound(&mut tag_writer, tag)?;
       17
     }
-    ast::Tag::DefineCffFont(ref _tag) => unimplemented!(),
+    ast::Tag::DefineCffFont(ref tag) => {
+      emit_define_cff_font(&mut tag_writer, tag)?;
+      91
+    }
     ast::Tag::DefineDynamicText(ref tag) => {
       emit_define_dynamic_text(&mut tag_writer, tag)?;
       37
@@ -131,7 +142,10 @@ pub fn emit_tag<W: io::Write>(writer: &mut W, value: &ast::Tag, swf_version: u8)
       MorphShapeVersion::MorphShape1 => 46,
       MorphShapeVersion::MorphShape2 => 84,
     },
-    ast::Tag::DefineScalingGrid(ref _tag) => unimplemented!(),
+    ast::Tag::DefineScalingGrid(ref tag) => {
+      emit_define_scaling_grid(&mut tag_writer, tag)?;
+      78
+    }
     ast::Tag::DefineSceneAndFrameLabelData(ref tag) => {
       emit_define_scene_and_frame_label_data(&mut tag_writer, tag)?;
       86
@@ -154,7 +168,10 @@ pub fn emit_tag<W: io::Write>(writer: &mut W, value: &ast::Tag, swf_version: u8)
       DefineTextVersion::Text1 => 11,
       DefineTextVersion::Text2 => 33,
     },
-    ast::Tag::DefineVideoStream(ref _tag) => unimplemented!(),
+    ast::Tag::DefineVideoStream(ref tag) => {
+      emit_define_video_stream(&mut tag_writer, tag)?;
+      60
+    }
     ast::Tag::DoAbc(ref tag) => match emit_do_abc_any(&mut tag_writer, tag)? {
       DoAbcVersion::Abc1 => 72,
       DoAbcVersion::Abc2 => 82,
@@ -163,7 +180,10 @@ pub fn emit_tag<W: io::Write>(writer: &mut W, value: &ast::Tag, swf_version: u8)
       emit_do_action(&mut tag_writer, tag)?;
       12
     }
-    ast::Tag::DoInitAction(ref _tag) => unimplemented!(),
+    ast::Tag::DoInitAction(ref tag) => {
+      emit_do_init_action(&mut tag_writer, tag)?;
+      59
+    }
     ast::Tag::EnableDebugger(ref _tag) => unimplemented!(),
     ast::Tag::EnablePostscript => unimplemented!(),
     ast::Tag::ExportAssets(ref tag) => {
@@ -208,8 +228,14 @@ pub fn emit_tag<W: io::Write>(writer: &mut W, value: &ast::Tag, swf_version: u8)
     }
     ast::Tag::SetTabIndex(ref _tag) => unimplemented!(),
     ast::Tag::ShowFrame => 1,
-    ast::Tag::SoundStreamBlock(ref _tag) => unimplemented!(),
-    ast::Tag::SoundStreamHead(ref _tag) => unimplemented!(),
+    ast::Tag::SoundStreamBlock(ref tag) => {
+      emit_sound_stream_block(&mut tag_writer, tag)?;
+      19
+    }
+    ast::Tag::SoundStreamHead(ref tag) => match emit_sound_stream_head_any(&mut tag_writer, tag)? {
+      SoundStreamHeadVersion::SoundStreamHead1 => 18,
+      SoundStreamHeadVersion::SoundStreamHead2 => 45,
+    },
     ast::Tag::StartSound(ref tag) => {
       emit_start_sound(&mut tag_writer, tag)?;
       15
@@ -293,7 +319,7 @@ pub(crate) fn emit_define_button_any<W: io::Write>(
       debug_assert!(!value.track_as_menu);
       writer.write_all(&record_writer)?;
       debug_assert_eq!(value.actions.len(), 1);
-      let action: &ast::ButtonCondAction = value.actions.get(0).unwrap();
+      let action: &ast::ButtonCondAction = value.actions.first().unwrap();
       debug_assert!(action.conditions.is_none());
       writer.write_all(&action.actions)?;
     }
@@ -317,6 +343,14 @@ pub(crate) fn emit_define_button_any<W: io::Write>(
   Ok(version)
 }
 
+pub fn emit_define_button_color_transform<W: io::Write>(
+  writer: &mut W,
+  value: &ast::tags::DefineButtonColorTransform,
+) -> io::Result<()> {
+  emit_le_u16(writer, value.button_id)?;
+  emit_color_transform(writer, &value.transform)
+}
+
 pub(crate) fn emit_define_button_sound<W: io::Write>(
   writer: &mut W,
   value: &ast::tags::DefineButtonSound,
@@ -329,6 +363,22 @@ pub(crate) fn emit_define_button_sound<W: io::Write>(
   Ok(())
 }
 
+pub fn emit_define_cff_font<W: io::Write>(writer: &mut W, value: &ast::tags::DefineCffFont) -> io::Result<()> {
+  emit_le_u16(writer, value.id)?;
+  emit_c_string(writer, &value.font_name)?;
+  #[allow(clippy::identity_op)]
+  let flags: u8 = 0
+    | (if value.is_bold { 1 << 0 } else { 0 })
+    | (if value.is_italic { 1 << 1 } else { 0 })
+    | (if value.data.is_some() { 1 << 2 } else { 0 });
+  // Bits [3, 7] are reserved
+  emit_u8(writer, flags)?;
+  if let Some(ref data) = value.data {
+    writer.write_all(data)?;
+  }
+  Ok(())
+}
+
 pub(crate) fn emit_define_dynamic_text<W: io::Write>(
   writer: &mut W,
   value: &ast::tags::DefineDynamicText,
@@ -499,17 +549,16 @@ pub(crate) fn emit_define_font_info_any<W: io::Write>(
   emit_u8(writer, font_name_bytes.len().try_into().unwrap())?;
   writer.write_all(font_name_bytes)?;
 
-  let mut use_wide_codes = version >= DefineFontInfoVersion::FontInfo2;
-  if !use_wide_codes {
-    for code_unit in value.code_units.iter() {
-      if *code_unit >= 256 {
-        use_wide_codes = true;
-        break;
-      }
-    }
+  if version >= DefineFontInfoVersion::FontInfo2 && (value.is_ansi || value.is_shift_jis) {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "DefineFontInfo2 requires `is_ansi` and `is_shift_jis` to be false",
+    ));
   }
 
-  // TODO: `is_ansi` and `is_shift_jis` must be `false` in FontInfo2.
+  let use_wide_codes = version >= DefineFontInfoVersion::FontInfo2
+    || value.code_units.iter().any(|code_unit| *code_unit > u16::from(u8::MAX));
+
   #[allow(clippy::identity_op)]
   let flags: u8 = 0
     | (if use_wide_codes { 1 << 0 } else { 0 })
@@ -528,7 +577,8 @@ pub(crate) fn emit_define_font_info_any<W: io::Write>(
     if use_wide_codes {
       emit_le_u16(writer, *code_unit)?;
     } else {
-      emit_u8(writer, (*code_unit).try_into().unwrap())?;
+      // Narrow codes are only used when all the code units fit in a byte
+      emit_u8(writer, *code_unit as u8)?;
     }
   }
 
@@ -556,6 +606,12 @@ pub fn emit_define_glyph_font<W: io::Write>(writer: &mut W, value: &ast::tags::D
   writer.write_all(&glyph_writer)
 }
 
+pub fn emit_define_binary_data<W: io::Write>(writer: &mut W, value: &ast::tags::DefineBinaryData) -> io::Result<()> {
+  emit_le_u16(writer, value.id)?;
+  emit_le_u32(writer, 0)?; // Reserved
+  writer.write_all(&value.data)
+}
+
 pub fn emit_define_jpeg_tables<W: io::Write>(writer: &mut W, value: &ast::tags::DefineJpegTables) -> io::Result<()> {
   writer.write_all(&value.data)
 }
@@ -568,24 +624,37 @@ pub fn emit_define_morph_shape_any<W: io::Write>(
   emit_rect(writer, &value.bounds)?;
   emit_rect(writer, &value.morph_bounds)?;
 
-  let version = if let Some(ref edge_bounds) = &value.edge_bounds {
-    let morph_edge_bounds = &value.morph_edge_bounds.unwrap();
-    emit_rect(writer, edge_bounds)?;
-    emit_rect(writer, morph_edge_bounds)?;
+  let version = if value.edge_bounds.is_some() || value.morph_edge_bounds.is_some() {
+    MorphShapeVersion::MorphShape2
+  } else {
+    get_min_morph_shape_version(&value.shape)
+  };
+  if version == MorphShapeVersion::MorphShape2 {
+    // Edge bounds are required by `DefineMorphShape2`: compute the missing ones.
+    match &value.edge_bounds {
+      Some(ref edge_bounds) => emit_rect(writer, edge_bounds)?,
+      None => emit_rect(writer, &get_shape_edge_bounds(&get_morph_shape_start(&value.shape)))?,
+    }
+    match &value.morph_edge_bounds {
+      Some(ref morph_edge_bounds) => emit_rect(writer, morph_edge_bounds)?,
+      None => emit_rect(writer, &get_shape_edge_bounds(&get_morph_shape_end(&value.shape)))?,
+    }
     #[allow(clippy::identity_op)]
     let flags: u8 = 0
       | (if value.has_scaling_strokes { 1 << 0 } else { 0 })
       | (if value.has_non_scaling_strokes { 1 << 1 } else { 0 });
     // Skip bits [2, 7]
     emit_u8(writer, flags)?;
-    MorphShapeVersion::MorphShape2
-  } else {
-    MorphShapeVersion::MorphShape1
-  };
+  }
   emit_morph_shape(writer, &value.shape, version)?;
   Ok(version)
 }
 
+pub fn emit_define_scaling_grid<W: io::Write>(writer: &mut W, value: &ast::tags::DefineScalingGrid) -> io::Result<()> {
+  emit_le_u16(writer, value.character_id)?;
+  emit_rect(writer, &value.splitter)
+}
+
 pub fn emit_define_scene_and_frame_label_data<W: io::Write>(
   writer: &mut W,
   value: &ast::tags::DefineSceneAndFrameLabelData,
@@ -606,8 +675,17 @@ pub fn emit_define_scene_and_frame_label_data<W: io::Write>(
 pub fn emit_define_shape_any<W: io::Write>(writer: &mut W, value: &ast::tags::DefineShape) -> io::Result<ShapeVersion> {
   emit_le_u16(writer, value.id)?;
   emit_rect(writer, &value.bounds)?;
-  let version = if let Some(ref edge_bounds) = &value.edge_bounds {
-    emit_rect(writer, edge_bounds)?;
+  let version = if value.edge_bounds.is_some() {
+    ShapeVersion::Shape4
+  } else {
+    get_min_shape_version(&value.shape)
+  };
+  if version == ShapeVersion::Shape4 {
+    // Edge bounds are required by `DefineShape4`: compute them if the shape only needs this version for its styles.
+    match &value.edge_bounds {
+      Some(ref edge_bounds) => emit_rect(writer, edge_bounds)?,
+      None => emit_rect(writer, &get_shape_edge_bounds(&value.shape))?,
+    }
     #[allow(clippy::identity_op)]
     let flags: u8 = 0
       | (if value.has_scaling_strokes { 1 << 0 } else { 0 })
@@ -615,10 +693,7 @@ pub fn emit_define_shape_any<W: io::Write>(writer: &mut W, value: &ast::tags::De
       | (if value.has_fill_winding { 1 << 2 } else { 0 });
     // Skip bits [3, 7]
     emit_u8(writer, flags)?;
-    ShapeVersion::Shape4
-  } else {
-    get_min_shape_version(&value.shape)
-  };
+  }
   emit_shape(writer, &value.shape, version)?;
   Ok(version)
 }
@@ -661,7 +736,7 @@ pub(crate) fn emit_define_text_any<W: io::Write>(
   let mut has_alpha = false;
   for record in &value.records {
     if let Some(color) = record.color {
-      if color.a != u8::max_value() {
+      if color.a != u8::MAX {
         has_alpha = true;
       }
     }
@@ -686,6 +761,42 @@ enum DoAbcVersion {
   Abc2,
 }
 
+pub fn emit_define_video_stream<W: io::Write>(writer: &mut W, value: &ast::tags::DefineVideoStream) -> io::Result<()> {
+  let frame_count: u16 = value.frame_count.try_into().map_err(|_| {
+    io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "DefineVideoStream frame count must be at most 65535",
+    )
+  })?;
+  emit_le_u16(writer, value.id)?;
+  emit_le_u16(writer, frame_count)?;
+  emit_le_u16(writer, value.width)?;
+  emit_le_u16(writer, value.height)?;
+  let deblocking_code: u8 = match value.deblocking {
+    ast::VideoDeblocking::PacketValue => 0,
+    ast::VideoDeblocking::Off => 1,
+    ast::VideoDeblocking::Level1 => 2,
+    ast::VideoDeblocking::Level2 => 3,
+    ast::VideoDeblocking::Level3 => 4,
+    ast::VideoDeblocking::Level4 => 5,
+  };
+  #[allow(clippy::identity_op)]
+  let flags: u8 = 0 | (if value.use_smoothing { 1 << 0 } else { 0 }) | (deblocking_code << 1);
+  // Bits [4, 7] are reserved
+  emit_u8(writer, flags)?;
+  let codec_code: u8 = match value.codec {
+    ast::VideoCodec::None => 0,
+    ast::VideoCodec::Jpeg => 1,
+    ast::VideoCodec::Sorenson => 2,
+    ast::VideoCodec::Screen => 3,
+    ast::VideoCodec::Vp6 => 4,
+    ast::VideoCodec::Vp6Alpha => 5,
+    ast::VideoCodec::Screen2 => 6,
+    ast::VideoCodec::Avc => 7,
+  };
+  emit_u8(writer, codec_code)
+}
+
 fn emit_do_abc_any<W: io::Write>(writer: &mut W, value: &ast::tags::DoAbc) -> io::Result<DoAbcVersion> {
   let version: DoAbcVersion = if let Some(ref header) = &value.header {
     emit_le_u32(writer, header.flags)?;
@@ -702,6 +813,11 @@ pub fn emit_do_action<W: io::Write>(writer: &mut W, value: &ast::tags::DoAction)
   writer.write_all(&value.actions)
 }
 
+pub fn emit_do_init_action<W: io::Write>(writer: &mut W, value: &ast::tags::DoInitAction) -> io::Result<()> {
+  emit_le_u16(writer, value.sprite_id)?;
+  writer.write_all(&value.actions)
+}
+
 pub fn emit_export_assets<W: io::Write>(writer: &mut W, value: &ast::tags::ExportAssets) -> io::Result<()> {
   emit_le_u16(writer, value.assets.len().try_into().unwrap())?;
   for asset in &value.assets {
@@ -934,6 +1050,47 @@ pub fn emit_set_background_color<W: io::Write>(
   emit_s_rgb8(writer, value.color)
 }
 
+pub fn emit_sound_stream_block<W: io::Write>(writer: &mut W, value: &ast::tags::SoundStreamBlock) -> io::Result<()> {
+  writer.write_all(&value.data)
+}
+
+pub enum SoundStreamHeadVersion {
+  SoundStreamHead1,
+  SoundStreamHead2,
+}
+
+pub fn emit_sound_stream_head_any<W: io::Write>(
+  writer: &mut W,
+  value: &ast::tags::SoundStreamHead,
+) -> io::Result<SoundStreamHeadVersion> {
+  // `SoundStreamHead` only supports compressed streams with a 16-bit playback
+  let version = match value.stream_format {
+    ast::AudioCodingFormat::Adpcm | ast::AudioCodingFormat::Mp3
+      if value.playback_sound_size == ast::SoundSize::SoundSize16 =>
+    {
+      SoundStreamHeadVersion::SoundStreamHead1
+    }
+    _ => SoundStreamHeadVersion::SoundStreamHead2,
+  };
+
+  #[allow(clippy::identity_op)]
+  let flags: u16 = 0
+    | (if value.playback_sound_type == ast::SoundType::Stereo { 1 << 0 } else { 0 })
+    | (if value.playback_sound_size == ast::SoundSize::SoundSize16 { 1 << 1 } else { 0 })
+    | (u16::from(sound_rate_to_code(value.playback_sound_rate)) << 2)
+    // Skip bits [4, 7]
+    | (if value.stream_sound_type == ast::SoundType::Stereo { 1 << 8 } else { 0 })
+    | (if value.stream_sound_size == ast::SoundSize::SoundSize16 { 1 << 9 } else { 0 })
+    | (u16::from(sound_rate_to_code(value.stream_sound_rate)) << 10)
+    | (u16::from(audio_coding_format_to_code(value.stream_format)) << 12);
+  emit_le_u16(writer, flags)?;
+  emit_le_u16(writer, value.stream_sample_count)?;
+  if value.stream_format == ast::AudioCodingFormat::Mp3 {
+    emit_le_i16(writer, value.latency_seek.unwrap_or(0))?;
+  }
+  Ok(version)
+}
+
 pub fn emit_start_sound<W: io::Write>(writer: &mut W, value: &ast::tags::StartSound) -> io::Result<()> {
   emit_le_u16(writer, value.sound_id)?;
   emit_sound_info(writer, &value.sound_info)?;
@@ -953,3 +1110,185 @@ pub fn emit_symbol_class<W: io::Write>(writer: &mut W, value: &ast::tags::Symbol
 pub fn emit_raw_body<W: io::Write>(writer: &mut W, value: &ast::tags::RawBody) -> io::Result<()> {
   writer.write_all(&value.data)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rect(x_max: i32, y_max: i32) -> ast::Rect {
+    ast::Rect {
+      x_min: 0,
+      x_max,
+      y_min: 0,
+      y_max,
+    }
+  }
+
+  fn solid(a: u8) -> ast::FillStyle {
+    ast::FillStyle::Solid(ast::fill_styles::Solid {
+      color: ast::StraightSRgba8 { r: 255, g: 0, b: 0, a },
+    })
+  }
+
+  fn line_style(cap: ast::CapStyle, fill: ast::FillStyle) -> ast::LineStyle {
+    ast::LineStyle {
+      width: 20,
+      start_cap: cap,
+      end_cap: cap,
+      join: ast::JoinStyle::Round,
+      no_h_scale: false,
+      no_v_scale: false,
+      no_close: false,
+      pixel_hinting: false,
+      fill,
+    }
+  }
+
+  fn style_change(new_styles: Option<ast::ShapeStyles>) -> ast::ShapeRecord {
+    ast::ShapeRecord::StyleChange(ast::shape_records::StyleChange {
+      move_to: Some(ast::Vector2D { x: 0, y: 0 }),
+      left_fill: None,
+      right_fill: None,
+      line_style: Some(1),
+      new_styles,
+    })
+  }
+
+  fn line(x: i32, y: i32) -> ast::ShapeRecord {
+    ast::ShapeRecord::Edge(ast::shape_records::Edge {
+      delta: ast::Vector2D { x, y },
+      control_delta: None,
+    })
+  }
+
+  fn define_shape(line_style: ast::LineStyle, new_styles: Option<ast::ShapeStyles>) -> ast::tags::DefineShape {
+    ast::tags::DefineShape {
+      id: 1,
+      bounds: rect(110, 60),
+      edge_bounds: None,
+      has_fill_winding: false,
+      has_non_scaling_strokes: false,
+      has_scaling_strokes: false,
+      shape: ast::Shape {
+        initial_styles: ast::ShapeStyles {
+          fill: vec![solid(255)],
+          line: vec![line_style],
+        },
+        records: vec![style_change(new_styles), line(100, 0), line(0, 50)],
+      },
+    }
+  }
+
+  /// Emits the tag, checks its code and returns the parsed tag.
+  fn round_trip(tag: ast::tags::DefineShape, code: u16) -> ast::tags::DefineShape {
+    let bytes = crate::emit_tag(&ast::Tag::DefineShape(tag), 10).unwrap();
+    assert_eq!(u16::from_le_bytes([bytes[0], bytes[1]]) >> 6, code);
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    match parsed {
+      Some(ast::Tag::DefineShape(parsed)) => parsed,
+      parsed => panic!("unexpected parsed tag: {:?}", parsed),
+    }
+  }
+
+  fn assert_round_trip(tag: ast::Tag) {
+    let bytes = crate::emit_tag(&tag, 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(tag));
+  }
+
+  #[test]
+  fn test_emit_define_button_color_transform() {
+    assert_round_trip(ast::Tag::DefineButtonColorTransform(
+      ast::tags::DefineButtonColorTransform {
+        button_id: 3,
+        transform: ast::ColorTransform {
+          red_mult: Sfixed8P8::from_epsilons(128),
+          blue_add: -20,
+          ..ast::ColorTransform::default()
+        },
+      },
+    ));
+  }
+
+  #[test]
+  fn test_emit_define_cff_font() {
+    let font = ast::tags::DefineCffFont {
+      id: 4,
+      font_name: String::from("Serif"),
+      is_bold: true,
+      is_italic: false,
+      data: Some(vec![0x4f, 0x54, 0x54, 0x4f, 0, 1]),
+    };
+    assert_round_trip(ast::Tag::DefineCffFont(font.clone()));
+    assert_round_trip(ast::Tag::DefineCffFont(ast::tags::DefineCffFont {
+      is_bold: false,
+      is_italic: true,
+      data: None,
+      ..font
+    }));
+  }
+
+  #[test]
+  fn test_emit_define_video_stream() {
+    let video_stream = ast::tags::DefineVideoStream {
+      id: 5,
+      frame_count: 120,
+      width: 320,
+      height: 240,
+      use_smoothing: true,
+      deblocking: ast::VideoDeblocking::Level3,
+      codec: ast::VideoCodec::Vp6Alpha,
+    };
+    assert_round_trip(ast::Tag::DefineVideoStream(video_stream.clone()));
+
+    let too_long = ast::tags::DefineVideoStream {
+      frame_count: 65536,
+      ..video_stream
+    };
+    let error = emit_define_video_stream(&mut Vec::new(), &too_long).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+  }
+
+  #[test]
+  fn test_emit_define_shape_version() {
+    // Opaque solid line fill with round caps
+    let tag = define_shape(line_style(ast::CapStyle::Round, solid(255)), None);
+    assert_eq!(round_trip(tag.clone(), 2), tag);
+
+    // New styles require `DefineShape2`
+    let styles = ast::ShapeStyles {
+      fill: Vec::new(),
+      line: vec![line_style(ast::CapStyle::Round, solid(255))],
+    };
+    let tag = define_shape(line_style(ast::CapStyle::Round, solid(255)), Some(styles));
+    assert_eq!(round_trip(tag.clone(), 22), tag);
+
+    // Transparent solid line fill
+    let tag = define_shape(line_style(ast::CapStyle::Round, solid(128)), None);
+    assert_eq!(round_trip(tag.clone(), 32), tag);
+  }
+
+  #[test]
+  fn test_emit_define_shape4_edge_bounds() {
+    // Square caps require `DefineShape4`: the edge bounds are computed without the line widths
+    let tag = define_shape(line_style(ast::CapStyle::Square, solid(255)), None);
+    let parsed = round_trip(tag.clone(), 83);
+    assert_eq!(
+      parsed,
+      ast::tags::DefineShape {
+        edge_bounds: Some(rect(100, 50)),
+        ..tag
+      }
+    );
+
+    // Provided edge bounds are kept
+    let tag = ast::tags::DefineShape {
+      edge_bounds: Some(rect(120, 70)),
+      has_fill_winding: true,
+      ..define_shape(line_style(ast::CapStyle::Round, solid(255)), None)
+    };
+    assert_eq!(round_trip(tag.clone(), 83), tag);
+  }
+}
@@ -65,6 +65,34 @@ pub(crate) fn text_renderer_to_code(value: ast::text::TextRenderer) -> u8 {
   }
 }
 
+/// Builds the `DefineFontInfo` code table of a glyph font: `characters` lists the character of each glyph, in glyph
+/// order.
+///
+/// The code table must be sorted: the characters must be in strictly ascending code unit order, so the glyphs of the
+/// font must be sorted by character.
+pub fn build_font_info_code_units(font: &ast::tags::DefineGlyphFont, characters: &str) -> io::Result<Vec<u16>> {
+  let code_units: Vec<u16> = characters.encode_utf16().collect();
+  if code_units.len() != characters.chars().count() {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "font info characters must be in the Basic Multilingual Plane",
+    ));
+  }
+  if code_units.len() != font.glyphs.len() {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "font info must have one character per glyph",
+    ));
+  }
+  if code_units.windows(2).any(|pair| pair[0] >= pair[1]) {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      "font info characters must be unique and in ascending order",
+    ));
+  }
+  Ok(code_units)
+}
+
 pub(crate) fn emit_text_record_string<W: io::Write>(
   writer: &mut W,
   value: &[ast::text::TextRecord],
@@ -108,7 +136,7 @@ pub(crate) fn emit_text_record<W: io::Write>(
     if with_alpha {
       emit_straight_s_rgba8(writer, color)?;
     } else {
-      assert!(color.a == u8::max_value());
+      assert!(color.a == u8::MAX);
       emit_s_rgb8(
         writer,
         ast::SRgb8 {
@@ -167,7 +195,7 @@ pub(crate) fn emit_offset_glyphs<W: io::Write>(writer: &mut W, value: &[ast::Gly
   let short_offset_table_size = offset_table_len * std::mem::size_of::<u16>();
   let max_offset_with_short_table = short_offset_table_size + glyph_writer.len();
 
-  let use_wide_offsets = max_offset_with_short_table > usize::from(u16::max_value());
+  let use_wide_offsets = max_offset_with_short_table > usize::from(u16::MAX);
 
   if use_wide_offsets {
     let wide_offset_table_size = offset_table_len * std::mem::size_of::<u32>();
@@ -220,3 +248,56 @@ pub(crate) fn emit_text_alignment<W: io::Write>(writer: &mut W, value: ast::text
   };
   emit_u8(writer, code)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn font_info(code_units: Vec<u16>, language: ast::LanguageCode, is_ansi: bool) -> ast::tags::DefineFontInfo {
+    ast::tags::DefineFontInfo {
+      font_id: 1,
+      font_name: String::from("Test"),
+      is_bold: false,
+      is_italic: false,
+      is_ansi,
+      is_shift_jis: false,
+      is_small: false,
+      language,
+      code_units,
+    }
+  }
+
+  #[test]
+  fn test_build_font_info_code_units() {
+    let font = ast::tags::DefineGlyphFont {
+      id: 1,
+      glyphs: vec![ast::Glyph { records: Vec::new() }; 3],
+    };
+    assert_eq!(
+      build_font_info_code_units(&font, "a\u{e9}\u{20ac}").unwrap(),
+      vec![0x61, 0xe9, 0x20ac]
+    );
+    assert!(build_font_info_code_units(&font, "ab").is_err());
+    assert!(build_font_info_code_units(&font, "aba").is_err());
+    assert!(build_font_info_code_units(&font, "bac").is_err());
+    assert!(build_font_info_code_units(&font, "a\u{1F600}").is_err());
+  }
+
+  #[test]
+  fn test_emit_define_font_info_code_units() {
+    for code_units in [vec![0x61, 0xe9], vec![0x61, 0x20ac]] {
+      let tag = ast::Tag::DefineFontInfo(font_info(code_units, ast::LanguageCode::Auto, true));
+      let bytes = crate::emit_tag(&tag, 10).unwrap();
+      let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+      assert!(rest.is_empty());
+      assert_eq!(parsed, Some(tag));
+    }
+  }
+
+  #[test]
+  fn test_emit_define_font_info2_rejects_encoding_flags() {
+    let tag = ast::Tag::DefineFontInfo(font_info(vec![0x61], ast::LanguageCode::Latin, true));
+    let err = crate::emit_tag(&tag, 10).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+  }
+}
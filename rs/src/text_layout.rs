@@ -0,0 +1,424 @@
+//! Lay out static text as `DefineText` records.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use swf_types as ast;
+
+#[derive(Debug)]
+pub enum TextLayoutError {
+  /// The font has no glyphs, code units or layout.
+  MissingLayout,
+  /// The font does not have a glyph for the character.
+  MissingGlyph(char),
+  /// A text record offset can't be encoded: it does not fit in 16 bits, or a line other than the first one has its
+  /// baseline at `y = 0` (an omitted offset keeps the previous baseline).
+  InvalidOffset,
+}
+
+impl fmt::Display for TextLayoutError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TextLayoutError::MissingLayout => f.write_str("font has no glyphs, code units or layout"),
+      TextLayoutError::MissingGlyph(c) => write!(f, "font has no glyph for character: {:?}", c),
+      TextLayoutError::InvalidOffset => f.write_str("text record offset can't be encoded"),
+    }
+  }
+}
+
+impl Error for TextLayoutError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextLayoutOptions {
+  /// Font size (EM square height), in twips.
+  pub font_size: u16,
+  pub color: ast::StraightSRgba8,
+  pub align: ast::text::TextAlignment,
+  /// Box containing the text, in twips. Lines are broken to fit its width; the first baseline is placed one ascent
+  /// below its top edge.
+  pub bounds: ast::Rect,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextLayout {
+  pub records: Vec<ast::text::TextRecord>,
+  /// Bounds of the glyphs, in twips.
+  pub bounds: ast::Rect,
+}
+
+/// A glyph of the text, with its advance (in EM units) including the kerning with the next glyph.
+#[derive(Copy, Clone, Debug)]
+struct LayoutGlyph {
+  index: usize,
+  advance: f64,
+  is_space: bool,
+}
+
+/// Lays out `text` with the font `font` (defined with the character id `font_id`).
+///
+/// Lines are broken at `\n` and, when they would overflow the width of the bounds, after spaces (or between
+/// characters for words wider than the bounds). Spaces at the end of wrapped lines are dropped. Justified lines
+/// distribute the remaining width between their spaces, except for the last line of each paragraph.
+pub fn layout_text(
+  text: &str,
+  font_id: u16,
+  font: &ast::tags::DefineFont,
+  options: &TextLayoutOptions,
+) -> Result<TextLayout, TextLayoutError> {
+  let (code_units, layout) = match (&font.code_units, &font.layout) {
+    (Some(code_units), Some(layout)) if layout.advances.len() >= code_units.len() => (code_units, layout),
+    _ => return Err(TextLayoutError::MissingLayout),
+  };
+  let glyph_indices: HashMap<u16, usize> = code_units
+    .iter()
+    .enumerate()
+    .map(|(index, code_unit)| (*code_unit, index))
+    .collect();
+  let kerning: HashMap<(u16, u16), i16> = layout
+    .kerning
+    .iter()
+    .map(|record| ((record.left, record.right), record.adjustment))
+    .collect();
+
+  let em_square_size = match font.em_square_size {
+    ast::text::EmSquareSize::EmSquareSize1024 => 1024.0,
+    ast::text::EmSquareSize::EmSquareSize20480 => 20480.0,
+  };
+  let scale = f64::from(options.font_size) / em_square_size;
+  let max_width = f64::from(options.bounds.x_max - options.bounds.x_min) / scale;
+  let line_height = f64::from(layout.ascent) + f64::from(layout.descent) + f64::from(layout.leading);
+
+  let mut lines: Vec<(Vec<LayoutGlyph>, bool)> = Vec::new();
+  for paragraph in text.split('\n') {
+    let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+    let mut glyphs: Vec<LayoutGlyph> = Vec::new();
+    let mut previous: Option<u16> = None;
+    for c in paragraph.chars() {
+      let code_unit = u16::try_from(u32::from(c)).map_err(|_| TextLayoutError::MissingGlyph(c))?;
+      let index = *glyph_indices.get(&code_unit).ok_or(TextLayoutError::MissingGlyph(c))?;
+      if let (Some(previous), Some(last)) = (previous, glyphs.last_mut()) {
+        last.advance += f64::from(kerning.get(&(previous, code_unit)).copied().unwrap_or(0));
+      }
+      glyphs.push(LayoutGlyph {
+        index,
+        advance: f64::from(layout.advances[index]),
+        is_space: c.is_whitespace(),
+      });
+      previous = Some(code_unit);
+    }
+    break_lines(&glyphs, max_width, &mut lines);
+  }
+
+  let mut records: Vec<ast::text::TextRecord> = Vec::new();
+  let mut bounds: Option<ast::Rect> = None;
+  // Pen position (in twips) after the last glyph entry.
+  let mut pen_x: i32 = 0;
+  let mut pen_y: i32 = 0;
+  for (line_index, (line, is_wrapped)) in lines.iter().enumerate() {
+    if line.is_empty() {
+      continue;
+    }
+    let width: f64 = line.iter().map(|glyph| glyph.advance).sum();
+    let space_count = line.iter().filter(|glyph| glyph.is_space).count();
+    let free_width = (max_width - width).max(0.0);
+    let (start_x, space_extra) = match options.align {
+      ast::text::TextAlignment::Left => (0.0, 0.0),
+      ast::text::TextAlignment::Right => (free_width, 0.0),
+      ast::text::TextAlignment::Center => (free_width / 2.0, 0.0),
+      ast::text::TextAlignment::Justify if *is_wrapped && space_count > 0 => (0.0, free_width / space_count as f64),
+      ast::text::TextAlignment::Justify => (0.0, 0.0),
+    };
+
+    let origin_x = f64::from(options.bounds.x_min);
+    let baseline =
+      f64::from(options.bounds.y_min) + (f64::from(layout.ascent) + line_index as f64 * line_height) * scale;
+    let line_x = (origin_x + start_x * scale).round() as i32;
+    let line_y = baseline.round() as i32;
+
+    // Omitted offsets keep the previous pen position: when a line starts at `x = 0`, the advance of the last glyph
+    // of the previous line moves the pen back to the start of the line.
+    if line_x == 0 && !records.is_empty() {
+      let last_entry = records.iter_mut().rev().find_map(|record| record.entries.last_mut());
+      if let Some(last_entry) = last_entry {
+        last_entry.advance -= pen_x;
+      }
+      pen_x = 0;
+    }
+
+    let mut entries: Vec<ast::text::GlyphEntry> = Vec::with_capacity(line.len());
+    let mut x = start_x;
+    let mut glyph_x = line_x;
+    for glyph in line.iter() {
+      extend_bounds(&mut bounds, layout.bounds.get(glyph.index), glyph_x, line_y, scale);
+      x += glyph.advance + if glyph.is_space { space_extra } else { 0.0 };
+      let next_x = (origin_x + x * scale).round() as i32;
+      entries.push(ast::text::GlyphEntry {
+        index: glyph.index,
+        advance: next_x - glyph_x,
+      });
+      glyph_x = next_x;
+    }
+
+    let is_first = records.is_empty();
+    if !is_first && line_y == 0 && pen_y != 0 {
+      return Err(TextLayoutError::InvalidOffset);
+    }
+    records.push(ast::text::TextRecord {
+      font_id: if is_first { Some(font_id) } else { None },
+      color: if is_first { Some(options.color) } else { None },
+      offset_x: if line_x != pen_x || is_first {
+        i16::try_from(line_x).map_err(|_| TextLayoutError::InvalidOffset)?
+      } else {
+        0
+      },
+      offset_y: if line_y != pen_y || is_first {
+        i16::try_from(line_y).map_err(|_| TextLayoutError::InvalidOffset)?
+      } else {
+        0
+      },
+      font_size: if is_first { Some(options.font_size) } else { None },
+      entries,
+    });
+    pen_x = glyph_x;
+    pen_y = line_y;
+  }
+
+  Ok(TextLayout {
+    records,
+    bounds: bounds.unwrap_or(ast::Rect {
+      x_min: options.bounds.x_min,
+      x_max: options.bounds.x_min,
+      y_min: options.bounds.y_min,
+      y_max: options.bounds.y_min,
+    }),
+  })
+}
+
+/// Splits a paragraph into lines fitting in `max_width` (in EM units).
+///
+/// Each line is pushed with a flag indicating if it was wrapped (as opposed to ending the paragraph).
+fn break_lines(glyphs: &[LayoutGlyph], max_width: f64, lines: &mut Vec<(Vec<LayoutGlyph>, bool)>) {
+  let mut start: usize = 0;
+  loop {
+    let mut width: f64 = 0.0;
+    let mut end = start;
+    let mut last_break: Option<usize> = None;
+    while end < glyphs.len() {
+      let glyph = glyphs[end];
+      if end > start && !glyph.is_space && width + glyph.advance > max_width {
+        break;
+      }
+      width += glyph.advance;
+      if glyph.is_space {
+        last_break = Some(end + 1);
+      }
+      end += 1;
+    }
+    let is_wrapped = end < glyphs.len();
+    if is_wrapped {
+      if let Some(last_break) = last_break {
+        end = last_break;
+      }
+    }
+    let mut line = glyphs[start..end].to_vec();
+    if is_wrapped {
+      while line.last().map(|glyph| glyph.is_space).unwrap_or(false) {
+        line.pop();
+      }
+    }
+    lines.push((line, is_wrapped));
+    if !is_wrapped {
+      return;
+    }
+    start = end;
+    while start < glyphs.len() && glyphs[start].is_space {
+      start += 1;
+    }
+  }
+}
+
+fn extend_bounds(bounds: &mut Option<ast::Rect>, glyph_bounds: Option<&ast::Rect>, x: i32, y: i32, scale: f64) {
+  let glyph_bounds = match glyph_bounds {
+    Some(glyph_bounds) if glyph_bounds.x_min != glyph_bounds.x_max || glyph_bounds.y_min != glyph_bounds.y_max => {
+      glyph_bounds
+    }
+    _ => return,
+  };
+  let x_min = x + (f64::from(glyph_bounds.x_min) * scale).floor() as i32;
+  let x_max = x + (f64::from(glyph_bounds.x_max) * scale).ceil() as i32;
+  let y_min = y + (f64::from(glyph_bounds.y_min) * scale).floor() as i32;
+  let y_max = y + (f64::from(glyph_bounds.y_max) * scale).ceil() as i32;
+  let rect = bounds.get_or_insert(ast::Rect {
+    x_min,
+    x_max,
+    y_min,
+    y_max,
+  });
+  rect.x_min = rect.x_min.min(x_min);
+  rect.x_max = rect.x_max.max(x_max);
+  rect.y_min = rect.y_min.min(y_min);
+  rect.y_max = rect.y_max.max(y_max);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn test_font() -> ast::tags::DefineFont {
+    let glyph_bounds = ast::Rect {
+      x_min: 0,
+      x_max: 512,
+      y_min: -700,
+      y_max: 0,
+    };
+    ast::tags::DefineFont {
+      id: 1,
+      font_name: String::from("Test"),
+      is_bold: false,
+      is_italic: false,
+      is_ansi: false,
+      is_small: false,
+      is_shift_jis: false,
+      em_square_size: ast::text::EmSquareSize::EmSquareSize1024,
+      language: ast::LanguageCode::Auto,
+      glyphs: Some(vec![ast::Glyph { records: Vec::new() }; 3]),
+      code_units: Some(" ab".encode_utf16().collect()),
+      layout: Some(ast::text::FontLayout {
+        ascent: 800,
+        descent: 200,
+        leading: 100,
+        advances: vec![256, 512, 512],
+        bounds: vec![
+          ast::Rect {
+            x_min: 0,
+            x_max: 0,
+            y_min: 0,
+            y_max: 0,
+          },
+          glyph_bounds,
+          glyph_bounds,
+        ],
+        kerning: vec![ast::text::KerningRecord {
+          left: u16::from(b'a'),
+          right: u16::from(b'b'),
+          adjustment: -128,
+        }],
+      }),
+    }
+  }
+
+  fn options(width: i32, align: ast::text::TextAlignment) -> TextLayoutOptions {
+    TextLayoutOptions {
+      font_size: 1024,
+      color: ast::StraightSRgba8 {
+        r: 255,
+        g: 0,
+        b: 0,
+        a: 255,
+      },
+      align,
+      bounds: ast::Rect {
+        x_min: 0,
+        x_max: width,
+        y_min: 0,
+        y_max: 4000,
+      },
+    }
+  }
+
+  fn entries(values: &[(usize, i32)]) -> Vec<ast::text::GlyphEntry> {
+    values
+      .iter()
+      .map(|(index, advance)| ast::text::GlyphEntry {
+        index: *index,
+        advance: *advance,
+      })
+      .collect()
+  }
+
+  fn round_trip(layout: &TextLayout) {
+    let tag = ast::Tag::DefineText(ast::tags::DefineText {
+      id: 2,
+      bounds: layout.bounds,
+      matrix: ast::Matrix::default(),
+      records: layout.records.clone(),
+    });
+    let bytes = crate::emit_tag(&tag, 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(tag));
+  }
+
+  #[test]
+  fn test_layout_text_kerning_and_bounds() {
+    let layout = layout_text("ab a", 1, &test_font(), &options(4000, ast::text::TextAlignment::Left)).unwrap();
+    round_trip(&layout);
+    assert_eq!(layout.records.len(), 1);
+    let record = &layout.records[0];
+    assert_eq!((record.font_id, record.font_size), (Some(1), Some(1024)));
+    assert_eq!((record.offset_x, record.offset_y), (0, 800));
+    assert_eq!(record.entries, entries(&[(1, 384), (2, 512), (0, 256), (1, 512)]));
+    assert_eq!(
+      layout.bounds,
+      ast::Rect {
+        x_min: 0,
+        x_max: 1664,
+        y_min: 100,
+        y_max: 800,
+      }
+    );
+  }
+
+  #[test]
+  fn test_layout_text_line_breaks() {
+    let layout = layout_text(
+      "ab ab\nb",
+      1,
+      &test_font(),
+      &options(1100, ast::text::TextAlignment::Left),
+    )
+    .unwrap();
+    round_trip(&layout);
+    assert_eq!(layout.records.len(), 3);
+    // The last advance of each line moves the pen back to `x = 0`
+    assert_eq!(layout.records[0].entries, entries(&[(1, 384), (2, -384)]));
+    assert_eq!((layout.records[1].offset_x, layout.records[1].offset_y), (0, 1900));
+    assert_eq!(layout.records[1].font_id, None);
+    assert_eq!(layout.records[1].entries, entries(&[(1, 384), (2, -384)]));
+    assert_eq!((layout.records[2].offset_x, layout.records[2].offset_y), (0, 3000));
+    assert_eq!(layout.bounds.y_max, 3000);
+  }
+
+  #[test]
+  fn test_layout_text_alignment() {
+    let layout = layout_text(
+      "ab ab",
+      1,
+      &test_font(),
+      &options(1100, ast::text::TextAlignment::Right),
+    )
+    .unwrap();
+    round_trip(&layout);
+    assert_eq!(layout.records[0].offset_x, 204);
+    assert_eq!(layout.records[1].offset_x, 204);
+
+    let layout = layout_text(
+      "a a a",
+      1,
+      &test_font(),
+      &options(1500, ast::text::TextAlignment::Justify),
+    )
+    .unwrap();
+    round_trip(&layout);
+    // First line: `a a` (1280 units) stretched to 1500 units, last line is not justified
+    assert_eq!(layout.records[0].entries, entries(&[(1, 512), (0, 476), (1, -988)]));
+    assert_eq!(layout.records[1].entries, entries(&[(1, 512)]));
+  }
+
+  #[test]
+  fn test_layout_text_missing_glyph() {
+    let result = layout_text("abc", 1, &test_font(), &options(1000, ast::text::TextAlignment::Left));
+    assert!(matches!(result, Err(TextLayoutError::MissingGlyph('c'))));
+  }
+}
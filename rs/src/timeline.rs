@@ -0,0 +1,310 @@
+//! Build `PlaceObject`, `RemoveObject` and `ShowFrame` sequences from the display list of each frame.
+//!
+//! Each frame declares the objects it displays, by depth. The tags of a frame only describe the difference with the
+//! previous frame: unchanged objects are kept, changed properties are updated in place and objects whose character
+//! changed are replaced.
+
+use std::collections::BTreeMap;
+use swf_types as ast;
+
+/// Object displayed at a given depth.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisplayObject {
+  pub character_id: u16,
+  pub matrix: ast::Matrix,
+  pub color_transform: Option<ast::ColorTransformWithAlpha>,
+  pub filters: Vec<ast::Filter>,
+  /// Instance name.
+  pub name: Option<String>,
+}
+
+impl DisplayObject {
+  /// Creates an object with the identity matrix and no color transform, filters or name.
+  pub fn new(character_id: u16) -> Self {
+    Self {
+      character_id,
+      matrix: ast::Matrix::default(),
+      color_transform: None,
+      filters: Vec::new(),
+      name: None,
+    }
+  }
+}
+
+/// Objects of a frame, by depth.
+pub type DisplayList = BTreeMap<u16, DisplayObject>;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Timeline {
+  pub frames: Vec<DisplayList>,
+}
+
+impl Timeline {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Appends a frame displaying the provided objects.
+  pub fn add_frame(&mut self, display_list: DisplayList) {
+    self.frames.push(display_list);
+  }
+
+  /// Returns the number of frames, for the header of the movie or sprite.
+  pub fn frame_count(&self) -> usize {
+    self.frames.len()
+  }
+
+  /// Returns the control tags of the timeline, each frame ending with a `ShowFrame` tag.
+  ///
+  /// The final `End` tag is not included.
+  pub fn emit_tags(&self) -> Vec<ast::Tag> {
+    let empty = DisplayList::new();
+    let mut tags: Vec<ast::Tag> = Vec::new();
+    let mut previous = &empty;
+    for display_list in self.frames.iter() {
+      tags.extend(diff_display_lists(previous, display_list));
+      tags.push(ast::Tag::ShowFrame);
+      previous = display_list;
+    }
+    tags
+  }
+}
+
+/// Returns the `PlaceObject` and `RemoveObject` tags transforming the `previous` display list into `next`.
+///
+/// Removals come first, then placements by increasing depth. Renamed objects are removed and placed again since
+/// the name of an instance cannot be updated.
+pub fn diff_display_lists(previous: &DisplayList, next: &DisplayList) -> Vec<ast::Tag> {
+  let mut removals: Vec<ast::Tag> = Vec::new();
+  let mut placements: Vec<ast::Tag> = Vec::new();
+
+  for (depth, old) in previous.iter() {
+    let is_kept = match next.get(depth) {
+      Some(new) => new.name == old.name,
+      None => false,
+    };
+    if !is_kept {
+      removals.push(ast::Tag::RemoveObject(ast::tags::RemoveObject {
+        character_id: None,
+        depth: *depth,
+      }));
+    }
+  }
+
+  for (depth, new) in next.iter() {
+    let old = previous.get(depth).filter(|old| old.name == new.name);
+    let place = match old {
+      Some(old) => update_object(*depth, old, new),
+      None => Some(place_object(*depth, new)),
+    };
+    if let Some(place) = place {
+      placements.push(ast::Tag::PlaceObject(place));
+    }
+  }
+
+  removals.extend(placements);
+  removals
+}
+
+fn place_object(depth: u16, new: &DisplayObject) -> ast::tags::PlaceObject {
+  ast::tags::PlaceObject {
+    is_update: false,
+    depth,
+    character_id: Some(new.character_id),
+    class_name: None,
+    matrix: if new.matrix == ast::Matrix::default() {
+      None
+    } else {
+      Some(new.matrix)
+    },
+    color_transform: new.color_transform,
+    ratio: None,
+    name: new.name.clone(),
+    clip_depth: None,
+    filters: if new.filters.is_empty() {
+      None
+    } else {
+      Some(new.filters.clone())
+    },
+    blend_mode: None,
+    bitmap_cache: None,
+    visible: None,
+    background_color: None,
+    clip_actions: None,
+  }
+}
+
+/// Returns the update of the object at `depth`, or `None` if it did not change.
+///
+/// A changed character replaces the object while keeping its other properties, so only the changed properties are
+/// set in both cases.
+fn update_object(depth: u16, old: &DisplayObject, new: &DisplayObject) -> Option<ast::tags::PlaceObject> {
+  if old == new {
+    return None;
+  }
+  let color_transform = if new.color_transform == old.color_transform {
+    None
+  } else {
+    // Clearing the color transform restores the identity
+    Some(new.color_transform.unwrap_or_default())
+  };
+  Some(ast::tags::PlaceObject {
+    is_update: true,
+    depth,
+    character_id: if new.character_id == old.character_id {
+      None
+    } else {
+      Some(new.character_id)
+    },
+    class_name: None,
+    matrix: if new.matrix == old.matrix {
+      None
+    } else {
+      Some(new.matrix)
+    },
+    color_transform,
+    ratio: None,
+    name: None,
+    clip_depth: None,
+    filters: if new.filters == old.filters {
+      None
+    } else {
+      Some(new.filters.clone())
+    },
+    blend_mode: None,
+    bitmap_cache: None,
+    visible: None,
+    background_color: None,
+    clip_actions: None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use swf_fixed::Sfixed8P8;
+
+  fn translated(character_id: u16, x: i32) -> DisplayObject {
+    DisplayObject {
+      matrix: ast::Matrix {
+        translate_x: x,
+        ..ast::Matrix::default()
+      },
+      ..DisplayObject::new(character_id)
+    }
+  }
+
+  fn get_places(tags: &[ast::Tag]) -> Vec<&ast::tags::PlaceObject> {
+    tags
+      .iter()
+      .filter_map(|tag| match tag {
+        ast::Tag::PlaceObject(place) => Some(place),
+        _ => None,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_timeline_emit_tags() {
+    let mut timeline = Timeline::new();
+    let mut frame = DisplayList::new();
+    frame.insert(2, translated(10, 0));
+    frame.insert(1, DisplayObject::new(11));
+    timeline.add_frame(frame.clone());
+
+    // Unchanged frame
+    timeline.add_frame(frame.clone());
+
+    // Move, replace and remove
+    frame.insert(2, translated(12, 40));
+    frame.remove(&1);
+    timeline.add_frame(frame.clone());
+
+    let tags = timeline.emit_tags();
+    assert_eq!(timeline.frame_count(), 3);
+    assert_eq!(tags.iter().filter(|tag| matches!(tag, ast::Tag::ShowFrame)).count(), 3);
+
+    let places = get_places(&tags);
+    assert_eq!(places.len(), 3);
+    assert_eq!(
+      (places[0].depth, places[0].is_update, places[0].character_id),
+      (1, false, Some(11))
+    );
+    assert_eq!(places[0].matrix, None);
+    assert_eq!(
+      (places[1].depth, places[1].is_update, places[1].character_id),
+      (2, false, Some(10))
+    );
+    assert_eq!(
+      (places[2].depth, places[2].is_update, places[2].character_id),
+      (2, true, Some(12))
+    );
+    assert_eq!(places[2].matrix.map(|matrix| matrix.translate_x), Some(40));
+    assert_eq!(places[2].color_transform, None);
+
+    // The third frame starts with the removal of depth 1
+    assert_eq!(tags[2], ast::Tag::ShowFrame);
+    assert_eq!(tags[3], ast::Tag::ShowFrame);
+    assert_eq!(
+      tags[4],
+      ast::Tag::RemoveObject(ast::tags::RemoveObject {
+        character_id: None,
+        depth: 1,
+      })
+    );
+
+    for tag in tags {
+      let bytes = crate::emit_tag(&tag, 10).unwrap();
+      let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+      assert!(rest.is_empty());
+      assert_eq!(parsed, Some(tag));
+    }
+  }
+
+  #[test]
+  fn test_diff_display_lists() {
+    let mut previous = DisplayList::new();
+    previous.insert(
+      1,
+      DisplayObject {
+        color_transform: Some(ast::ColorTransformWithAlpha {
+          alpha_mult: Sfixed8P8::from_epsilons(128),
+          ..ast::ColorTransformWithAlpha::default()
+        }),
+        ..DisplayObject::new(5)
+      },
+    );
+    previous.insert(
+      2,
+      DisplayObject {
+        name: Some(String::from("a")),
+        ..DisplayObject::new(6)
+      },
+    );
+
+    let mut next = DisplayList::new();
+    next.insert(1, DisplayObject::new(5));
+    next.insert(
+      2,
+      DisplayObject {
+        name: Some(String::from("b")),
+        ..DisplayObject::new(6)
+      },
+    );
+    let tags = diff_display_lists(&previous, &next);
+
+    // The renamed object is placed again, the cleared color transform is reset
+    assert_eq!(tags.len(), 3);
+    assert!(matches!(&tags[0], ast::Tag::RemoveObject(remove) if remove.depth == 2));
+    let places = get_places(&tags);
+    assert_eq!(
+      (places[0].depth, places[0].is_update, places[0].character_id),
+      (1, true, None)
+    );
+    assert_eq!(places[0].color_transform, Some(ast::ColorTransformWithAlpha::default()));
+    assert_eq!((places[1].depth, places[1].is_update), (2, false));
+    assert_eq!(places[1].name.as_deref(), Some("b"));
+
+    assert!(diff_display_lists(&next, &next).is_empty());
+  }
+}
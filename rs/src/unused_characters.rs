@@ -0,0 +1,254 @@
+//! Remove the character definitions that are not used by the movie.
+
+use crate::dictionary::{
+  get_attached_character, get_character_references, get_defined_character, get_tag_name, CharacterKind,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use swf_types as ast;
+
+/// Tags removed by `remove_unused_characters`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnusedCharacterStats {
+  /// Emitted size of the removed tags (including their headers) in bytes, by tag name.
+  pub saved_bytes: BTreeMap<&'static str, usize>,
+}
+
+impl UnusedCharacterStats {
+  /// Total number of bytes saved.
+  pub fn saved(&self) -> usize {
+    self.saved_bytes.values().sum()
+  }
+}
+
+/// Removes the characters that cannot be reached from the main timeline, `ExportAssets` or `SymbolClass`.
+///
+/// A character is reachable if it is referenced by a tag of the main timeline (`PlaceObject`, `StartSound`, ...) or
+/// an exported or bound character, or by the definition of a reachable character: its button records, text fonts,
+/// bitmap fills, sprite timeline or button sounds. The tags attached to the removed characters (`DefineFontInfo`,
+/// `DefineFontName`, `DefineFontAlignZones`, `DefineButtonSound`, `DefineScalingGrid`, `DoInitAction`, ...) are
+/// removed with them.
+///
+/// HTML text fields select their fonts by name (`<font face="...">`), possibly from HTML set at runtime: if a reachable
+/// `DefineDynamicText` uses HTML, all the fonts are kept.
+///
+/// Characters used only by ActionScript code (AVM1 `attachMovie` without export, AVM2 code without `SymbolClass`)
+/// are not detected and will be removed. This includes fonts selected by name with `TextFormat.font` on text fields
+/// without HTML.
+pub fn remove_unused_characters(movie: &mut ast::Movie) -> io::Result<UnusedCharacterStats> {
+  let mut definitions: HashMap<u16, Vec<u16>> = HashMap::new();
+  let mut roots: Vec<u16> = Vec::new();
+  let mut fonts: Vec<u16> = Vec::new();
+  let mut html_texts: HashSet<u16> = HashSet::new();
+  for tag in movie.tags.iter() {
+    if let Some((id, kind)) = get_defined_character(tag) {
+      match (kind, tag) {
+        (CharacterKind::Font, _) => fonts.push(id),
+        (_, ast::Tag::DefineDynamicText(ref text)) if text.html => {
+          html_texts.insert(id);
+        }
+        _ => {}
+      }
+      definitions.entry(id).or_default().extend(get_character_references(tag));
+    } else if let Some(id) = get_attached_character(tag) {
+      definitions.entry(id).or_default().extend(get_character_references(tag));
+    } else {
+      roots.extend(get_character_references(tag));
+    }
+  }
+
+  let mut reachable: HashSet<u16> = HashSet::new();
+  while let Some(id) = roots.pop() {
+    if reachable.insert(id) {
+      if let Some(references) = definitions.get(&id) {
+        roots.extend(references.iter().copied());
+      }
+      if html_texts.contains(&id) {
+        // Empties `fonts`: they are only added once
+        roots.append(&mut fonts);
+      }
+    }
+  }
+
+  let defined: HashSet<u16> = movie
+    .tags
+    .iter()
+    .filter_map(|tag| get_defined_character(tag).map(|(id, _)| id))
+    .collect();
+  let mut stats = UnusedCharacterStats::default();
+  let mut tags: Vec<ast::Tag> = Vec::with_capacity(movie.tags.len());
+  for tag in std::mem::take(&mut movie.tags) {
+    let id = get_defined_character(&tag)
+      .map(|(id, _)| id)
+      .or_else(|| get_attached_character(&tag));
+    let is_unused = match id {
+      Some(id) => defined.contains(&id) && !reachable.contains(&id),
+      None => false,
+    };
+    if is_unused {
+      let size = crate::emit_tag(&tag, movie.header.swf_version)?.len();
+      *stats.saved_bytes.entry(get_tag_name(&tag)).or_default() += size;
+    } else {
+      tags.push(tag);
+    }
+  }
+  movie.tags = tags;
+  Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::timeline::{DisplayList, DisplayObject, Timeline};
+
+  fn define_font(id: u16) -> ast::Tag {
+    ast::Tag::DefineGlyphFont(ast::tags::DefineGlyphFont { id, glyphs: Vec::new() })
+  }
+
+  fn define_font_name(font_id: u16) -> ast::Tag {
+    ast::Tag::DefineFontName(ast::tags::DefineFontName {
+      font_id,
+      name: String::from("Font"),
+      copyright: String::new(),
+    })
+  }
+
+  fn define_sprite(id: u16, character_ids: &[u16]) -> ast::Tag {
+    let mut display_list = DisplayList::new();
+    for (depth, character_id) in character_ids.iter().enumerate() {
+      display_list.insert(depth as u16 + 1, DisplayObject::new(*character_id));
+    }
+    let mut timeline = Timeline::new();
+    timeline.add_frame(display_list);
+    ast::Tag::DefineSprite(ast::tags::DefineSprite {
+      id,
+      frame_count: 1,
+      tags: timeline.emit_tags(),
+    })
+  }
+
+  #[test]
+  fn test_remove_unused_characters() {
+    let mut movie = ast::Movie {
+      header: ast::Header {
+        swf_version: 10,
+        frame_size: ast::Rect {
+          x_min: 0,
+          x_max: 0,
+          y_min: 0,
+          y_max: 0,
+        },
+        frame_rate: swf_fixed::Ufixed8P8::from_epsilons(256),
+        frame_count: 1,
+      },
+      tags: vec![
+        define_font(1),
+        define_font_name(1),
+        define_font(2),
+        define_font_name(2),
+        define_sprite(3, &[1]),
+        define_sprite(4, &[3]),
+        define_sprite(5, &[2]),
+        ast::Tag::ExportAssets(ast::tags::ExportAssets {
+          assets: vec![ast::NamedId {
+            id: 4,
+            name: String::from("clip"),
+          }],
+        }),
+        ast::Tag::ShowFrame,
+      ],
+    };
+    let removed = [define_font(2), define_font_name(2), define_sprite(5, &[2])];
+    let expected_stats: usize = removed.iter().map(|tag| crate::emit_tag(tag, 10).unwrap().len()).sum();
+
+    let stats = remove_unused_characters(&mut movie).unwrap();
+    assert_eq!(movie.tags.len(), 6);
+    assert!(removed.iter().all(|tag| !movie.tags.contains(tag)));
+    assert_eq!(stats.saved_bytes.len(), 3);
+    assert_eq!(stats.saved(), expected_stats);
+  }
+
+  #[test]
+  fn test_remove_unused_characters_keeps_fonts_of_html_text() {
+    let define_text = |id: u16, html: bool| {
+      ast::Tag::DefineDynamicText(ast::tags::DefineDynamicText {
+        id,
+        bounds: ast::Rect {
+          x_min: 0,
+          x_max: 1000,
+          y_min: 0,
+          y_max: 1000,
+        },
+        word_wrap: false,
+        multiline: false,
+        password: false,
+        readonly: true,
+        auto_size: false,
+        no_select: false,
+        border: false,
+        was_static: false,
+        html,
+        use_glyph_font: true,
+        font_id: Some(1),
+        font_class: None,
+        font_size: Some(240),
+        color: None,
+        max_length: None,
+        align: ast::text::TextAlignment::Left,
+        margin_left: 0,
+        margin_right: 0,
+        indent: 0,
+        leading: 0,
+        variable_name: None,
+        text: Some(String::from(r#"<font face="Font">a</font>"#)),
+      })
+    };
+    let movie = |html: bool| ast::Movie {
+      header: ast::Header {
+        swf_version: 10,
+        frame_size: ast::Rect {
+          x_min: 0,
+          x_max: 0,
+          y_min: 0,
+          y_max: 0,
+        },
+        frame_rate: swf_fixed::Ufixed8P8::from_epsilons(256),
+        frame_count: 1,
+      },
+      tags: vec![
+        define_font(1),
+        define_font(2),
+        define_font_name(2),
+        define_text(3, html),
+        define_sprite(4, &[3]),
+        define_sprite(5, &[2]),
+        define_sprite(6, &[4]),
+        ast::Tag::ExportAssets(ast::tags::ExportAssets {
+          assets: vec![ast::NamedId {
+            id: 6,
+            name: String::from("clip"),
+          }],
+        }),
+      ],
+    };
+
+    // The font 2 may be selected by name, through the sprites 6 and 4
+    let mut html_movie = movie(true);
+    let mut expected = html_movie.tags.clone();
+    expected.retain(|tag| *tag != define_sprite(5, &[2]));
+    let stats = remove_unused_characters(&mut html_movie).unwrap();
+    assert_eq!(html_movie.tags, expected);
+    assert_eq!(
+      stats.saved_bytes.keys().copied().collect::<Vec<_>>(),
+      vec!["DefineSprite"]
+    );
+
+    let mut text_movie = movie(false);
+    let stats = remove_unused_characters(&mut text_movie).unwrap();
+    assert_eq!(text_movie.tags.len(), 5);
+    assert_eq!(
+      stats.saved_bytes.keys().copied().collect::<Vec<_>>(),
+      vec!["DefineFontName", "DefineGlyphFont", "DefineSprite"]
+    );
+  }
+}
@@ -0,0 +1,236 @@
+//! Import WAV files as `DefineSound` tags.
+//!
+//! Only uncompressed PCM files (8-bit or 16-bit, mono or stereo) are supported. The samples are stored without
+//! conversion, so the sample rate must match a SWF sound rate; use `sound_encoder::encode_define_sound` to resample
+//! sounds with other rates.
+
+use std::error::Error;
+use std::fmt;
+
+use swf_types as ast;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WavError {
+  /// The input is not a RIFF/WAVE file, or one of its chunks is truncated.
+  InvalidFile,
+  /// The file does not have a `fmt ` chunk before its `data` chunk.
+  MissingFormat,
+  /// The file does not have a `data` chunk.
+  MissingData,
+  /// The audio format is not PCM.
+  UnsupportedFormat(u16),
+  /// Only mono and stereo sounds are supported.
+  UnsupportedChannelCount(u16),
+  /// Only 8-bit and 16-bit samples are supported.
+  UnsupportedBitsPerSample(u16),
+  /// The sample rate does not match a SWF sound rate (5512, 11025, 22050 or 44100 Hz).
+  UnsupportedSampleRate(u32),
+}
+
+impl fmt::Display for WavError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      WavError::InvalidFile => f.write_str("invalid WAV file"),
+      WavError::MissingFormat => f.write_str("WAV file has no format chunk"),
+      WavError::MissingData => f.write_str("WAV file has no data chunk"),
+      WavError::UnsupportedFormat(format) => write!(f, "unsupported WAV format (expected PCM): 0x{:04x}", format),
+      WavError::UnsupportedChannelCount(count) => {
+        write!(f, "unsupported WAV channel count (expected 1 or 2): {}", count)
+      }
+      WavError::UnsupportedBitsPerSample(bits) => {
+        write!(f, "unsupported WAV sample size (expected 8 or 16 bits): {}", bits)
+      }
+      WavError::UnsupportedSampleRate(rate) => write!(
+        f,
+        "unsupported WAV sample rate (expected 5512, 11025, 22050 or 44100 Hz): {} Hz",
+        rate
+      ),
+    }
+  }
+}
+
+impl Error for WavError {}
+
+#[derive(Copy, Clone, Debug)]
+struct WavFormat {
+  sound_type: ast::SoundType,
+  sound_size: ast::SoundSize,
+  sound_rate: ast::SoundRate,
+  block_align: usize,
+}
+
+/// Converts a PCM WAV file to a `DefineSound` tag with the provided character id.
+///
+/// A `data` chunk whose declared size exceeds the file (as written by some streaming encoders) is read up to the end
+/// of the file; an incomplete trailing sample frame is dropped.
+pub fn wav_to_define_sound(id: u16, data: &[u8]) -> Result<ast::tags::DefineSound, WavError> {
+  if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+    return Err(WavError::InvalidFile);
+  }
+
+  let mut format: Option<WavFormat> = None;
+  let mut offset: usize = 12;
+  while offset + 8 <= data.len() {
+    let chunk_id = &data[offset..offset + 4];
+    let chunk_size = read_u32(data, offset + 4) as usize;
+    let chunk_start = offset + 8;
+    if chunk_id == b"data" {
+      let format = format.ok_or(WavError::MissingFormat)?;
+      let chunk_end = chunk_start.saturating_add(chunk_size).min(data.len());
+      let mut samples = &data[chunk_start..chunk_end];
+      samples = &samples[..samples.len() - samples.len() % format.block_align];
+      let sample_count = u32::try_from(samples.len() / format.block_align).map_err(|_| WavError::InvalidFile)?;
+      return Ok(ast::tags::DefineSound {
+        id,
+        sound_type: format.sound_type,
+        sound_size: format.sound_size,
+        sound_rate: format.sound_rate,
+        format: ast::AudioCodingFormat::UncompressedLittleEndian,
+        sample_count,
+        data: samples.to_vec(),
+      });
+    }
+    let chunk = data
+      .get(chunk_start..chunk_start.saturating_add(chunk_size))
+      .ok_or(WavError::InvalidFile)?;
+    if chunk_id == b"fmt " {
+      format = Some(parse_format(chunk)?);
+    }
+    // Chunks are padded to an even size
+    offset = chunk_start + chunk_size + (chunk_size & 1);
+  }
+  Err(WavError::MissingData)
+}
+
+fn parse_format(chunk: &[u8]) -> Result<WavFormat, WavError> {
+  if chunk.len() < 16 {
+    return Err(WavError::InvalidFile);
+  }
+  let mut audio_format = read_u16(chunk, 0);
+  let channels = read_u16(chunk, 2);
+  let sample_rate = read_u32(chunk, 4);
+  let bits_per_sample = read_u16(chunk, 14);
+  if audio_format == WAVE_FORMAT_EXTENSIBLE {
+    // The format is the start of the sub-format GUID
+    if chunk.len() < 26 {
+      return Err(WavError::InvalidFile);
+    }
+    audio_format = read_u16(chunk, 24);
+  }
+  if audio_format != WAVE_FORMAT_PCM {
+    return Err(WavError::UnsupportedFormat(audio_format));
+  }
+
+  let sound_type = match channels {
+    1 => ast::SoundType::Mono,
+    2 => ast::SoundType::Stereo,
+    _ => return Err(WavError::UnsupportedChannelCount(channels)),
+  };
+  let sound_size = match bits_per_sample {
+    8 => ast::SoundSize::SoundSize8,
+    16 => ast::SoundSize::SoundSize16,
+    _ => return Err(WavError::UnsupportedBitsPerSample(bits_per_sample)),
+  };
+  let sound_rate = match sample_rate {
+    5512 | 5513 => ast::SoundRate::SoundRate5500,
+    11025 => ast::SoundRate::SoundRate11000,
+    22050 => ast::SoundRate::SoundRate22000,
+    44100 => ast::SoundRate::SoundRate44000,
+    _ => return Err(WavError::UnsupportedSampleRate(sample_rate)),
+  };
+  Ok(WavFormat {
+    sound_type,
+    sound_size,
+    sound_rate,
+    block_align: usize::from(channels) * usize::from(bits_per_sample / 8),
+  })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+  u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+  u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn wav(channels: u16, sample_rate: u32, bits_per_sample: u16, samples: &[u8]) -> Vec<u8> {
+    let block_align = channels * bits_per_sample / 8;
+    let mut fmt: Vec<u8> = Vec::new();
+    fmt.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+    fmt.extend_from_slice(&channels.to_le_bytes());
+    fmt.extend_from_slice(&sample_rate.to_le_bytes());
+    fmt.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+    fmt.extend_from_slice(&block_align.to_le_bytes());
+    fmt.extend_from_slice(&bits_per_sample.to_le_bytes());
+
+    let mut body: Vec<u8> = b"WAVE".to_vec();
+    for (id, chunk) in [(b"fmt ", fmt.as_slice()), (b"LIST", &[1, 2, 3]), (b"data", samples)] {
+      body.extend_from_slice(id);
+      body.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+      body.extend_from_slice(chunk);
+      if chunk.len() % 2 == 1 {
+        body.push(0);
+      }
+    }
+    let mut file: Vec<u8> = b"RIFF".to_vec();
+    file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    file.extend(body);
+    file
+  }
+
+  #[test]
+  fn test_wav_to_define_sound() {
+    let tag = wav_to_define_sound(3, &wav(2, 22050, 16, &[1, 0, 2, 0, 3, 0, 4, 0])).unwrap();
+    assert_eq!(
+      tag,
+      ast::tags::DefineSound {
+        id: 3,
+        sound_type: ast::SoundType::Stereo,
+        sound_size: ast::SoundSize::SoundSize16,
+        sound_rate: ast::SoundRate::SoundRate22000,
+        format: ast::AudioCodingFormat::UncompressedLittleEndian,
+        sample_count: 2,
+        data: vec![1, 0, 2, 0, 3, 0, 4, 0],
+      }
+    );
+
+    let tag = wav_to_define_sound(3, &wav(1, 5512, 8, &[128, 129, 130])).unwrap();
+    assert_eq!(tag.sound_size, ast::SoundSize::SoundSize8);
+    assert_eq!(tag.sound_rate, ast::SoundRate::SoundRate5500);
+    assert_eq!(tag.sample_count, 3);
+
+    let bytes = crate::emit_tag(&ast::Tag::DefineSound(tag.clone()), 10).unwrap();
+    let (rest, parsed) = swf_parser::parse_tag(&bytes, 10);
+    assert!(rest.is_empty());
+    assert_eq!(parsed, Some(ast::Tag::DefineSound(tag)));
+  }
+
+  #[test]
+  fn test_wav_errors() {
+    assert_eq!(
+      wav_to_define_sound(1, &wav(1, 48000, 16, &[0, 0])),
+      Err(WavError::UnsupportedSampleRate(48000))
+    );
+    assert_eq!(
+      wav_to_define_sound(1, &wav(1, 44100, 24, &[0, 0, 0])),
+      Err(WavError::UnsupportedBitsPerSample(24))
+    );
+    assert_eq!(
+      wav_to_define_sound(1, &wav(6, 44100, 16, &[0; 12])),
+      Err(WavError::UnsupportedChannelCount(6))
+    );
+    assert_eq!(
+      wav_to_define_sound(1, b"RIFF\x04\0\0\0WAVE"),
+      Err(WavError::MissingData)
+    );
+    assert_eq!(wav_to_define_sound(1, b"not a wav file"), Err(WavError::InvalidFile));
+  }
+}